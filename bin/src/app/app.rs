@@ -1,9 +1,34 @@
 use clap::{App, Arg, AppSettings};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use app::completions::SHELLS;
+
+/// Parses the value of --since, either a day (2021-02-24, midnight UTC)
+/// or a RFC 3339 date
+pub fn parse_since(value: &str) -> Result<NaiveDateTime, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|day| day.and_hms(0, 0, 0))
+        .or_else(|_| DateTime::parse_from_rfc3339(value).map(|date| date.naive_utc()))
+        .map_err(|_| format!("{} is neither a day like 2021-02-24 nor a RFC 3339 date", value))
+}
 
 pub fn gen_app() -> App<'static> {
     let app = App::new("NotesManager")
         .setting(AppSettings::ArgRequiredElseHelp)
         .version(env!("CARGO_PKG_VERSION"))
+        .arg(Arg::new("db")
+            .long("db")
+            .about("Path of the notes database, overrides db_path of the config file")
+            .takes_value(true)
+            .global(true)
+        )
+        .arg(Arg::new("output")
+            .long("output")
+            .about("Format of the results, json prints one document per command to stdout")
+            .takes_value(true)
+            .possible_values(&["text", "json"])
+            .default_value("text")
+            .global(true)
+        )
         .subcommand(App::new("list")
             .about("Lists all available notes")
             .arg(Arg::new("uuid")
@@ -24,6 +49,31 @@ pub fn gen_app() -> App<'static> {
                 .about("Prints deleted notes only")
                 .required(false)
             )
+            .arg(Arg::new("tag")
+                .short('t')
+                .long("tag")
+                .about("Prints notes that contain this hashtag only")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::new("since")
+                .long("since")
+                .about("Prints notes that got modified since this day (2021-02-24) or RFC 3339 date only")
+                .required(false)
+                .takes_value(true)
+                .validator(parse_since)
+            )
+            .arg(Arg::new("sort")
+                .short('s')
+                .long("sort")
+                .about("Order of the notes, modified and created list the newest notes first")
+                .takes_value(true)
+                .possible_values(&["folder", "modified", "created"])
+                .default_value("folder")
+            )
+        )
+        .subcommand(App::new("tags")
+            .about("Lists all hashtags together with the number of notes that contain them")
         )
         .about("Interface for interacting with Apple Notes on Linux")
         .subcommand(App::new("edit")
@@ -33,6 +83,25 @@ pub fn gen_app() -> App<'static> {
                 .takes_value(true)
                 .about("Subject or UUID of the note that should be edited")
             )
+            .arg(Arg::new("stdin")
+                .long("stdin")
+                .about("Replaces the text of the note with stdin instead of opening the editor")
+                .required(false)
+            )
+        )
+        .subcommand(App::new("append")
+            .about("Appends text to the end of a note without opening the editor")
+            .arg(Arg::new("path")
+                .required(true)
+                .takes_value(true)
+                .about("Subject or UUID of the note the text should be appended to")
+            )
+            .arg(Arg::new("text")
+                .required(false)
+                .takes_value(true)
+                .multiple(true)
+                .about("Text that should be appended, stdin is read if omitted")
+            )
         )
         .subcommand(App::new("sync")
             .about("Syncs local with remote notes and vice versa")
@@ -72,6 +141,223 @@ pub fn gen_app() -> App<'static> {
         .subcommand(App::new("backup")
             .about("Duplicates current note tree on the imap server")
         )
+        .subcommand(App::new("export")
+            .about("Exports all notes into a directory")
+            .arg(Arg::new("format")
+                .long("format")
+                .about("Format of the exported files")
+                .takes_value(true)
+                .possible_values(&["markdown"])
+                .default_value("markdown")
+            )
+            .arg(Arg::new("prune")
+                .long("prune")
+                .about("Removes previously exported files of notes that got deleted or renamed")
+            )
+            .arg(Arg::new("dir")
+                .required(true)
+                .takes_value(true)
+                .about("Directory the notes should be written to")
+            )
+        )
+        .subcommand(App::new("import")
+            .about("Creates new notes from a directory of markdown or text files")
+            .arg(Arg::new("skip-imported")
+                .short('s')
+                .long("skip-imported")
+                .about("Skips files whose recorded uuid is already present")
+                .required(false)
+            )
+            .arg(Arg::new("record")
+                .short('r')
+                .long("record")
+                .about("Records the uuid of the created note inside the front matter of the file")
+                .required(false)
+            )
+            .arg(Arg::new("dir")
+                .required(true)
+                .takes_value(true)
+                .about("Directory that contains the files that should be imported")
+            )
+        )
+        .subcommand(App::new("restore")
+            .about("Restores notes from a backup")
+            .arg(Arg::new("archive")
+                .short('a')
+                .long("archive")
+                .about("Restores from a copy of the notes database instead of the imap backup folders")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::new("list")
+                .short('l')
+                .long("list")
+                .about("Lists the notes inside the backup without restoring them")
+                .required(false)
+            )
+            .arg(Arg::new("all")
+                .long("all")
+                .about("Restores every note inside the backup")
+                .required(false)
+                .conflicts_with("uuid")
+            )
+            .arg(Arg::new("on-clash")
+                .long("on-clash")
+                .about("What should happen if a restored note is already present locally")
+                .takes_value(true)
+                .possible_values(&["keep-both", "overwrite"])
+                .default_value("keep-both")
+            )
+            .arg(Arg::new("uuid")
+                .multiple(true)
+                .takes_value(true)
+                .required_unless_present_any(vec!["all", "list"])
+                .about("UUIDs of the notes that should be restored")
+            )
+        )
+        .subcommand(App::new("history")
+            .about("Lists the previous versions of a note")
+            .arg(Arg::new("note")
+                .required(true)
+                .takes_value(true)
+                .about("Uuid or subject of the note")
+            )
+        )
+        .subcommand(App::new("diff")
+            .about("Shows the changes between a previous version and the current content of a note")
+            .arg(Arg::new("note")
+                .required(true)
+                .takes_value(true)
+                .about("Uuid or subject of the note")
+            )
+            .arg(Arg::new("revision")
+                .required(true)
+                .takes_value(true)
+                .validator(|value| value.parse::<i64>())
+                .about("Revision number as shown by history")
+            )
+        )
+        .subcommand(App::new("revert")
+            .about("Restores a previous version of a note, the change gets uploaded with the next sync")
+            .arg(Arg::new("note")
+                .required(true)
+                .takes_value(true)
+                .about("Uuid or subject of the note")
+            )
+            .arg(Arg::new("revision")
+                .required(true)
+                .takes_value(true)
+                .validator(|value| value.parse::<i64>())
+                .about("Revision number as shown by history")
+            )
+        )
+        .subcommand(App::new("search")
+            .about("Searches the text of all notes")
+            .arg(Arg::new("folder")
+                .short('f')
+                .long("folder")
+                .about("Only searches notes inside this folder and its subfolders, can be passed multiple times")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::new("limit")
+                .short('n')
+                .long("limit")
+                .about("Maximum number of notes to show")
+                .takes_value(true)
+                .validator(|value| value.parse::<usize>())
+            )
+            .arg(Arg::new("query")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .about("Terms that have to match, use \"multiple words\" for phrases and term* for prefixes")
+            )
+        )
+        .subcommand(App::new("mirror")
+            .about("Mirrors all notes as markdown files into a directory and saves changes of the files")
+            .arg(Arg::new("interval")
+                .short('i')
+                .long("interval")
+                .about("Seconds between writing changes of the database back to the files")
+                .takes_value(true)
+                .default_value("5")
+                .validator(|value| value.parse::<u64>())
+            )
+            .arg(Arg::new("dir")
+                .required(true)
+                .takes_value(true)
+                .about("Directory the notes should be mirrored to")
+            )
+        )
+        .subcommand(App::new("init")
+            .about("Asks for the account settings, tests the login and writes the config file")
+            .arg(Arg::new("force")
+                .long("force")
+                .about("Replaces an existing config file")
+                .required(false)
+            )
+        )
+        .subcommand(App::new("rpc")
+            .about("Answers JSON-RPC 2.0 requests on stdin and stdout, for editor plugins")
+        )
+        .subcommand(App::new("serve")
+            .about("Serves the notes as REST API, every request needs the token of the config file")
+            .arg(Arg::new("listen")
+                .short('l')
+                .long("listen")
+                .about("Address and port the server listens on")
+                .takes_value(true)
+                .default_value("127.0.0.1:8421")
+            )
+        )
+        .subcommand(App::new("completions")
+            .about("Prints the shell completion script")
+            .arg(Arg::new("shell")
+                .required(true)
+                .takes_value(true)
+                .possible_values(&SHELLS)
+                .about("Shell the script is generated for")
+            )
+        )
+        .subcommand(App::new("config")
+            .about("Inspects the config file")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(App::new("path")
+                .about("Prints the location and format of the config file")
+            )
+            .subcommand(App::new("check")
+                .about("Validates the config file and lists every wrong key")
+            )
+            .subcommand(App::new("show")
+                .about("Prints the effective settings as TOML config, the password is masked")
+            )
+        )
+        .subcommand(App::new("trash")
+            .about("Manages notes that got deleted locally or on another device")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(App::new("list")
+                .about("Lists the notes inside the trash")
+            )
+            .subcommand(App::new("restore")
+                .about("Restores notes from the trash, they get uploaded with the next sync")
+                .arg(Arg::new("uuid")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple(true)
+                    .about("UUIDs of the notes that should be restored")
+                )
+            )
+            .subcommand(App::new("purge")
+                .about("Removes notes that exceeded the retention period from the trash")
+                .arg(Arg::new("all")
+                    .long("all")
+                    .about("Removes every note from the trash")
+                    .required(false)
+                )
+            )
+        )
         .subcommand(App::new("new")
             .about("Creates a new note")
             .arg(Arg::new("folder")
@@ -81,11 +367,32 @@ pub fn gen_app() -> App<'static> {
                 .required(false)
                 .takes_value(true)
             )
+            .arg(Arg::new("stdin")
+                .long("stdin")
+                .about("Reads the text of the note from stdin instead of opening the editor")
+                .required(false)
+            )
             .arg(Arg::new("title")
-                .required(true)
+                .required_unless_present("stdin")
                 .takes_value(true)
-                .about("Title of the new note")
+                .about("Title of the new note, with --stdin the first line of the input is used if omitted")
             )
         );
     app
 }
+
+/// gen_app() plus hidden subcommands that only the completion scripts call.
+/// They are kept out of gen_app(), because clap_generate neither skips hidden
+/// subcommands nor copes with the underscores in their names
+pub fn gen_app_with_helpers() -> App<'static> {
+    gen_app()
+        .subcommand(App::new("__complete-notes")
+            .about("Prints the titles and uuids of all notes separated by NUL for the completion scripts")
+            .setting(AppSettings::Hidden)
+            .arg(Arg::new("deleted")
+                .long("deleted")
+                .about("Prints deleted notes only")
+                .required(false)
+            )
+        )
+}
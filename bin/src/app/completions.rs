@@ -0,0 +1,175 @@
+use std::io::Write;
+use clap_generate::generate;
+use clap_generate::generators::{Bash, Fish, Zsh};
+use app::app::gen_app;
+
+const BIN_NAME: &str = "apnotes";
+
+/// Shells completion scripts can be generated for
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// Subcommands whose first positional argument is a note
+const NOTE_COMMANDS: [&str; 8] = ["edit", "append", "delete", "merge", "print", "history", "diff", "revert"];
+
+/// Subcommands whose first positional argument is a deleted note
+const DELETED_NOTE_COMMANDS: [&str; 1] = ["undelete"];
+
+/// Writes the completion script for the shell. The scripts are generated from
+/// gen_app(), note arguments are completed by calling apnotes __complete-notes
+pub fn write_completions(shell: &str, out: &mut dyn Write) -> std::io::Result<()> {
+    let mut app = gen_app();
+    let mut script = Vec::new();
+    match shell {
+        "bash" => generate::<Bash, _>(&mut app, BIN_NAME, &mut script),
+        "zsh" => generate::<Zsh, _>(&mut app, BIN_NAME, &mut script),
+        "fish" => generate::<Fish, _>(&mut app, BIN_NAME, &mut script),
+        _ => unreachable!()
+    }
+    let script = String::from_utf8_lossy(&script);
+
+    let script = match shell {
+        "bash" => format!("{}\n{}", script, bash_notes()),
+        "zsh" => zsh_notes(&script),
+        _ => format!("{}\n{}", script, fish_notes())
+    };
+    out.write_all(script.as_bytes())
+}
+
+/// Wraps the generated _apnotes, which only knows flags and subcommands
+fn bash_notes() -> String {
+    format!(r#"_{name}_notes() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" cmd="" positionals=0 i word note
+    local -a flags=()
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        word="${{COMP_WORDS[i]}}"
+        case "${{word}}" in
+            --db)
+                flags+=(--db "${{COMP_WORDS[i+1]}}")
+                (( i++ ))
+                ;;
+            --output)
+                (( i++ ))
+                ;;
+            -*)
+                ;;
+            *)
+                if [[ -z "${{cmd}}" ]]; then cmd="${{word}}"; else (( positionals++ )); fi
+                ;;
+        esac
+    done
+
+    if [[ ${{cur}} == -* || ${{positionals}} -ne 0 ]]; then
+        _{name}
+        return
+    fi
+    case "${{cmd}}" in
+        {notes})
+            ;;
+        {deleted})
+            flags+=(--deleted)
+            ;;
+        *)
+            _{name}
+            return
+            ;;
+    esac
+
+    COMPREPLY=()
+    while IFS= read -r -d '' note; do
+        if [[ ${{note}} == "${{cur}}"* ]]; then
+            COMPREPLY+=("$(printf '%q' "${{note}}")")
+        fi
+    done < <({name} __complete-notes "${{flags[@]}}" 2>/dev/null)
+}}
+
+complete -F _{name}_notes -o bashdefault -o default {name}
+"#, name = BIN_NAME, notes = NOTE_COMMANDS.join("|"), deleted = DELETED_NOTE_COMMANDS.join("|"))
+}
+
+/// Points the note arguments of the generated script to _apnotes_notes. The
+/// helper is defined before the generated code runs _apnotes
+fn zsh_notes(script: &str) -> String {
+    let helper = format!(r#"
+_{name}_notes() {{
+    local -a notes flags
+    if [[ ${{words[1]}} == ({deleted}) ]]; then
+        flags=(--deleted)
+    fi
+    notes=(${{(0)"$(_call_program notes {name} __complete-notes $flags 2>/dev/null)"}})
+    compadd -a notes
+}}
+"#, name = BIN_NAME, deleted = DELETED_NOTE_COMMANDS.join("|"));
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut subcommand = String::new();
+    for line in script.lines() {
+        let trimmed = line.trim_start();
+        if let Some(name) = trimmed.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+            subcommand = name.to_string();
+        }
+
+        let is_note_argument = (trimmed.starts_with("':path -- ") || trimmed.starts_with("':note -- "))
+            && (NOTE_COMMANDS.contains(&subcommand.as_str()) || DELETED_NOTE_COMMANDS.contains(&subcommand.as_str()));
+        match line.strip_suffix(":' \\") {
+            Some(start) if is_note_argument => lines.push(format!("{}:_{}_notes' \\", start, BIN_NAME)),
+            _ => lines.push(line.to_string())
+        }
+
+        if lines.len() == 1 {
+            lines.push(helper.clone());
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// The fish generator skips positional arguments, so notes are offered after
+/// every note subcommand
+fn fish_notes() -> String {
+    format!(
+        "complete -c {name} -n \"__fish_seen_subcommand_from {notes}\" -f -a \"({name} __complete-notes 2>/dev/null | string split0)\"\n\
+         complete -c {name} -n \"__fish_seen_subcommand_from {deleted}\" -f -a \"({name} __complete-notes --deleted 2>/dev/null | string split0)\"\n",
+        name = BIN_NAME,
+        notes = NOTE_COMMANDS.join(" "),
+        deleted = DELETED_NOTE_COMMANDS.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod completions_tests {
+    use super::*;
+
+    fn completions(shell: &str) -> String {
+        let mut script = Vec::new();
+        write_completions(shell, &mut script).unwrap();
+        String::from_utf8(script).unwrap()
+    }
+
+    /// The note argument of a note command completes notes, arguments of other commands stay untouched
+    #[test]
+    fn zsh_completes_notes() {
+        let script = completions("zsh");
+        assert!(script.contains(&format!("_{}_notes() {{", BIN_NAME)));
+
+        let mut subcommand = "";
+        let mut completed = vec![];
+        for line in script.lines() {
+            let trimmed = line.trim_start();
+            if let Some(name) = trimmed.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+                subcommand = name;
+            }
+            if line.ends_with(":_apnotes_notes' \\") {
+                completed.push(subcommand);
+            }
+        }
+        assert!(completed.contains(&"print"));
+        assert!(completed.contains(&"undelete"));
+        assert!(completed.iter().all(|subcommand| NOTE_COMMANDS.contains(subcommand) || DELETED_NOTE_COMMANDS.contains(subcommand)));
+    }
+
+    #[test]
+    fn bash_and_fish_complete_notes() {
+        for shell in &["bash", "fish"] {
+            assert!(completions(shell).contains("apnotes __complete-notes"), "{} script does not complete notes", shell);
+        }
+    }
+}
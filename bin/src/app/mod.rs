@@ -1,3 +1,5 @@
 pub mod app;
-
-
+pub mod completions;
+pub mod output;
+pub mod rpc;
+pub mod server;
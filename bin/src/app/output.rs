@@ -0,0 +1,157 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use apnotes_lib::error::ErrorCode;
+use apnotes_lib::notes::localnote::LocalNote;
+use apnotes_lib::notes::traits::identifyable_note::IdentifiableNote;
+use apnotes_lib::search::{SearchHit, MATCH_START, MATCH_END};
+use apnotes_lib::SyncResult;
+use apnotes_lib::tags::extract_tags;
+use serde::ser::Serialize;
+
+/// Format of the results that are printed to stdout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Colored log lines for humans
+    Text,
+    /// One JSON document per command
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: Option<&str>) -> OutputFormat {
+        match name {
+            Some("json") => OutputFormat::Json,
+            _ => OutputFormat::Text
+        }
+    }
+}
+
+/// A note like it gets listed by list
+#[derive(Serialize, Debug)]
+pub struct NoteSummary {
+    pub uuid: String,
+    pub folder: String,
+    /// One subject per body, notes that need a merge have more than one
+    pub subjects: Vec<String>,
+    pub needs_merge: bool,
+    pub locally_deleted: bool,
+    /// The note was not uploaded yet
+    pub new: bool,
+    /// RFC 3339
+    pub modified: Option<String>,
+    /// RFC 3339
+    pub created: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl NoteSummary {
+    pub fn from_note(note: &LocalNote) -> NoteSummary {
+        NoteSummary {
+            uuid: note.metadata.uuid.clone(),
+            folder: note.metadata.folder(),
+            subjects: note.body.iter().map(|body| body.subject()).collect(),
+            needs_merge: note.needs_merge(),
+            locally_deleted: note.metadata.locally_deleted,
+            new: note.metadata.new,
+            modified: note.metadata.date.as_ref().map(rfc3339),
+            created: note.metadata.created_date.as_ref().map(rfc3339),
+            tags: extract_tags(note.body[0].text.as_deref().unwrap_or(""))
+        }
+    }
+}
+
+/// A note like it gets printed by print
+#[derive(Serialize, Debug)]
+pub struct NoteContent {
+    pub uuid: String,
+    pub folder: String,
+    pub subject: String,
+    pub text: String,
+}
+
+impl NoteContent {
+    pub fn from_note(note: &LocalNote) -> NoteContent {
+        let body = note.body.first().unwrap();
+        NoteContent {
+            uuid: note.metadata.uuid.clone(),
+            folder: note.metadata.folder(),
+            subject: body.subject(),
+            text: body.text.clone().unwrap_or_default()
+        }
+    }
+}
+
+/// Outcome of syncing a single note
+#[derive(Serialize, Debug)]
+pub struct SyncEntry {
+    pub action: String,
+    pub subject: String,
+    pub error: Option<ErrorOutput>,
+}
+
+impl SyncEntry {
+    pub fn from_result(result: &SyncResult) -> SyncEntry {
+        SyncEntry {
+            action: result.action.clone(),
+            subject: result.subject.clone(),
+            error: result.result.as_ref().err().map(|e| ErrorOutput::from_error(e.as_ref()))
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchEntry {
+    pub uuid: String,
+    pub folder: String,
+    pub subject: String,
+    /// Excerpt of the note text around the matches
+    pub snippet: String,
+    pub rank: f64,
+}
+
+impl SearchEntry {
+    pub fn from_hit(hit: &SearchHit) -> SearchEntry {
+        SearchEntry {
+            uuid: hit.uuid.clone(),
+            folder: hit.folder.clone(),
+            subject: hit.subject.clone(),
+            snippet: hit.snippet.replace(MATCH_START, "").replace(MATCH_END, ""),
+            rank: hit.rank
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ErrorOutput {
+    /// Same value as the exit code
+    pub code: i32,
+    pub description: String,
+    pub message: String,
+}
+
+impl ErrorOutput {
+    pub fn from_error(error: &dyn ErrorCode) -> ErrorOutput {
+        ErrorOutput {
+            code: error.error_code(),
+            description: error.human_readable_error_message(),
+            message: error.to_string()
+        }
+    }
+}
+
+/// Wraps the error into {"error": ...}
+#[derive(Serialize, Debug)]
+pub struct ErrorDocument {
+    pub error: ErrorOutput,
+}
+
+/// Prints the value as a single line JSON document to stdout
+pub fn print_json<T: Serialize>(value: &T) {
+    match ::serde_json::to_string(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Could not serialize output: {}", e)
+    }
+}
+
+fn rfc3339(date: &NaiveDateTime) -> String {
+    DateTime::<Utc>::from_utc(*date, Utc).to_rfc3339()
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+use apnotes_lib::AppleNotes;
+use apnotes_lib::error::{Result, ErrorCode, NoteError};
+use apnotes_lib::notes::localnote::LocalNote;
+use app::output::{NoteSummary, NoteContent, SyncEntry, ErrorOutput};
+
+const JSONRPC_VERSION: &str = "2.0";
+
+/// Error codes defined by JSON-RPC 2.0, errors of apnotes use their exit code
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// Notification that is sent after a sync added, changed or removed notes
+const NOTES_CHANGED: &str = "notes-changed";
+
+#[derive(Deserialize, Debug)]
+struct Request {
+    /// Notifications have no id and get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize, Debug)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize, Debug)]
+struct Notification<T: Serialize> {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: T,
+}
+
+#[derive(Serialize, Debug)]
+struct RpcError {
+    code: i32,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<ErrorOutput>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: &str) -> RpcError {
+        RpcError { code, message: message.to_string(), data: None }
+    }
+
+    fn from_error(e: Box<dyn ErrorCode>) -> RpcError {
+        RpcError {
+            code: e.error_code(),
+            message: e.to_string(),
+            data: Some(ErrorOutput::from_error(e.as_ref()))
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct NoteParams {
+    /// Note reference like the cli accepts it
+    note: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SaveParams {
+    note: String,
+    text: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreateParams {
+    /// Apple Notes uses the first line as subject
+    text: String,
+    #[serde(default)]
+    folder: String,
+}
+
+#[derive(Serialize, Debug)]
+struct MergePreview {
+    uuid: String,
+    /// Lines that only one version contains are prefixed with < or >
+    text: String,
+}
+
+#[derive(Serialize, Debug)]
+struct NotesChanged {
+    changed: Vec<NoteSummary>,
+    removed: Vec<String>,
+}
+
+/// Answers JSON-RPC 2.0 requests that are framed like LSP messages
+/// (Content-Length header, empty line, JSON body) until stdin gets closed
+/// or shutdown is called. Log output has to stay on stderr
+pub fn serve_stdio(app: &AppleNotes) -> Result<()> {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let stdout = std::io::stdout();
+    let mut output = stdout.lock();
+
+    while let Some(body) = read_message(&mut input)? {
+        let request: Request = match ::serde_json::from_slice::<Value>(&body) {
+            Err(e) => {
+                respond(&mut output, Value::Null, Err(RpcError::new(PARSE_ERROR, &e.to_string())))?;
+                continue;
+            }
+            Ok(value) => match ::serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(e) => {
+                    respond(&mut output, Value::Null, Err(RpcError::new(INVALID_REQUEST, &e.to_string())))?;
+                    continue;
+                }
+            }
+        };
+
+        if request.method == "shutdown" {
+            if let Some(id) = request.id {
+                respond(&mut output, id, Ok(Value::Null))?;
+            }
+            break;
+        }
+
+        let result = dispatch(app, &request, &mut output);
+        if let Some(id) = request.id {
+            respond(&mut output, id, result)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn dispatch(app: &AppleNotes, request: &Request, output: &mut dyn Write) -> std::result::Result<Value, RpcError> {
+    match request.method.as_str() {
+        "list" => {
+            let notes = app.get_notes().map_err(RpcError::from_error)?;
+            let mut summaries: Vec<NoteSummary> = notes.iter().map(NoteSummary::from_note).collect();
+            summaries.sort_by(|a, b| (&a.folder, &a.subjects).cmp(&(&b.folder, &b.subjects)));
+            to_value(&summaries)
+        }
+        "open" => {
+            let params: NoteParams = params(request)?;
+            let note = app.find_note(&params.note).map_err(RpcError::from_error)?;
+            if note.needs_merge() {
+                return Err(RpcError::from_error(NoteError::NeedsMerge.into()));
+            }
+            to_value(&NoteContent::from_note(&note))
+        }
+        "save" => {
+            let params: SaveParams = params(request)?;
+            app.find_note_strictly(&params.note)
+                .and_then(|note| app.replace_text(&note, &params.text))
+                .and_then(|note| app.update_note(&note).map(|_| note))
+                .map_err(RpcError::from_error)
+                .and_then(|note| to_value(&NoteContent::from_note(&note)))
+        }
+        "create" => {
+            let params: CreateParams = params(request)?;
+            app.create_note_with_text(&params.text, &params.folder)
+                .map_err(RpcError::from_error)
+                .and_then(|note| to_value(&NoteContent::from_note(&note)))
+        }
+        "delete" => {
+            let params: NoteParams = params(request)?;
+            app.delete_note(&params.note).map_err(RpcError::from_error)?;
+            Ok(Value::Null)
+        }
+        "merge-preview" => {
+            let params: NoteParams = params(request)?;
+            let note = app.find_note(&params.note).map_err(RpcError::from_error)?;
+            let text = app.merge_preview(&note.metadata.uuid).map_err(RpcError::from_error)?;
+            to_value(&MergePreview { uuid: note.metadata.uuid, text })
+        }
+        "sync" => {
+            let before = app.get_notes().map_err(RpcError::from_error)?;
+            let results = app.sync_notes().map_err(RpcError::from_error)?;
+            let after = app.get_notes().map_err(RpcError::from_error)?;
+
+            let changes = changes(before.iter(), after.iter());
+            if !changes.changed.is_empty() || !changes.removed.is_empty() {
+                notify(output, NOTES_CHANGED, &changes).map_err(RpcError::from_error)?;
+            }
+            to_value(&results.iter().map(SyncEntry::from_result).collect::<Vec<SyncEntry>>())
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, &format!("Unknown method {}", request.method)))
+    }
+}
+
+/// Compares the notes before and after a sync
+fn changes<'a>(before: impl Iterator<Item = &'a LocalNote>, after: impl Iterator<Item = &'a LocalNote>) -> NotesChanged {
+    let before: HashMap<&String, &LocalNote> = before.map(|note| (&note.metadata.uuid, note)).collect();
+    let after: Vec<&LocalNote> = after.collect();
+
+    let mut changed: Vec<NoteSummary> = after.iter()
+        .filter(|note| match before.get(&note.metadata.uuid) {
+            Some(old) => old.body != note.body || old.metadata.locally_deleted != note.metadata.locally_deleted,
+            None => true
+        })
+        .map(|note| NoteSummary::from_note(note))
+        .collect();
+    changed.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+
+    let mut removed: Vec<String> = before.keys()
+        .filter(|uuid| after.iter().all(|note| &&note.metadata.uuid != *uuid))
+        .map(|uuid| uuid.to_string())
+        .collect();
+    removed.sort();
+
+    NotesChanged { changed, removed }
+}
+
+fn params<T: DeserializeOwned>(request: &Request) -> std::result::Result<T, RpcError> {
+    ::serde_json::from_value(request.params.clone())
+        .map_err(|e| RpcError::new(INVALID_PARAMS, &e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: &T) -> std::result::Result<Value, RpcError> {
+    ::serde_json::to_value(value).map_err(|e| RpcError::new(INVALID_REQUEST, &e.to_string()))
+}
+
+/// Reads the body of the next message, None if the input got closed
+fn read_message(input: &mut dyn BufRead) -> Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn respond(output: &mut dyn Write, id: Value, result: std::result::Result<Value, RpcError>) -> Result<()> {
+    let response = match result {
+        Ok(result) => Response { jsonrpc: JSONRPC_VERSION, id, result: Some(result), error: None },
+        Err(error) => Response { jsonrpc: JSONRPC_VERSION, id, result: None, error: Some(error) }
+    };
+    write_message(output, &response)
+}
+
+fn notify<T: Serialize>(output: &mut dyn Write, method: &'static str, params: T) -> Result<()> {
+    write_message(output, &Notification { jsonrpc: JSONRPC_VERSION, method, params })
+}
+
+fn write_message<T: Serialize>(output: &mut dyn Write, message: &T) -> Result<()> {
+    let body = ::serde_json::to_string(message).unwrap_or_default();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,354 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use itertools::Itertools;
+use tiny_http::{Header, Method, Request, Response, Server};
+use apnotes_lib::AppleNotes;
+use apnotes_lib::error::{Result, ErrorCode, NoteError, ServerError, UpdateError};
+use apnotes_lib::notes::localnote::LocalNote;
+use apnotes_lib::notes::traits::identifyable_note::IdentifiableNote;
+use apnotes_lib::search::SearchOptions;
+use app::output::{NoteSummary, NoteContent, SearchEntry, SyncEntry, ErrorDocument, ErrorOutput};
+use serde::ser::Serialize;
+
+/// Body of the requests that create or update a note
+#[derive(Deserialize, Debug)]
+struct NoteInput {
+    /// Apple Notes uses the first line as subject
+    text: String,
+    /// Only used while creating a note, the default folder is used if omitted
+    folder: Option<String>,
+}
+
+/// State of the last sync that got triggered through the api
+#[derive(Serialize, Debug, Default)]
+pub struct SyncStatus {
+    pub running: bool,
+    /// RFC 3339
+    pub started: Option<String>,
+    /// RFC 3339
+    pub finished: Option<String>,
+    pub results: Vec<SyncEntry>,
+    /// Set if the sync could not connect to the mail server or query the db
+    pub error: Option<ErrorOutput>,
+}
+
+/// Number of threads that answer requests
+const WORKERS: usize = 4;
+
+/// How long a request waits for the notes before it gets answered with 503
+const NOTES_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct State {
+    /// Serializes the requests of this process, see lock_notes. Writes of other processes,
+    /// like the cli or the tui, are serialized by the database lock that AppleNotes takes
+    notes: Mutex<AppleNotes>,
+    sync: Mutex<SyncStatus>,
+    token: String,
+}
+
+/// Status code and JSON body of a response
+struct Reply {
+    status: u16,
+    body: Option<String>,
+}
+
+impl Reply {
+    fn json<T: Serialize>(status: u16, value: &T) -> Reply {
+        Reply { status, body: Some(::serde_json::to_string(value).unwrap_or_default()) }
+    }
+
+    fn empty(status: u16) -> Reply {
+        Reply { status, body: None }
+    }
+
+    fn error(e: Box<dyn ErrorCode>) -> Reply {
+        Reply::json(status_of(e.as_ref()), &ErrorDocument { error: ErrorOutput::from_error(e.as_ref()) })
+    }
+}
+
+/// Answers REST requests on the passed address until the process gets killed.
+/// Every request has to carry the token as bearer token, the requests are
+/// handled by a fixed number of worker threads
+pub fn serve(apple_notes: AppleNotes, listen: &str, token: &str) -> Result<()> {
+    let server = Arc::new(Server::http(listen)
+        .map_err(|e| UpdateError::IoError(format!("Could not listen on {}: {}", listen, e)))?);
+
+    let state = Arc::new(State {
+        notes: Mutex::new(apple_notes),
+        sync: Mutex::new(SyncStatus::default()),
+        token: token.to_string(),
+    });
+
+    info!("Listening on http://{}", listen);
+
+    let workers: Vec<_> = (0..WORKERS).map(|_| {
+        let server = server.clone();
+        let state = state.clone();
+        std::thread::spawn(move || loop {
+            match server.recv() {
+                // A panicking handler must not shrink the pool
+                Ok(request) => if catch_unwind(AssertUnwindSafe(|| handle(request, &state))).is_err() {
+                    error!("Request handler panicked");
+                },
+                Err(e) => warn!("Could not receive request: {}", e)
+            }
+        })
+    }).collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    Ok(())
+}
+
+/// Locks the mutex even if a thread panicked while holding it, the handlers
+/// never leave the guarded values half updated
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Waits up to NOTES_TIMEOUT for the notes. A running sync holds them for the whole
+/// round trip to the mail server, requests that would wait longer fail with
+/// ServerError::Busy instead of occupying a worker, so that GET /sync stays answered
+fn lock_notes(state: &State) -> Result<MutexGuard<AppleNotes>> {
+    let deadline = Instant::now() + NOTES_TIMEOUT;
+    loop {
+        match state.notes.try_lock() {
+            Ok(notes) => return Ok(notes),
+            Err(TryLockError::Poisoned(poisoned)) => return Ok(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            Err(TryLockError::WouldBlock) => return Err(ServerError::Busy.into()),
+        }
+    }
+}
+
+/// Marks the sync as finished once dropped, also if the sync panicked,
+/// otherwise every later sync would be rejected as running
+struct SyncFinished<'a>(&'a State);
+
+impl<'a> Drop for SyncFinished<'a> {
+    fn drop(&mut self) {
+        let mut status = lock(&self.0.sync);
+        status.running = false;
+        status.finished = Some(Utc::now().to_rfc3339());
+    }
+}
+
+fn handle(mut request: Request, state: &Arc<State>) {
+    let reply = if is_authorized(&request, &state.token) {
+        route(&mut request, state).unwrap_or_else(Reply::error)
+    } else {
+        Reply::error(ServerError::Unauthorized.into())
+    };
+
+    debug!("{} {} {}", request.method(), request.url(), reply.status);
+
+    let response = match reply.body {
+        Some(body) => Response::from_string(body)
+            .with_status_code(reply.status)
+            .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap()),
+        None => Response::from_string("").with_status_code(reply.status)
+    };
+
+    if let Err(e) = request.respond(response) {
+        warn!("Could not answer request: {}", e);
+    }
+}
+
+fn is_authorized(request: &Request, token: &str) -> bool {
+    request.headers().iter()
+        .filter(|header| header.field.equiv("Authorization"))
+        .filter_map(|header| header.value.as_str().strip_prefix("Bearer "))
+        .any(|sent| constant_time_eq(sent.trim().as_bytes(), token.as_bytes()))
+}
+
+/// Compares without returning early, so that the response time does not
+/// reveal how much of the token is right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn route(request: &mut Request, state: &Arc<State>) -> Result<Reply> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<String> = path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(|segment| segment.as_str()).collect();
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["notes"]) => list_notes(state),
+        (Method::Post, ["notes"]) => {
+            let input = read_note_input(request)?;
+            create_note(state, input)
+        }
+        (Method::Get, ["notes", reference]) => get_note(state, reference),
+        (Method::Put, ["notes", reference]) => {
+            let input = read_note_input(request)?;
+            update_note(state, reference, input)
+        }
+        (Method::Delete, ["notes", reference]) => delete_note(state, reference),
+        (Method::Get, ["search"]) => search(state, query),
+        (Method::Get, ["sync"]) => Ok(Reply::json(200, &*lock(&state.sync))),
+        (Method::Post, ["sync"]) => start_sync(state),
+        (method, _) => Err(ServerError::UnknownRoute(format!("{} {}", method, path)).into())
+    }
+}
+
+fn list_notes(state: &State) -> Result<Reply> {
+    let notes = lock_notes(state)?.get_notes()?;
+    let summaries: Vec<NoteSummary> = notes.iter()
+        .sorted_by_key(|note| (note.metadata.folder(), note.first_subject()))
+        .map(NoteSummary::from_note)
+        .collect();
+    Ok(Reply::json(200, &summaries))
+}
+
+fn get_note(state: &State, reference: &str) -> Result<Reply> {
+    let note = lock_notes(state)?.find_note(&reference.to_string())?;
+    if note.needs_merge() {
+        return Err(NoteError::NeedsMerge.into());
+    }
+    Ok(Reply::json(200, &NoteContent::from_note(&note)))
+}
+
+fn create_note(state: &State, input: NoteInput) -> Result<Reply> {
+    let folder = input.folder.clone().unwrap_or_default();
+    let note = lock_notes(state)?.create_note_with_text(&input.text, &folder)?;
+    Ok(Reply::json(201, &NoteContent::from_note(&note)))
+}
+
+fn update_note(state: &State, reference: &str, input: NoteInput) -> Result<Reply> {
+    let app = lock_notes(state)?;
+    let note: LocalNote = app.find_note_strictly(&reference.to_string())
+        .and_then(|note| app.replace_text(&note, &input.text))?;
+    app.update_note(&note)?;
+    Ok(Reply::json(200, &NoteContent::from_note(&note)))
+}
+
+fn delete_note(state: &State, reference: &str) -> Result<Reply> {
+    lock_notes(state)?.delete_note(&reference.to_string())?;
+    Ok(Reply::empty(204))
+}
+
+fn search(state: &State, query: &str) -> Result<Reply> {
+    let parameters = parse_query(query);
+    let text = parameters.iter()
+        .find(|(name, _)| name == "q")
+        .map(|(_, value)| value.clone())
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| ServerError::BadRequest("the query parameter q is missing".to_string()))?;
+
+    let limit = match parameters.iter().find(|(name, _)| name == "limit") {
+        Some((_, value)) => Some(value.parse::<usize>()
+            .map_err(|_| ServerError::BadRequest(format!("limit has to be a number, got \"{}\"", value)))?),
+        None => None
+    };
+    let options = SearchOptions {
+        folders: parameters.iter().filter(|(name, _)| name == "folder").map(|(_, value)| value.clone()).collect(),
+        limit
+    };
+
+    let hits = lock_notes(state)?.search(&text, &options)?;
+    Ok(Reply::json(200, &hits.iter().map(SearchEntry::from_hit).collect::<Vec<SearchEntry>>()))
+}
+
+/// Syncs in the background, the progress can be followed with GET /sync
+fn start_sync(state: &Arc<State>) -> Result<Reply> {
+    {
+        let mut status = lock(&state.sync);
+        if status.running {
+            return Err(ServerError::SyncRunning.into());
+        }
+        *status = SyncStatus {
+            running: true,
+            started: Some(Utc::now().to_rfc3339()),
+            ..SyncStatus::default()
+        };
+    }
+
+    let sync_state = state.clone();
+    std::thread::spawn(move || {
+        let _finished = SyncFinished(&sync_state);
+        let result = lock(&sync_state.notes).sync_notes();
+        let mut status = lock(&sync_state.sync);
+        match result {
+            Ok(results) => status.results = results.iter().map(SyncEntry::from_result).collect(),
+            Err(e) => status.error = Some(ErrorOutput::from_error(e.as_ref()))
+        }
+    });
+
+    Ok(Reply::json(202, &*lock(&state.sync)))
+}
+
+fn read_note_input(request: &mut Request) -> Result<NoteInput> {
+    let mut body = String::new();
+    request.as_reader().read_to_string(&mut body)?;
+    let input: NoteInput = ::serde_json::from_str(&body)
+        .map_err(|e| ServerError::BadRequest(format!("invalid note: {}", e)))?;
+    if input.text.trim().is_empty() {
+        return Err(ServerError::BadRequest("text must not be empty".to_string()).into());
+    }
+    Ok(input)
+}
+
+fn status_of(error: &dyn ErrorCode) -> u16 {
+    let code = error.error_code();
+    if code == ServerError::Unauthorized.error_code() {
+        401
+    } else if code == ServerError::UnknownRoute(String::new()).error_code()
+        || code == NoteError::NoteNotFound.error_code() {
+        404
+    } else if code == ServerError::SyncRunning.error_code()
+        || code == NoteError::NeedsMerge.error_code()
+        || code == NoteError::AmbiguousNote(String::new(), vec![]).error_code() {
+        409
+    } else if code == ServerError::Busy.error_code() {
+        503
+    } else if code == ServerError::BadRequest(String::new()).error_code()
+        || code == NoteError::InsertionError(String::new()).error_code()
+        || code == NoteError::ContentNotChanged.error_code() {
+        400
+    } else {
+        500
+    }
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(&name.replace('+', " ")), percent_decode(&value.replace('+', " ")))
+        })
+        .collect()
+}
+
+/// Decodes %XX escapes, invalid escapes are kept as they are
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = match bytes[index] {
+            b'%' if index + 2 < bytes.len() => std::str::from_utf8(&bytes[index + 1..index + 3]).ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
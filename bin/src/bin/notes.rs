@@ -12,11 +12,30 @@ extern crate serde_json;
 use clap::{ArgMatches};
 use colored::Colorize;
 use itertools::*;
-use apnotes_lib::AppleNotes;
+use apnotes_lib::{AppleNotes, AppleNotesBuilder};
+use apnotes_lib::lock::lock_file_of;
+use apnotes_lib::db::SqliteDBConnection;
 use apnotes_lib::notes::traits::identifyable_note::IdentifiableNote;
 use flexi_logger::{Logger, Record, DeferredNow};
-use apnotes_bin::app::app::gen_app;
-use apnotes_lib::error::Result;
+use apnotes_bin::app::app::{gen_app_with_helpers, parse_since};
+use apnotes_bin::app::completions::write_completions;
+use apnotes_bin::app::server::serve;
+use apnotes_bin::app::rpc::serve_stdio;
+use apnotes_bin::app::output::{OutputFormat, NoteSummary, NoteContent, SyncEntry, SearchEntry, ErrorDocument, ErrorOutput, print_json};
+use apnotes_lib::notes::localnote::LocalNote;
+use std::cmp::Reverse;
+use apnotes_lib::error::{Result, ProfileError, UpdateError, NoteError, ErrorCode};
+use apnotes_lib::restore::{BackupSource, ClashStrategy};
+use apnotes_lib::export::ExportFormat;
+use apnotes_lib::import::{ImportOptions, ImportOutcome};
+use std::path::PathBuf;
+use std::time::Duration;
+use apnotes_lib::revisions::DiffLine;
+use apnotes_lib::search::{SearchOptions, MATCH_START, MATCH_END};
+use apnotes_lib::config;
+use apnotes_lib::config::{ConfigFile, ConfigFormat, ConfigIssue};
+use apnotes_lib::setup::{Setup, PasswordStorage};
+use std::io::{Read, Write};
 
 //Minimal println like formatting for flexi_logger
 pub fn default_format(
@@ -35,51 +54,452 @@ pub fn main() {
 
     Logger::with_env_or_str("info").format(default_format).start().unwrap();
 
-    let app = gen_app();
-
-    let db_connection= ::apnotes_lib::db::SqliteDBConnection::new();
+    let app = gen_app_with_helpers();
 
     let matches = app.get_matches();
 
+    let output = OutputFormat::from_name(matches.value_of("output"));
+
+    // Have to work with a missing or broken config file, so they do not need a profile
+    let config_result = match matches.subcommand() {
+        Some(("init", sub_matches)) => Some(init_config(sub_matches)),
+        Some(("config", sub_matches)) => Some(manage_config(sub_matches, output)),
+        Some(("completions", sub_matches)) => Some(print_completions(sub_matches)),
+        _ => None
+    };
+
+    if let Some(result) = config_result {
+        if let Err(e) = result {
+            exit_with_error(e, output);
+        }
+        return;
+    }
+
     match ::apnotes_lib::get_user_profile() {
         Ok(profile) => {
-            let apple_notes = ::apnotes_lib::AppleNotes::new(
-                profile,
-                Box::new(db_connection)
-            );
+            let db_path = match matches.value_of("db") {
+                Some(path) => Ok(PathBuf::from(path)),
+                None => profile.database_path()
+            };
+
+            let db_connection = match db_path.and_then(|path| SqliteDBConnection::open(&path).map(|db| (path, db))) {
+                Ok(db_connection) => db_connection,
+                Err(e) => {
+                    if output == OutputFormat::Text {
+                        error!("Could not open database: {}", e.to_string());
+                    }
+                    exit_with_error(e, output);
+                }
+            };
+
+            let server_token = profile.server_settings().token.clone();
+
+            let (db_path, db_connection) = db_connection;
+            let apple_notes = match AppleNotesBuilder::new()
+                .with_profile(profile)
+                .with_database(Box::new(db_connection))
+                .with_lock_file(lock_file_of(&db_path))
+                .build() {
+                Ok(apple_notes) => apple_notes,
+                Err(e) => exit_with_error(e, output)
+            };
 
             let result = match matches.subcommand() {
                 Some(("new",  sub_matches)) => new(sub_matches,&apple_notes),
-                Some(("sync", _sub_matches)) => apple_notes.sync_notes().map(|_| ()),
-                Some(("list", sub_matches)) => list_notes(sub_matches,&apple_notes),
+                Some(("sync", _sub_matches)) => sync_notes(&apple_notes, output),
+                Some(("list", sub_matches)) => list_notes(sub_matches,&apple_notes, output),
                 Some(("edit", sub_matches)) => edit_passed_note(sub_matches,&apple_notes),
+                Some(("append", sub_matches)) => append_to_note(sub_matches,&apple_notes),
                 Some(("merge", sub_matches)) => merge_note(sub_matches,&apple_notes),
                 Some(("delete", sub_matches)) => delete_note(sub_matches,&apple_notes),
                 Some(("undelete", sub_matches)) => undelete_note(sub_matches,&apple_notes),
-                Some(("print", sub_matches)) => print_note(sub_matches, &apple_notes),
+                Some(("print", sub_matches)) => print_note(sub_matches, &apple_notes, output),
+                Some(("restore", sub_matches)) => restore_notes(sub_matches, &apple_notes),
+                Some(("export", sub_matches)) => export_notes(sub_matches, &apple_notes),
+                Some(("import", sub_matches)) => import_notes(sub_matches, &apple_notes),
+                Some(("mirror", sub_matches)) => mirror_notes(sub_matches, &apple_notes),
+                Some(("search", sub_matches)) => search_notes(sub_matches, &apple_notes, output),
+                Some(("tags", _sub_matches)) => list_tags(&apple_notes),
+                Some(("history", sub_matches)) => show_history(sub_matches, &apple_notes),
+                Some(("diff", sub_matches)) => show_diff(sub_matches, &apple_notes),
+                Some(("revert", sub_matches)) => revert_note(sub_matches, &apple_notes),
+                Some(("trash", sub_matches)) => manage_trash(sub_matches, &apple_notes),
+                Some(("__complete-notes", sub_matches)) => complete_notes(sub_matches, &apple_notes),
+                Some(("rpc", _sub_matches)) => serve_stdio(&apple_notes),
+                Some(("serve", sub_matches)) => serve_notes(sub_matches, apple_notes, server_token),
                 _ => unreachable!(),
             };
 
             match result {
                 Ok(_) => {}
-                Err(e) => {
-                    error!("Error: {}\n{} - ({})", e.human_readable_error_message(), e.to_string(), e.error_code().to_string());
-                    std::process::exit(e.error_code());
-                },
+                Err(e) => exit_with_error(e, output),
             }
         }
         Err(e) => {
-            error!("Could not load profile: {}", e.to_string());
+            if output == OutputFormat::Text {
+                error!("Could not load profile: {}", e.to_string());
+            }
+            exit_with_error(e, output);
         }
     }
+}
+
+/// Reports the error in the requested format and exits with its error code
+fn exit_with_error(e: Box<dyn ErrorCode>, output: OutputFormat) -> ! {
+    match output {
+        OutputFormat::Json => print_json(&ErrorDocument { error: ErrorOutput::from_error(e.as_ref()) }),
+        OutputFormat::Text => error!("Error: {}\n{} - ({})", e.human_readable_error_message(), e.to_string(), e.error_code().to_string())
+    }
+    std::process::exit(e.error_code());
+}
 
+fn sync_notes(app: &AppleNotes, output: OutputFormat) -> Result<()> {
+    app.sync_notes().map(|results| {
+        if output == OutputFormat::Json {
+            print_json(&results.iter().map(SyncEntry::from_result).collect::<Vec<SyncEntry>>());
+        }
+    })
+}
 
+fn export_notes(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let dir = PathBuf::from(sub_matches.value_of("dir").unwrap());
+    let format = match sub_matches.value_of("format") {
+        Some("markdown") => ExportFormat::Markdown,
+        _ => unreachable!(),
+    };
 
+    app.export(format, &dir, sub_matches.is_present("prune")).map(|summary| {
+        info!("Exported notes to {}: {} written, {} unchanged, {} removed",
+              dir.display(), summary.written, summary.unchanged, summary.removed);
+    })
+}
+
+fn import_notes(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let dir = PathBuf::from(sub_matches.value_of("dir").unwrap());
+    let options = ImportOptions {
+        skip_imported: sub_matches.is_present("skip-imported"),
+        record_uuid: sub_matches.is_present("record"),
+    };
+
+    app.import(&dir, options).map(|results| {
+        for result in results {
+            match result.result {
+                Ok(ImportOutcome::Imported(uuid)) => info!("{} [{}] {}", "Imported".green(), result.path.display(), uuid),
+                Ok(ImportOutcome::Skipped(uuid)) => info!("{} [{}] {}", "Skipped".yellow(), result.path.display(), uuid),
+                Err(e) => error!("{} [{}] {}", "Failed".red(), result.path.display(), e.to_string())
+            }
+        }
+    })
 }
 
-fn print_note(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+fn show_history(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let note = sub_matches.value_of("note").unwrap().to_string();
+    app.history(&note).map(|revisions| {
+        for revision in revisions {
+            let subject = revision.text.as_deref().unwrap_or("").lines().next().unwrap_or("").to_string();
+            info!("{:>5}  {}  {:<5}  [{}]", revision.id, revision.created_at, revision.kind, subject);
+        }
+    })
+}
+
+fn show_diff(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let note = sub_matches.value_of("note").unwrap().to_string();
+    let revision = sub_matches.value_of_t::<i64>("revision").unwrap();
+    app.diff(&note, revision).map(|lines| {
+        for line in lines {
+            match line {
+                DiffLine::Unchanged(line) => println!("  {}", line),
+                DiffLine::Removed(line) => println!("{}", format!("- {}", line).red()),
+                DiffLine::Added(line) => println!("{}", format!("+ {}", line).green()),
+            }
+        }
+    })
+}
+
+fn revert_note(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let note = sub_matches.value_of("note").unwrap().to_string();
+    let revision = sub_matches.value_of_t::<i64>("revision").unwrap();
+    app.revert(&note, revision)
+        .map(|note| info!("Reverted {} to revision {}", note.metadata.uuid, revision))
+}
+
+fn init_config(sub_matches: &ArgMatches) -> Result<()> {
+    let found = config::locate()?;
+    let overwrite = sub_matches.is_present("force");
+    if found.exists && !overwrite {
+        return Err(UpdateError::IoError(format!("{} already exists, use --force to replace it", found.path.display())).into());
+    }
+
+    let file = ConfigFile {
+        path: found.path.with_file_name(config::CONFIG_FILE),
+        format: ConfigFormat::Toml,
+        exists: found.exists
+    };
+
+    let imap_server = prompt("IMAP server", None)?;
+    let username = prompt("Username", None)?;
+    let email = prompt("Email", Some(&username).filter(|username| username.contains('@')).map(|username| username.as_str()))?;
+    let editor = prompt("Editor", Some(&std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string())))?;
+    let editor_arguments = prompt("Editor arguments", Some(""))?
+        .split_whitespace()
+        .map(|argument| argument.to_string())
+        .collect();
+
+    let password = loop {
+        match prompt("Password storage (plain, secret_service, command)", Some("plain"))?.as_str() {
+            "plain" => break PasswordStorage::Plain(prompt_password("Password")?),
+            "secret_service" => break PasswordStorage::SecretService {
+                attribute: prompt("Secret service attribute", None)?,
+                value: prompt("Secret service value", None)?
+            },
+            "command" => break PasswordStorage::Command(prompt("Command that prints the password", None)?),
+            _ => continue
+        }
+    };
+
+    let setup = Setup { imap_server, username, email, editor, editor_arguments, password };
+
+    match setup.verify_login() {
+        Ok(folders) if folders.is_empty() => {
+            warn!("Login succeeded, but there are no Notes folders. Enable notes for this account on your apple device")
+        }
+        Ok(folders) => info!("{} Found {}", "Login succeeded.".green(), folders.join(", ")),
+        Err(e) => {
+            error!("{} {}", "Login failed:".red(), e.to_string());
+            if prompt("Write the config anyway? (y/n)", Some("n"))? != "y" {
+                return Err(e);
+            }
+        }
+    }
+
+    setup.write(&file, overwrite).map(|_| info!("Wrote {}", file.path.display()))
+}
+
+/// Asks for a value on stdin, an empty answer selects the default
+fn prompt(question: &str, default: Option<&str>) -> Result<String> {
+    loop {
+        match default {
+            Some(default) if !default.is_empty() => print!("{} [{}]: ", question, default),
+            _ => print!("{}: ", question)
+        }
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Err(UpdateError::IoError("Input ended before all settings were answered".to_string()).into());
+        }
+
+        match (answer.trim(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => continue,
+            (answer, _) => return Ok(answer.to_string())
+        }
+    }
+}
+
+/// Like prompt, but the terminal does not echo the typed password
+fn prompt_password(question: &str) -> Result<String> {
+    #[cfg(target_family = "unix")]
+    let _ = std::process::Command::new("stty").arg("-echo").status();
+
+    let password = prompt(question, None);
+
+    #[cfg(target_family = "unix")]
+    {
+        let _ = std::process::Command::new("stty").arg("echo").status();
+        println!();
+    }
+
+    password
+}
+
+fn manage_config(sub_matches: &ArgMatches, output: OutputFormat) -> Result<()> {
+    let file = config::locate()?;
+    match sub_matches.subcommand() {
+        Some(("path", _)) => {
+            info!("{} ({})", file.path.display(), file.format.as_str());
+            if !file.exists {
+                warn!("The config file does not exist yet");
+            } else if file.format == ConfigFormat::Legacy {
+                warn!("Legacy config format, \"apnotes config show\" prints the TOML equivalent");
+            }
+            Ok(())
+        }
+        Some(("check", _)) => {
+            if !file.exists {
+                return Err(ProfileError::NotFound(format!("No config file at {}", file.path.display())).into());
+            }
+            let issues = config::check(&file)?;
+            if issues.is_empty() {
+                info!("{} {}", "Valid".green(), file.path.display());
+                return Ok(());
+            }
+            if output == OutputFormat::Text {
+                for issue in &issues {
+                    error!("{} {}", issue.key.red(), issue.message);
+                }
+            }
+            Err(ProfileError::InvalidConfig(issues).into())
+        }
+        _ => ::apnotes_lib::get_user_profile().map(|profile| {
+            print!("{}", config::render(&profile));
+        })
+    }
+}
+
+fn serve_notes(sub_matches: &ArgMatches, app: AppleNotes, token: Option<String>) -> Result<()> {
+    let token = token.ok_or_else(|| ProfileError::InvalidConfig(vec![ConfigIssue {
+        key: "server.token".to_string(),
+        message: "missing, required by apnotes serve".to_string()
+    }]))?;
+    serve(app, sub_matches.value_of("listen").unwrap(), &token)
+}
+
+fn print_completions(sub_matches: &ArgMatches) -> Result<()> {
+    let shell = sub_matches.value_of("shell").unwrap();
+    write_completions(shell, &mut std::io::stdout())?;
+    Ok(())
+}
+
+/// Prints the titles and then the uuids of the notes, each terminated by NUL,
+/// so that titles with newlines or quotes reach the completion scripts unchanged
+fn complete_notes(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let deleted = sub_matches.is_present("deleted");
+    let notes: Vec<LocalNote> = app.get_notes()?.into_iter()
+        .filter(|note| note.metadata.locally_deleted == deleted)
+        .sorted_by_key(|note| note.first_subject())
+        .collect();
+
+    let mut stdout = std::io::stdout();
+    for title in notes.iter().map(|note| note.first_subject()).dedup() {
+        write!(stdout, "{}\0", title)?;
+    }
+    for note in &notes {
+        write!(stdout, "{}\0", note.metadata.uuid)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn manage_trash(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    match sub_matches.subcommand() {
+        Some(("restore", restore_matches)) => {
+            let uuids: Vec<String> = restore_matches.values_of("uuid").unwrap().map(|uuid| uuid.to_string()).collect();
+            app.restore_from_trash(&uuids).map(|results| {
+                for result in results {
+                    match result.result {
+                        Ok(_) => info!("{} [{}] {}", "Restored".green(), result.subject, result.uuid),
+                        Err(e) => error!("{} [{}] {} {}", "Failed".red(), result.subject, result.uuid, e.to_string())
+                    }
+                }
+            })
+        }
+        Some(("purge", purge_matches)) => app.purge_trash(purge_matches.is_present("all"))
+            .map(|purged| info!("Removed {} notes from the trash", purged)),
+        _ => app.get_trash().map(|trashed| {
+            for trashed_note in trashed {
+                info!("{} {}  {:<6}  {}  [{}]",
+                      trashed_note.note.metadata.uuid,
+                      trashed_note.deleted_at,
+                      trashed_note.origin,
+                      trashed_note.note.metadata.folder(),
+                      trashed_note.note.first_subject());
+            }
+        })
+    }
+}
+
+fn list_tags(app: &AppleNotes) -> Result<()> {
+    app.get_tags().map(|tags| {
+        let max_len = tags.iter().map(|(tag, _)| tag.len() + 1).max().unwrap_or(0);
+        for (tag, count) in tags {
+            info!("{:<width$}  {}", format!("#{}", tag), count, width = max_len);
+        }
+    })
+}
+
+fn search_notes(sub_matches: &ArgMatches, app: &AppleNotes, output: OutputFormat) -> Result<()> {
+    let query = sub_matches.values_of("query").unwrap().join(" ");
+    let options = SearchOptions {
+        folders: sub_matches.values_of("folder").map(|folders| folders.map(|folder| folder.to_string()).collect()).unwrap_or_default(),
+        limit: sub_matches.value_of("limit").map(|limit| limit.parse().unwrap()),
+    };
+
+    app.search(&query, &options).map(|hits| {
+        if output == OutputFormat::Json {
+            return print_json(&hits.iter().map(SearchEntry::from_hit).collect::<Vec<SearchEntry>>());
+        }
+
+        for hit in hits {
+            info!("{} {}  [{}]", hit.uuid, hit.folder, hit.subject.bold());
+            let snippet = hit.snippet
+                .split(MATCH_START)
+                .enumerate()
+                .map(|(index, part)| match part.split_once(MATCH_END) {
+                    Some((matched, rest)) if index > 0 => format!("{}{}", matched.yellow().bold(), rest),
+                    _ => part.to_string()
+                })
+                .join("")
+                .replace('\n', " ");
+            info!("    {}", snippet);
+        }
+    })
+}
+
+fn mirror_notes(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let dir = PathBuf::from(sub_matches.value_of("dir").unwrap());
+    let interval = sub_matches.value_of_t::<u64>("interval").unwrap();
+    app.mirror(&dir, Duration::from_secs(interval))
+}
+
+fn restore_notes(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let source = match sub_matches.value_of("archive") {
+        Some(path) => BackupSource::Archive(PathBuf::from(path)),
+        None => BackupSource::Imap
+    };
+
+    if sub_matches.is_present("list") {
+        return app.list_backup(&source).map(|notes| {
+            notes.iter()
+                .sorted_by_key(|note| format!("{}_{}", &note.metadata.subfolder, note.first_subject()))
+                .for_each(|note| {
+                    let titles = note.body.iter().map(|body| body.subject()).join(",");
+                    info!("{} {}  [{}]", note.metadata.uuid, note.metadata.folder(), titles);
+                });
+        });
+    }
+
+    let strategy = match sub_matches.value_of("on-clash") {
+        Some("overwrite") => ClashStrategy::Overwrite,
+        _ => ClashStrategy::KeepBoth
+    };
+
+    let uuids: Vec<String> = sub_matches.values_of("uuid")
+        .map(|values| values.map(|uuid| uuid.to_string()).collect())
+        .unwrap_or_default();
+
+    app.restore(&source, &uuids, strategy).map(|results| {
+        for result in results {
+            match result.result {
+                Ok(_) => info!("{} [{}] {}", "Restored".green(), result.subject, result.uuid),
+                Err(e) => error!("{} [{}] {} {}", "Failed".red(), result.subject, result.uuid, e.to_string())
+            }
+        }
+    })
+}
+
+fn print_note(sub_matches: &ArgMatches, app: &AppleNotes, output: OutputFormat) -> Result<()> {
     let uuid_or_name = sub_matches.value_of("path").unwrap().to_string();
-    app.print(&uuid_or_name)
+    match output {
+        OutputFormat::Json => app.find_note(&uuid_or_name).and_then(|note| {
+            if note.needs_merge() {
+                return Err(NoteError::NeedsMerge.into());
+            }
+            print_json(&NoteContent::from_note(&note));
+            Ok(())
+        }),
+        OutputFormat::Text => app.print(&uuid_or_name)
+    }
 }
 
 fn undelete_note(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
@@ -99,23 +519,65 @@ fn merge_note(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
 
 fn edit_passed_note(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
     let uuid_or_name = sub_matches.value_of("path").unwrap().to_string();
-    app.find_note(&uuid_or_name)
+
+    if sub_matches.is_present("stdin") {
+        let text = read_stdin()?;
+        return app.find_note_strictly(&uuid_or_name)
+            .and_then(|note| app.replace_text(&note, &text))
+            .and_then(|note| app.update_note(&note));
+    }
+
+    app.find_note_strictly(&uuid_or_name)
         .and_then(|note| app.edit_note(&note, false).map_err(|e| e.into()))
         .and_then(|note| app.update_note(&note).map_err(|e| e.into()))
 }
 
-fn list_notes(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()>{
+fn append_to_note(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
+    let uuid_or_name = sub_matches.value_of("path").unwrap().to_string();
+    let text = match sub_matches.values_of("text") {
+        Some(words) => words.collect::<Vec<&str>>().join(" "),
+        None => read_stdin()?
+    };
+
+    app.find_note_strictly(&uuid_or_name)
+        .and_then(|note| app.append_text(&note, &text))
+        .and_then(|note| app.update_note(&note))
+}
+
+/// Reads the whole stdin, empty input is rejected so that a broken pipe
+/// does not wipe a note
+fn read_stdin() -> Result<String> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    if text.trim().is_empty() {
+        return Err(NoteError::EditError("No text on stdin".to_string()).into());
+    }
+    Ok(text)
+}
+
+fn list_notes(sub_matches: &ArgMatches, app: &AppleNotes, output: OutputFormat) -> Result<()>{
     let _show_uuid = sub_matches.is_present("uuid");
     let print_names_only = sub_matches.is_present("names");
     let show_only_deleted = sub_matches.is_present("deleted");
 
-    app.get_notes()
+    let notes = match sub_matches.value_of("tag") {
+        Some(tag) => app.get_notes_with_tag(tag),
+        None => app.get_notes()
+    };
+
+    let notes = match sub_matches.value_of("since").map(|since| parse_since(since).unwrap()) {
+        Some(since) => notes.and_then(|notes| app.get_notes_modified_since(&since)
+            .map(|modified| notes.intersection(&modified).cloned().collect())),
+        None => notes
+    };
+
+    notes
         .and_then(|notes| {
 
-            let notes = if show_only_deleted {
+            let notes: Vec<LocalNote> = if show_only_deleted {
                 notes.into_iter().filter(|note| note.metadata.locally_deleted).collect()
             } else {
-                notes
+                notes.into_iter().collect()
             };
 
             let max_len = notes.iter()
@@ -123,8 +585,18 @@ fn list_notes(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()>{
                 .max()
                 .unwrap_or(0);
 
-            notes.iter().
-                sorted_by_key(|note| format!("{}_{}",&note.metadata.subfolder, &note.body[0].subject()))
+            let sorted: Vec<&LocalNote> = match sub_matches.value_of("sort") {
+                Some("modified") => notes.iter().sorted_by_key(|note| Reverse(note.metadata.date)).collect(),
+                Some("created") => notes.iter().sorted_by_key(|note| Reverse(note.metadata.created_or_modified())).collect(),
+                _ => notes.iter().sorted_by_key(|note| format!("{}_{}",&note.metadata.subfolder, &note.body[0].subject())).collect()
+            };
+
+            if output == OutputFormat::Json {
+                print_json(&sorted.into_iter().map(NoteSummary::from_note).collect::<Vec<NoteSummary>>());
+                return Ok(());
+            }
+
+            sorted.into_iter()
                 .for_each(|ee| {
                     let titles = ee.body.iter()
                         .map(|body| body.subject())
@@ -159,6 +631,16 @@ fn list_notes(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()>{
 
 fn new(sub_matches: &ArgMatches, app: &AppleNotes) -> Result<()> {
     let folder = sub_matches.value_of("folder").unwrap_or("").to_string();
+
+    if sub_matches.is_present("stdin") {
+        // Apple Notes uses the first line as subject
+        let text = match sub_matches.value_of("title") {
+            Some(title) => format!("{}\n{}", title, read_stdin()?),
+            None => read_stdin()?
+        };
+        return app.create_note_with_text(&text, &folder).map(|_| ());
+    }
+
     let subject = sub_matches.value_of("title").unwrap().to_string();
 
     app.create_new_note(&subject,&folder)
@@ -1,3 +1,14 @@
 extern crate clap;
+extern crate clap_generate;
+extern crate chrono;
+extern crate apnotes_lib;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate itertools;
+extern crate tiny_http;
+#[macro_use]
+extern crate log;
 
-pub mod app;
\ No newline at end of file
+pub mod app;
@@ -0,0 +1,491 @@
+use std::cell::Cell;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Style, Color};
+use tui::terminal::Frame;
+use tui::widgets::{Block, Borders, Paragraph};
+
+/// Spaces that get inserted instead of a tab
+const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Normal,
+    Insert,
+}
+
+/// What the ui has to do after the editor handled a key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditorAction {
+    Continue,
+    Save,
+    Discard,
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    lines: Vec<String>,
+    row: usize,
+    col: usize,
+}
+
+/// Multi-line text editor with vim keys in normal mode and readline keys in
+/// insert mode. Ctrl-s saves in both modes, Ctrl-z undoes the last change
+pub struct Editor {
+    lines: Vec<String>,
+    row: usize,
+    /// Position inside the line in chars
+    col: usize,
+    mode: Mode,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// Every insert mode session is undone at once, so it is only snapshotted once
+    insert_snapshot_taken: bool,
+    /// First key of two key commands like dd and gg
+    pending: Option<char>,
+    /// Set by the first Esc if there are unsaved changes
+    confirm_discard: bool,
+    original: String,
+    /// First visible row and column, updated while rendering
+    offset: Cell<(usize, usize)>,
+}
+
+impl Editor {
+
+    pub fn new(text: &str) -> Editor {
+        Editor {
+            lines: text.split('\n').map(|line| line.to_string()).collect(),
+            row: 0,
+            col: 0,
+            mode: Mode::Normal,
+            undo: vec![],
+            redo: vec![],
+            insert_snapshot_taken: false,
+            pending: None,
+            confirm_discard: false,
+            original: text.to_string(),
+            offset: Cell::new((0, 0)),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.text() != self.original
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> EditorAction {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('s') if ctrl => return EditorAction::Save,
+            KeyCode::Char('z') if ctrl => {
+                self.undo();
+                return EditorAction::Continue;
+            }
+            _ => {}
+        }
+
+        let action = match self.mode {
+            Mode::Normal => self.normal_key(key, ctrl),
+            Mode::Insert => {
+                self.insert_key(key, ctrl);
+                EditorAction::Continue
+            }
+        };
+        self.clamp_col();
+        action
+    }
+
+    fn normal_key(&mut self, key: KeyEvent, ctrl: bool) -> EditorAction {
+        let pending = self.pending.take();
+        if key.code != KeyCode::Esc {
+            self.confirm_discard = false;
+        }
+
+        match key.code {
+            KeyCode::Esc => {
+                if self.is_modified() && !self.confirm_discard {
+                    self.confirm_discard = true;
+                } else {
+                    return EditorAction::Discard;
+                }
+            }
+            KeyCode::Char('r') if ctrl => self.redo(),
+            KeyCode::Char('h') | KeyCode::Left => self.col = self.col.saturating_sub(1),
+            KeyCode::Char('l') | KeyCode::Right => self.col += 1,
+            KeyCode::Char('j') | KeyCode::Down => self.move_row(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_row(-1),
+            KeyCode::PageDown => self.move_row(20),
+            KeyCode::PageUp => self.move_row(-20),
+            KeyCode::Char('0') | KeyCode::Home => self.col = 0,
+            KeyCode::Char('$') | KeyCode::End => self.col = self.line_len(),
+            KeyCode::Char('w') => self.next_word(),
+            KeyCode::Char('b') => self.previous_word(),
+            KeyCode::Char('g') if pending == Some('g') => {
+                self.row = 0;
+                self.col = 0;
+            }
+            KeyCode::Char('G') => self.row = self.lines.len() - 1,
+            KeyCode::Char('i') => self.insert_mode(),
+            KeyCode::Char('a') => {
+                self.col = (self.col + 1).min(self.line_len());
+                self.insert_mode();
+            }
+            KeyCode::Char('I') => {
+                self.col = 0;
+                self.insert_mode();
+            }
+            KeyCode::Char('A') => {
+                self.col = self.line_len();
+                self.insert_mode();
+            }
+            KeyCode::Char('o') => {
+                self.insert_mode();
+                self.change();
+                self.row += 1;
+                self.lines.insert(self.row, String::new());
+                self.col = 0;
+            }
+            KeyCode::Char('O') => {
+                self.insert_mode();
+                self.change();
+                self.lines.insert(self.row, String::new());
+                self.col = 0;
+            }
+            KeyCode::Char('x') | KeyCode::Delete if self.col < self.line_len() => {
+                self.change();
+                self.delete_chars(self.col, self.col + 1);
+            }
+            KeyCode::Char('D') => {
+                self.change();
+                self.delete_chars(self.col, self.line_len());
+            }
+            KeyCode::Char('d') if pending == Some('d') => {
+                self.change();
+                if self.lines.len() > 1 {
+                    self.lines.remove(self.row);
+                    self.row = self.row.min(self.lines.len() - 1);
+                } else {
+                    self.lines[0].clear();
+                }
+                self.col = 0;
+            }
+            KeyCode::Char('u') => self.undo(),
+            KeyCode::Char(c @ 'd') | KeyCode::Char(c @ 'g') => self.pending = Some(c),
+            _ => {}
+        }
+        EditorAction::Continue
+    }
+
+    fn insert_key(&mut self, key: KeyEvent, ctrl: bool) {
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.col = self.col.saturating_sub(1);
+            }
+            KeyCode::Char('a') if ctrl => self.col = 0,
+            KeyCode::Char('e') if ctrl => self.col = self.line_len(),
+            KeyCode::Char('b') if ctrl => self.col = self.col.saturating_sub(1),
+            KeyCode::Char('f') if ctrl => self.col += 1,
+            KeyCode::Char('p') if ctrl => self.move_row(-1),
+            KeyCode::Char('n') if ctrl => self.move_row(1),
+            KeyCode::Char('k') if ctrl => {
+                self.change();
+                self.delete_chars(self.col, self.line_len());
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.change();
+                self.delete_chars(0, self.col);
+                self.col = 0;
+            }
+            KeyCode::Char('w') if ctrl => {
+                self.change();
+                let end = self.col;
+                self.previous_word_in_line();
+                self.delete_chars(self.col, end);
+            }
+            KeyCode::Char('d') if ctrl => self.delete_forward(),
+            KeyCode::Char(_) if ctrl => {}
+            KeyCode::Char(c) => self.insert(&c.to_string()),
+            KeyCode::Tab => self.insert(&" ".repeat(TAB_WIDTH)),
+            KeyCode::Enter => {
+                self.change();
+                let index = byte_index(&self.lines[self.row], self.col);
+                let rest = self.lines[self.row].split_off(index);
+                self.row += 1;
+                self.lines.insert(self.row, rest);
+                self.col = 0;
+            }
+            KeyCode::Backspace => {
+                if self.col > 0 {
+                    self.change();
+                    self.delete_chars(self.col - 1, self.col);
+                    self.col -= 1;
+                } else if self.row > 0 {
+                    self.change();
+                    let line = self.lines.remove(self.row);
+                    self.row -= 1;
+                    self.col = self.line_len();
+                    self.lines[self.row].push_str(&line);
+                }
+            }
+            KeyCode::Delete => self.delete_forward(),
+            KeyCode::Left => self.col = self.col.saturating_sub(1),
+            KeyCode::Right => self.col += 1,
+            KeyCode::Up => self.move_row(-1),
+            KeyCode::Down => self.move_row(1),
+            KeyCode::Home => self.col = 0,
+            KeyCode::End => self.col = self.line_len(),
+            _ => {}
+        }
+    }
+
+    /// Renders the text into area and places the terminal cursor
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>, area: Rect, title: &str) {
+        let height = area.height.saturating_sub(2) as usize;
+        let width = area.width.saturating_sub(2) as usize;
+
+        // Scrolls just enough to keep the cursor visible
+        let (mut top, mut left) = self.offset.get();
+        if self.row < top {
+            top = self.row;
+        } else if height > 0 && self.row >= top + height {
+            top = self.row + 1 - height;
+        }
+        if self.col < left {
+            left = self.col;
+        } else if width > 0 && self.col >= left + width {
+            left = self.col + 1 - width;
+        }
+        self.offset.set((top, left));
+
+        let mode = match self.mode {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+        };
+        let modified = if self.is_modified() { " [+]" } else { "" };
+        let title = if self.confirm_discard {
+            format!("{} [{}]{} unsaved changes, Esc again to discard", title, mode, modified)
+        } else {
+            format!("{} [{}]{}", title, mode, modified)
+        };
+
+        let paragraph = Paragraph::new(self.text())
+            .block(Block::default().title(title).borders(Borders::ALL).border_style(Style::default().fg(Color::Cyan)))
+            .style(Style::default().fg(Color::White))
+            .scroll((top as u16, left as u16));
+
+        f.render_widget(paragraph, area);
+        f.set_cursor(
+            area.x + 1 + (self.col - left) as u16,
+            area.y + 1 + (self.row - top) as u16
+        );
+    }
+
+    fn insert_mode(&mut self) {
+        self.mode = Mode::Insert;
+        self.insert_snapshot_taken = false;
+    }
+
+    /// Has to be called before every modification, so that it can be undone
+    fn change(&mut self) {
+        if self.mode == Mode::Insert && self.insert_snapshot_taken {
+            return;
+        }
+        self.insert_snapshot_taken = self.mode == Mode::Insert;
+        self.undo.push(self.snapshot());
+        self.redo.clear();
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot { lines: self.lines.clone(), row: self.row, col: self.col }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.lines = snapshot.lines;
+        self.row = snapshot.row;
+        self.col = snapshot.col;
+        self.insert_snapshot_taken = false;
+    }
+
+    fn undo(&mut self) {
+        if let Some(snapshot) = self.undo.pop() {
+            self.redo.push(self.snapshot());
+            self.restore(snapshot);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(snapshot) = self.redo.pop() {
+            self.undo.push(self.snapshot());
+            self.restore(snapshot);
+        }
+    }
+
+    fn insert(&mut self, text: &str) {
+        self.change();
+        let index = byte_index(&self.lines[self.row], self.col);
+        self.lines[self.row].insert_str(index, text);
+        self.col += text.chars().count();
+    }
+
+    /// Deletes the char under the cursor or joins the next line
+    fn delete_forward(&mut self) {
+        if self.col < self.line_len() {
+            self.change();
+            self.delete_chars(self.col, self.col + 1);
+        } else if self.row + 1 < self.lines.len() {
+            self.change();
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    /// Removes the chars from start to end (exclusive) of the current line
+    fn delete_chars(&mut self, start: usize, end: usize) {
+        let line = &mut self.lines[self.row];
+        let range = byte_index(line, start)..byte_index(line, end);
+        line.replace_range(range, "");
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    fn move_row(&mut self, rows: isize) {
+        let row = self.row as isize + rows;
+        self.row = row.max(0).min(self.lines.len() as isize - 1) as usize;
+    }
+
+    fn clamp_col(&mut self) {
+        let max = match self.mode {
+            Mode::Insert => self.line_len(),
+            Mode::Normal => self.line_len().saturating_sub(1),
+        };
+        self.col = self.col.min(max);
+    }
+
+    /// Moves to the start of the next word, continues on the next line at the end of a line
+    fn next_word(&mut self) {
+        let chars: Vec<char> = self.lines[self.row].chars().collect();
+        let mut col = self.col;
+        while col < chars.len() && !chars[col].is_whitespace() {
+            col += 1;
+        }
+        while col < chars.len() && chars[col].is_whitespace() {
+            col += 1;
+        }
+        if col >= chars.len() && self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        } else {
+            self.col = col;
+        }
+    }
+
+    fn previous_word(&mut self) {
+        if self.col == 0 && self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len();
+        }
+        self.previous_word_in_line();
+    }
+
+    fn previous_word_in_line(&mut self) {
+        let chars: Vec<char> = self.lines[self.row].chars().collect();
+        let mut col = self.col.min(chars.len());
+        while col > 0 && chars[col - 1].is_whitespace() {
+            col -= 1;
+        }
+        while col > 0 && !chars[col - 1].is_whitespace() {
+            col -= 1;
+        }
+        self.col = col;
+    }
+}
+
+/// Byte offset of the char at position col, the length of the line if col is beyond it
+fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map(|(index, _)| index).unwrap_or_else(|| line.len())
+}
+
+#[cfg(test)]
+mod editor_tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn type_keys(editor: &mut Editor, keys: &str) {
+        for c in keys.chars() {
+            editor.handle_key(key(KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn insert_and_save() {
+        let mut editor = Editor::new("Title");
+        type_keys(&mut editor, "A text");
+        editor.handle_key(key(KeyCode::Esc));
+        assert_eq!(editor.text(), "Title text");
+        assert!(editor.is_modified());
+        assert_eq!(editor.handle_key(ctrl('s')), EditorAction::Save);
+    }
+
+    #[test]
+    fn split_and_join_lines() {
+        let mut editor = Editor::new("firstsecond");
+        type_keys(&mut editor, "llllli");
+        editor.handle_key(key(KeyCode::Enter));
+        assert_eq!(editor.text(), "first\nsecond");
+        editor.handle_key(key(KeyCode::Backspace));
+        assert_eq!(editor.text(), "firstsecond");
+    }
+
+    /// A whole insert mode session is undone at once
+    #[test]
+    fn undo_and_redo() {
+        let mut editor = Editor::new("one\ntwo");
+        type_keys(&mut editor, "dd");
+        assert_eq!(editor.text(), "two");
+        type_keys(&mut editor, "u");
+        assert_eq!(editor.text(), "one\ntwo");
+        editor.handle_key(ctrl('r'));
+        assert_eq!(editor.text(), "two");
+
+        type_keys(&mut editor, "Aabc");
+        editor.handle_key(key(KeyCode::Esc));
+        assert_eq!(editor.text(), "twoabc");
+        type_keys(&mut editor, "u");
+        assert_eq!(editor.text(), "two");
+    }
+
+    #[test]
+    fn multibyte_chars() {
+        let mut editor = Editor::new("äöü");
+        type_keys(&mut editor, "lx");
+        assert_eq!(editor.text(), "äü");
+        type_keys(&mut editor, "iß");
+        assert_eq!(editor.text(), "äßü");
+    }
+
+    /// Unsaved changes need a second Esc to get discarded
+    #[test]
+    fn discard() {
+        let mut editor = Editor::new("text");
+        assert_eq!(editor.handle_key(key(KeyCode::Esc)), EditorAction::Discard);
+
+        let mut editor = Editor::new("text");
+        type_keys(&mut editor, "x");
+        assert_eq!(editor.handle_key(key(KeyCode::Esc)), EditorAction::Continue);
+        assert_eq!(editor.handle_key(key(KeyCode::Esc)), EditorAction::Discard);
+    }
+}
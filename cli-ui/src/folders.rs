@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, HashSet};
+use apnotes_lib::notes::localnote::LocalNote;
+use apnotes_lib::notes::traits::identifyable_note::IdentifiableNote;
+use tui::style::{Style, Color};
+use tui::text::{Span, Spans};
+use tui::widgets::ListItem;
+
+/// Separator of nested imap folders, e.g. Notes.Work.Projects
+const FOLDER_SEPARATOR: char = '.';
+
+/// A folder of the tree, the counts include the notes of all subfolders
+#[derive(Debug, Default, Clone)]
+pub struct FolderNode {
+    /// Full imap folder name
+    pub folder: String,
+    pub depth: usize,
+    pub notes: usize,
+    pub merges: usize,
+    /// Notes that are new, edited or flagged for deletion and not synced yet
+    pub changes: usize,
+    pub has_children: bool,
+}
+
+impl FolderNode {
+    fn name(&self) -> &str {
+        self.folder.rsplit(FOLDER_SEPARATOR).next().unwrap_or(&self.folder)
+    }
+}
+
+/// Collapsible folder tree, the first entry lists the notes of all folders
+#[derive(Default)]
+pub struct FolderTree {
+    nodes: Vec<FolderNode>,
+    total: FolderNode,
+    collapsed: HashSet<String>,
+    /// Index inside the visible entries, 0 is the "All notes" entry
+    selected: usize,
+}
+
+impl FolderTree {
+
+    /// Rebuilds the folders from the notes, the selected folder and the collapsed
+    /// folders are kept if they still exist
+    pub fn update(&mut self, notes: &[LocalNote]) {
+        let selected_folder = self.selected_folder().map(|folder| folder.to_string());
+
+        // Keyed by the path, so that subfolders always follow their parent. Joined names
+        // would sort "Work Archive" between "Work" and "Work.Projects"
+        let mut folders: BTreeMap<Vec<String>, FolderNode> = BTreeMap::new();
+        self.total = FolderNode::default();
+
+        for note in notes {
+            let folder = note.folder();
+            let parts: Vec<&str> = folder.split(FOLDER_SEPARATOR).collect();
+
+            // Parent folders are part of the tree even if they contain no notes themselves
+            for depth in 0..parts.len() {
+                let path: Vec<String> = parts[..=depth].iter().map(|part| part.to_string()).collect();
+                let name = path.join(&FOLDER_SEPARATOR.to_string());
+                let node = folders.entry(path).or_insert_with(|| FolderNode { folder: name, depth, ..FolderNode::default() });
+                count(node, note);
+                node.has_children |= depth + 1 < parts.len();
+            }
+            count(&mut self.total, note);
+        }
+
+        self.nodes = folders.into_iter().map(|(_, node)| node).collect();
+        let nodes = &self.nodes;
+        self.collapsed.retain(|folder| nodes.iter().any(|node| &node.folder == folder));
+        self.select_folder(selected_folder);
+    }
+
+    /// None if all notes are selected
+    pub fn selected_folder(&self) -> Option<&str> {
+        match self.selected {
+            0 => None,
+            index => self.visible().get(index - 1).map(|node| node.folder.as_str())
+        }
+    }
+
+    /// Notes inside subfolders of the selected folder match as well
+    pub fn contains(&self, note: &LocalNote) -> bool {
+        match self.selected_folder() {
+            None => true,
+            Some(folder) => {
+                let note_folder = note.folder();
+                note_folder == folder || note_folder.starts_with(&format!("{}{}", folder, FOLDER_SEPARATOR))
+            }
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn next(&mut self) {
+        if self.selected < self.visible().len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+        }
+    }
+
+    /// Collapses the selected folder or expands it again
+    pub fn toggle(&mut self) {
+        if let Some(folder) = self.selected_folder().map(|folder| folder.to_string()) {
+            if !self.collapsed.remove(&folder) {
+                self.collapsed.insert(folder);
+            }
+        }
+    }
+
+    pub fn items<'a>(&self) -> Vec<ListItem<'a>> {
+        let mut items = vec![ListItem::new(label("All notes".to_string(), &self.total))];
+        items.extend(self.visible().into_iter().map(|node| {
+            let marker = match (node.has_children, self.collapsed.contains(&node.folder)) {
+                (false, _) => "  ",
+                (true, true) => "+ ",
+                (true, false) => "- ",
+            };
+            ListItem::new(label(format!("{}{}{}", "  ".repeat(node.depth), marker, node.name()), node))
+        }));
+        items
+    }
+
+    /// Folders whose parents are not collapsed
+    fn visible(&self) -> Vec<&FolderNode> {
+        self.nodes.iter()
+            .filter(|node| !self.collapsed.iter().any(|collapsed| node.folder.starts_with(&format!("{}{}", collapsed, FOLDER_SEPARATOR))))
+            .collect()
+    }
+
+    fn select_folder(&mut self, folder: Option<String>) {
+        self.selected = match folder {
+            Some(folder) => self.visible().iter()
+                .position(|node| node.folder == folder)
+                .map(|index| index + 1)
+                .unwrap_or(0),
+            None => 0
+        };
+    }
+}
+
+fn count(node: &mut FolderNode, note: &LocalNote) {
+    node.notes += 1;
+    if note.needs_merge() {
+        node.merges += 1;
+    }
+    if note.content_changed_locally() || note.metadata.new || note.metadata.locally_deleted {
+        node.changes += 1;
+    }
+}
+
+/// Name and note count, merges are marked with M and local changes with *
+/// in the colors the note list uses
+fn label<'a>(name: String, node: &FolderNode) -> Spans<'a> {
+    let mut spans = vec![Span::raw(format!("{} ({})", name, node.notes))];
+    if node.merges > 0 {
+        spans.push(Span::styled(format!(" M{}", node.merges), Style::default().fg(Color::LightBlue)));
+    }
+    if node.changes > 0 {
+        spans.push(Span::styled(format!(" *{}", node.changes), Style::default().fg(Color::LightYellow)));
+    }
+    Spans::from(spans)
+}
+
+#[cfg(test)]
+mod folders_tests {
+    use super::*;
+    use apnotes_lib::builder::NotesMetadataBuilder;
+
+    fn note(folder: &str) -> LocalNote {
+        LocalNote { metadata: NotesMetadataBuilder::new().with_folder(folder.to_string()).build(), body: vec![] }
+    }
+
+    /// Subfolders follow their parent, even if a sibling sorts between them by name
+    #[test]
+    fn subfolders_follow_their_parent() {
+        let mut tree = FolderTree::default();
+        tree.update(&[note("Work.Projects"), note("Work Archive"), note("Work-Old"), note("Work")]);
+
+        let folders: Vec<(&str, usize)> = tree.nodes.iter().map(|node| (node.folder.as_str(), node.depth)).collect();
+        assert_eq!(folders, vec![
+            ("Notes", 0),
+            ("Notes.Work", 1),
+            ("Notes.Work.Projects", 2),
+            ("Notes.Work Archive", 1),
+            ("Notes.Work-Old", 1),
+        ]);
+        assert_eq!(tree.nodes[1].notes, 2);
+    }
+}
@@ -4,14 +4,22 @@ extern crate log;
 extern crate diesel;
 
 mod ui;
+mod folders;
+mod editor;
+mod markdown;
+mod merge_view;
 
 use std::sync::{mpsc, Mutex, Arc};
+use std::sync::atomic::AtomicBool;
 use std::time::{Duration};
 use std::{thread};
 use apnotes_lib::db::{SqliteDBConnection};
 use std::thread::{sleep, JoinHandle};
 use crate::Outcome::{Success, Failure, End, Busy};
 use apnotes_lib::AppleNotes;
+use apnotes_lib::config::TuiSettings;
+use apnotes_lib::error::{Result, ErrorCode};
+use std::path::PathBuf;
 use std::sync::mpsc::{
     Sender,
     Receiver
@@ -19,7 +27,8 @@ use std::sync::mpsc::{
 use crossterm::event::KeyEvent;
 use crate::ui::{
     UiState,
-    Ui
+    Ui,
+    Focus
 };
 use tui::style::Color;
 use tui::widgets::List;
@@ -51,27 +60,38 @@ struct AppStuff {
 
 struct App {
     apple_notes: Arc<Mutex<AppleNotes>>,
+    settings: TuiSettings,
     app_stuff: Arc<Mutex<AppStuff>>
 }
 
 impl App {
 
-    pub fn new(action_receiver: Receiver<Task>, event_sender: Sender<Event<KeyEvent>>) -> App {
+    pub fn new(action_receiver: Receiver<Task>, event_sender: Sender<Event<KeyEvent>>, db_path: Option<PathBuf>) -> Result<App> {
 
-        let profile = apnotes_lib::get_user_profile();
-        let db_connection = SqliteDBConnection::new();
+        let profile = apnotes_lib::get_user_profile()?;
+        let db_path = match db_path {
+            Some(db_path) => db_path,
+            None => profile.database_path()?
+        };
+        let db_connection = SqliteDBConnection::open(&db_path)?;
         let connection = Box::new(db_connection);
-        let app = apnotes_lib::AppleNotes::new(profile.unwrap(), connection);
+        let settings = profile.tui_settings().clone();
+        let app = apnotes_lib::AppleNotesBuilder::new()
+            .with_profile(profile)
+            .with_database(connection)
+            .with_lock_file(apnotes_lib::lock::lock_file_of(&db_path))
+            .build()?;
 
         let app = App {
             apple_notes: Arc::new(Mutex::new(app)),
+            settings,
             app_stuff: Arc::new(Mutex::new(AppStuff {
                 action_receiver,
                 event_sender
             }))
         };
 
-        app
+        Ok(app)
 
     }
 
@@ -156,19 +176,53 @@ impl App {
 
 }
 
+/// Returns the path passed with --db, the only argument the tui knows
+fn db_path_argument() -> std::result::Result<Option<PathBuf>, String> {
+    let mut args = std::env::args().skip(1);
+    let mut db_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => match args.next() {
+                Some(path) => db_path = Some(PathBuf::from(path)),
+                None => return Err("--db needs the path of the database".to_string())
+            },
+            arg if arg.starts_with("--db=") => db_path = Some(PathBuf::from(&arg["--db=".len()..])),
+            arg => return Err(format!("Unknown argument {}, usage: apnotes-tui [--db <path>]", arg))
+        }
+    }
+
+    Ok(db_path)
+}
+
 fn main() {
 
+    let db_path = match db_path_argument() {
+        Ok(db_path) => db_path,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
     let (event_sender, event_receiver) = mpsc::channel();
     let (action_tx, action_rx) = mpsc::channel::<Task>();
 
-    let app = App::new(action_rx, event_sender.clone());
+    let app = match App::new(action_rx, event_sender.clone(), db_path) {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("Error: {}\n{} - ({})", e.human_readable_error_message(), e.to_string(), e.error_code());
+            std::process::exit(e.error_code());
+        }
+    };
 
     let handle = app.start_action_event_loop();
 
     let ui_state = UiState {
         action_sender: action_tx,
         event_receiver,
-        event_sender: Arc::new(Mutex::new(event_sender))
+        event_sender: Arc::new(Mutex::new(event_sender)),
+        input_paused: Arc::new(AtomicBool::new(false))
     };
 
     let mut ui = Ui {
@@ -177,15 +231,21 @@ fn main() {
         color: Color::Reset,
         status: "Started".to_string(),
         app: app.apple_notes,
+        settings: app.settings,
         ui_state: ui_state,
         entries: vec![],
         keyword: None,
         items: vec![],
         list: List::new(Vec::new()),
-        text: "".to_string(),
+        text: Default::default(),
+        rendered: true,
         scroll_amount: 0,
         in_search_mode: false,
-        new_note_mode: false
+        new_note_mode: false,
+        folder_tree: Default::default(),
+        focus: Focus::Notes,
+        editor: None,
+        merge_view: None
     };
 
     ui.run().unwrap();
@@ -0,0 +1,292 @@
+use pulldown_cmark::{Event, Options, Parser, Tag, CodeBlockKind};
+use tui::style::{Style, Color, Modifier};
+use tui::text::{Span, Spans, Text};
+
+/// Width of horizontal rules and separators
+const RULE_WIDTH: usize = 40;
+
+/// Renders the markdown of a note with styled headings, emphasis, lists,
+/// checklists, code and links. Line breaks inside paragraphs are kept, because
+/// notes are written line by line
+pub fn render(markdown: &str) -> Text<'static> {
+    let mut renderer = Renderer::default();
+    let options = Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES;
+    for event in Parser::new_ext(markdown, options) {
+        renderer.event(event);
+    }
+    renderer.finish()
+}
+
+/// Line that separates the bodies of a note that needs to be merged
+pub fn separator(title: &str) -> Spans<'static> {
+    let line = "─".repeat(RULE_WIDTH.saturating_sub(title.chars().count() + 2) / 2);
+    Spans::from(Span::styled(format!("{} {} {}", line, title, line), Style::default().fg(Color::LightBlue)))
+}
+
+#[derive(Default)]
+struct Renderer {
+    lines: Vec<Spans<'static>>,
+    current: Vec<Span<'static>>,
+    styles: Vec<Style>,
+    /// Next number of every open list, None for bullet lists
+    lists: Vec<Option<u64>>,
+    /// One entry per open list item, true for checklist items that pushed the style of their text
+    items: Vec<bool>,
+    quote_depth: usize,
+    in_code_block: bool,
+    /// Url and text of the open link
+    link: Option<(String, String)>,
+}
+
+impl Renderer {
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.in_code_block => {
+                for line in text.lines() {
+                    self.current.push(Span::styled(format!("  {}", line), code_style()));
+                    self.flush();
+                }
+            }
+            Event::Text(text) => {
+                if let Some((_, link_text)) = &mut self.link {
+                    link_text.push_str(&text);
+                }
+                let style = self.style();
+                self.current.push(Span::styled(text.to_string(), style));
+            }
+            Event::Code(code) => self.current.push(Span::styled(code.to_string(), code_style())),
+            Event::Html(html) => self.current.push(Span::raw(html.trim_end_matches('\n').to_string())),
+            Event::SoftBreak | Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.flush();
+                self.lines.push(Spans::from(Span::styled("─".repeat(RULE_WIDTH), Style::default().fg(Color::DarkGray))));
+            }
+            Event::TaskListMarker(checked) => {
+                // Replaces the bullet of the item
+                self.current.pop();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                if checked {
+                    self.current.push(Span::styled(format!("{}[x] ", indent), Style::default().fg(Color::Green)));
+                    self.styles.push(Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT));
+                } else {
+                    self.current.push(Span::styled(format!("{}[ ] ", indent), Style::default().fg(Color::Yellow)));
+                    self.styles.push(Style::default());
+                }
+                if let Some(checklist_item) = self.items.last_mut() {
+                    *checklist_item = true;
+                }
+            }
+            Event::FootnoteReference(name) => self.current.push(Span::raw(format!("[^{}]", name))),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading(level) => {
+                self.flush();
+                let style = match level {
+                    1 => Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                    2 => Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                    _ => Style::default().add_modifier(Modifier::BOLD),
+                };
+                self.styles.push(style);
+            }
+            Tag::BlockQuote => {
+                self.flush();
+                self.quote_depth += 1;
+                self.styles.push(Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC));
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                self.in_code_block = true;
+                if let CodeBlockKind::Fenced(language) = kind {
+                    if !language.is_empty() {
+                        self.lines.push(Spans::from(Span::styled(format!("  {}", language), Style::default().fg(Color::DarkGray))));
+                    }
+                }
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                self.items.push(false);
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}{}. ", indent, *number - 1)
+                    }
+                    _ => format!("{}• ", indent)
+                };
+                self.current.push(Span::styled(bullet, Style::default().fg(Color::Yellow)));
+            }
+            Tag::Emphasis => self.styles.push(Style::default().add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self.styles.push(Style::default().add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self.styles.push(Style::default().add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link(_, url, _) | Tag::Image(_, url, _) => {
+                self.link = Some((url.to_string(), String::new()));
+                self.styles.push(Style::default().fg(Color::LightBlue).add_modifier(Modifier::UNDERLINED));
+            }
+            Tag::Paragraph | Tag::Table(_) | Tag::TableHead | Tag::TableRow | Tag::TableCell
+            | Tag::FootnoteDefinition(_) => {}
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading(_) => {
+                self.styles.pop();
+                self.flush();
+                self.blank_line();
+            }
+            Tag::Paragraph => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            }
+            Tag::BlockQuote => {
+                self.styles.pop();
+                self.flush();
+                self.quote_depth -= 1;
+                self.blank_line();
+            }
+            Tag::CodeBlock(_) => {
+                self.in_code_block = false;
+                self.blank_line();
+            }
+            Tag::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            }
+            Tag::Item => {
+                self.flush();
+                if self.items.pop().unwrap_or(false) {
+                    self.styles.pop();
+                }
+            }
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough => { self.styles.pop(); }
+            Tag::Link(_, _, _) | Tag::Image(_, _, _) => {
+                self.styles.pop();
+                if let Some((url, text)) = self.link.take() {
+                    if url != text {
+                        self.current.push(Span::styled(format!(" <{}>", url), Style::default().fg(Color::DarkGray)));
+                    }
+                }
+            }
+            Tag::TableCell => self.current.push(Span::styled(" │ ", Style::default().fg(Color::DarkGray))),
+            Tag::TableHead | Tag::TableRow => self.flush(),
+            Tag::Table(_) => self.blank_line(),
+            Tag::FootnoteDefinition(_) => self.flush(),
+        }
+    }
+
+    fn style(&self) -> Style {
+        self.styles.iter().fold(Style::default(), |style, patch| style.patch(*patch))
+    }
+
+    /// Ends the current line
+    fn flush(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+        let mut spans = std::mem::take(&mut self.current);
+        if self.quote_depth > 0 {
+            spans.insert(0, Span::styled("│ ".repeat(self.quote_depth), Style::default().fg(Color::DarkGray)));
+        }
+        self.lines.push(Spans::from(spans));
+    }
+
+    fn blank_line(&mut self) {
+        if self.lines.last().map_or(false, |line| line.width() > 0) {
+            self.lines.push(Spans::default());
+        }
+    }
+
+    fn finish(mut self) -> Text<'static> {
+        self.flush();
+        while self.lines.last().map_or(false, |line| line.width() == 0) {
+            self.lines.pop();
+        }
+        Text::from(self.lines)
+    }
+}
+
+fn code_style() -> Style {
+    Style::default().fg(Color::Yellow)
+}
+
+#[cfg(test)]
+mod markdown_tests {
+    use super::*;
+
+    fn lines(markdown: &str) -> Vec<String> {
+        render(markdown).lines.iter()
+            .map(|line| line.0.iter().map(|span| span.content.to_string()).collect())
+            .collect()
+    }
+
+    /// Style of the span that contains the text
+    fn style_of(markdown: &str, text: &str) -> Style {
+        render(markdown).lines.iter()
+            .flat_map(|line| line.0.iter())
+            .find(|span| span.content.contains(text))
+            .map(|span| span.style)
+            .unwrap()
+    }
+
+    fn crossed_out(style: Style) -> bool {
+        style.add_modifier.contains(Modifier::CROSSED_OUT)
+    }
+
+    #[test]
+    fn lines_are_kept() {
+        assert_eq!(lines("# Title\nfirst\nsecond"), vec!["Title", "", "first", "second"]);
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(lines("- one\n- two\n\n1. first\n2. second"), vec!["• one", "• two", "", "1. first", "2. second"]);
+        assert_eq!(lines("- one\n  - nested"), vec!["• one", "  • nested"]);
+    }
+
+    #[test]
+    fn checklist() {
+        let markdown = "- [x] done\n- [ ] open\n\nafter";
+        assert_eq!(lines(markdown), vec!["[x] done", "[ ] open", "", "after"]);
+        assert!(crossed_out(style_of(markdown, "done")));
+        assert!(!crossed_out(style_of(markdown, "open")));
+        assert!(!crossed_out(style_of(markdown, "after")));
+    }
+
+    /// In loose lists every item is a paragraph that gets flushed before the item ends
+    #[test]
+    fn loose_checklist() {
+        let markdown = "- [x] done\n\n- [ ] open\n\nafter";
+        assert!(crossed_out(style_of(markdown, "done")));
+        assert!(!crossed_out(style_of(markdown, "open")));
+        assert!(!crossed_out(style_of(markdown, "after")));
+    }
+
+    #[test]
+    fn multi_line_checklist_item() {
+        let markdown = "- [x] done\n  still done\n\nafter";
+        assert_eq!(lines(markdown), vec!["[x] done", "still done", "", "after"]);
+        assert!(crossed_out(style_of(markdown, "still done")));
+        assert!(!crossed_out(style_of(markdown, "after")));
+    }
+
+    #[test]
+    fn links_show_their_url() {
+        assert_eq!(lines("[apnotes](https://github.com/findus/apnotes)"), vec!["apnotes <https://github.com/findus/apnotes>"]);
+        assert_eq!(lines("<https://github.com>"), vec!["https://github.com"]);
+    }
+}
@@ -0,0 +1,306 @@
+use std::cell::Cell;
+use apnotes_lib::merge::{hunks, Hunk};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Style, Color, Modifier};
+use tui::terminal::Frame;
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Paragraph};
+use crate::editor::{Editor, EditorAction};
+
+/// Which version of a conflict ends up in the merged text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+    Undecided,
+    Left,
+    Right,
+    /// Left lines followed by the right lines
+    Both,
+}
+
+/// What the ui has to do after the merge view handled a key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeAction {
+    Continue,
+    Save,
+    Cancel,
+}
+
+/// Shows the two versions of an unmerged note side by side, conflicts are
+/// resolved one by one or by editing the merged text by hand
+pub struct MergeView {
+    hunks: Vec<Hunk>,
+    choices: Vec<Choice>,
+    /// Index of the selected hunk, always a conflict if the note has one
+    selected: usize,
+    /// Merged text that gets edited by hand
+    editor: Option<Editor>,
+    /// First visible row, updated while rendering
+    offset: Cell<usize>,
+}
+
+impl MergeView {
+
+    pub fn new(left: &str, right: &str) -> MergeView {
+        let hunks = hunks(left, right);
+        let selected = hunks.iter().position(is_conflict).unwrap_or(0);
+        MergeView {
+            choices: vec![Choice::Undecided; hunks.len()],
+            hunks,
+            selected,
+            editor: None,
+            offset: Cell::new(0),
+        }
+    }
+
+    /// Merged text of the chosen versions, undecided conflicts keep both
+    /// versions marked with < and > like the merge inside the external editor
+    pub fn text(&self) -> String {
+        if let Some(editor) = &self.editor {
+            return editor.text();
+        }
+
+        let mut lines: Vec<String> = vec![];
+        for (hunk, choice) in self.hunks.iter().zip(&self.choices) {
+            match (hunk, choice) {
+                (Hunk::Same(same), _) => lines.extend(same.iter().cloned()),
+                (Hunk::Conflict { left, .. }, Choice::Left) => lines.extend(left.iter().cloned()),
+                (Hunk::Conflict { right, .. }, Choice::Right) => lines.extend(right.iter().cloned()),
+                (Hunk::Conflict { left, right }, Choice::Both) => lines.extend(left.iter().chain(right).cloned()),
+                (Hunk::Conflict { left, right }, Choice::Undecided) => {
+                    lines.extend(left.iter().map(|line| format!("< {}", line)));
+                    lines.extend(right.iter().map(|line| format!("> {}", line)));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Conflicts without a choice, a text that got edited by hand counts as resolved
+    pub fn unresolved(&self) -> usize {
+        if self.editor.is_some() {
+            return 0;
+        }
+        self.hunks.iter().zip(&self.choices)
+            .filter(|(hunk, choice)| is_conflict(hunk) && **choice == Choice::Undecided)
+            .count()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> MergeAction {
+        if let Some(editor) = self.editor.as_mut() {
+            return match editor.handle_key(key) {
+                EditorAction::Save => MergeAction::Save,
+                // Back to the choices of the conflicts
+                EditorAction::Discard => {
+                    self.editor = None;
+                    MergeAction::Continue
+                }
+                EditorAction::Continue => MergeAction::Continue,
+            };
+        }
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key.code {
+                KeyCode::Char('s') => MergeAction::Save,
+                _ => MergeAction::Continue,
+            };
+        }
+
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Char('n') | KeyCode::Down => self.select_conflict(true),
+            KeyCode::Char('k') | KeyCode::Char('N') | KeyCode::Up => self.select_conflict(false),
+            KeyCode::Char('h') | KeyCode::Left => self.choose(Choice::Left),
+            KeyCode::Char('l') | KeyCode::Right => self.choose(Choice::Right),
+            KeyCode::Char('b') => self.choose(Choice::Both),
+            KeyCode::Char('u') | KeyCode::Backspace => self.choose(Choice::Undecided),
+            KeyCode::Char('e') => self.editor = Some(Editor::new(&self.text())),
+            KeyCode::Esc | KeyCode::Char('q') => return MergeAction::Cancel,
+            _ => {}
+        }
+        MergeAction::Continue
+    }
+
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        if let Some(editor) = &self.editor {
+            editor.render(f, area, "Merge");
+            return;
+        }
+
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(area);
+
+        // Gutter of two chars for the marker of the selected conflict
+        let width = (columns[0].width.saturating_sub(2) as usize).saturating_sub(2).max(1);
+        let (left, right, selected_row) = self.rows(width);
+
+        let height = area.height.saturating_sub(2) as usize;
+        let mut top = self.offset.get();
+        if selected_row < top {
+            top = selected_row;
+        } else if height > 0 && selected_row >= top + height {
+            top = selected_row.saturating_sub(height / 2);
+        }
+        self.offset.set(top);
+
+        let conflicts = self.hunks.iter().filter(|hunk| is_conflict(hunk)).count();
+        let titles = [
+            format!("Version 1, {} of {} conflicts resolved", conflicts - self.unresolved(), conflicts),
+            "Version 2".to_string(),
+        ];
+
+        for ((rows, column), title) in vec![left, right].into_iter().zip(columns).zip(&titles) {
+            let paragraph = Paragraph::new(rows)
+                .block(Block::default().title(title.as_str()).borders(Borders::ALL).border_style(Style::default().fg(Color::Cyan)))
+                .style(Style::default().fg(Color::White))
+                .scroll((top as u16, 0));
+            f.render_widget(paragraph, column);
+        }
+    }
+
+    /// Lines of both columns wrapped to width, conflicts are padded so that both
+    /// sides stay aligned. Also returns the first row of the selected hunk
+    fn rows(&self, width: usize) -> (Vec<Spans<'static>>, Vec<Spans<'static>>, usize) {
+        let mut left_rows = vec![];
+        let mut right_rows = vec![];
+        let mut selected_row = 0;
+
+        for (index, (hunk, choice)) in self.hunks.iter().zip(&self.choices).enumerate() {
+            let selected = index == self.selected && is_conflict(hunk);
+            if index == self.selected {
+                selected_row = left_rows.len();
+            }
+            let gutter = if selected { "▶ " } else { "  " };
+
+            let (left, right, left_style, right_style) = match hunk {
+                Hunk::Same(lines) => (lines, lines, Style::default(), Style::default()),
+                Hunk::Conflict { left, right } => {
+                    let (left_style, right_style) = conflict_styles(*choice);
+                    (left, right, left_style, right_style)
+                }
+            };
+            let (left_style, right_style) = if selected {
+                (left_style.add_modifier(Modifier::BOLD), right_style.add_modifier(Modifier::BOLD))
+            } else {
+                (left_style, right_style)
+            };
+
+            let left = wrap(left, width);
+            let right = wrap(right, width);
+            for row in 0..left.len().max(right.len()).max(1) {
+                left_rows.push(row_spans(gutter, left.get(row), left_style));
+                right_rows.push(row_spans(gutter, right.get(row), right_style));
+            }
+        }
+
+        (left_rows, right_rows, selected_row)
+    }
+
+    fn select_conflict(&mut self, forward: bool) {
+        let next = if forward {
+            (self.selected + 1..self.hunks.len()).find(|index| is_conflict(&self.hunks[*index]))
+        } else {
+            (0..self.selected).rev().find(|index| is_conflict(&self.hunks[*index]))
+        };
+        if let Some(index) = next {
+            self.selected = index;
+        }
+    }
+
+    /// Sets the choice of the selected conflict and moves on to the next one
+    fn choose(&mut self, choice: Choice) {
+        if self.hunks.get(self.selected).map_or(false, is_conflict) {
+            self.choices[self.selected] = choice;
+            if choice != Choice::Undecided {
+                self.select_conflict(true);
+            }
+        }
+    }
+}
+
+fn is_conflict(hunk: &Hunk) -> bool {
+    matches!(hunk, Hunk::Conflict { .. })
+}
+
+/// Chosen lines are green, dropped lines are crossed out
+fn conflict_styles(choice: Choice) -> (Style, Style) {
+    let chosen = Style::default().fg(Color::Green);
+    let dropped = Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT);
+    let undecided = Style::default().fg(Color::Yellow);
+    match choice {
+        Choice::Undecided => (undecided, undecided),
+        Choice::Left => (chosen, dropped),
+        Choice::Right => (dropped, chosen),
+        Choice::Both => (chosen, chosen),
+    }
+}
+
+/// Splits the lines into rows of at most width chars
+fn wrap(lines: &[String], width: usize) -> Vec<String> {
+    lines.iter().flat_map(|line| {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            vec![String::new()]
+        } else {
+            chars.chunks(width).map(|chunk| chunk.iter().collect()).collect()
+        }
+    }).collect()
+}
+
+fn row_spans(gutter: &'static str, line: Option<&String>, style: Style) -> Spans<'static> {
+    Spans::from(vec![
+        Span::styled(gutter, Style::default().fg(Color::Cyan)),
+        Span::styled(line.cloned().unwrap_or_default(), style),
+    ])
+}
+
+#[cfg(test)]
+mod merge_view_tests {
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn merge_view() -> MergeView {
+        MergeView::new("Title\nleft one\nsame\nleft two", "Title\nright one\nsame\nright two")
+    }
+
+    /// Undecided conflicts are marked like inside the external editor
+    #[test]
+    fn undecided() {
+        let view = merge_view();
+        assert_eq!(view.unresolved(), 2);
+        assert_eq!(view.text(), "Title\n< left one\n> right one\nsame\n< left two\n> right two");
+    }
+
+    #[test]
+    fn choices() {
+        let mut view = merge_view();
+        view.handle_key(key('h'));
+        view.handle_key(key('j'));
+        view.handle_key(key('b'));
+        assert_eq!(view.unresolved(), 0);
+        assert_eq!(view.text(), "Title\nleft one\nsame\nleft two\nright two");
+
+        view.handle_key(key('k'));
+        view.handle_key(key('l'));
+        assert_eq!(view.text(), "Title\nright one\nsame\nleft two\nright two");
+        view.handle_key(key('u'));
+        assert_eq!(view.unresolved(), 1);
+    }
+
+    /// The hand edited text replaces the choices
+    #[test]
+    fn edited_by_hand() {
+        let mut view = merge_view();
+        view.handle_key(key('e'));
+        view.handle_key(key('d'));
+        view.handle_key(key('d'));
+        assert_eq!(view.unresolved(), 0);
+        assert_eq!(view.text(), "< left one\n> right one\nsame\n< left two\n> right two");
+        assert_eq!(view.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL)), MergeAction::Save);
+    }
+}
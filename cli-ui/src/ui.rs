@@ -1,29 +1,50 @@
 use crate::{Outcome, Event, Task};
-use apnotes_lib::db::DatabaseService;
 use apnotes_lib::notes::localnote::LocalNote;
 use tui::widgets::{Wrap, Borders, Block, Paragraph, ListState, ListItem, List};
 use tui::style::{Style, Color, Modifier};
 use tui::layout::{Constraint, Direction, Layout, Alignment};
+use tui::text::{Spans, Text};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Instant, Duration};
 use std::{thread, io};
+use std::io::Stdout;
 use tui::Terminal;
 use tui::backend::CrosstermBackend;
 use apnotes_lib::AppleNotes;
+use apnotes_lib::config::{TuiSettings, NoteOrder};
 use std::sync::mpsc::{Sender, Receiver};
 use apnotes_lib::notes::traits::identifyable_note::IdentifiableNote;
 use crossterm::{
+    execute,
     event::{self, Event as CEvent, KeyCode},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use crossterm::event::KeyEvent;
 use itertools::Itertools;
-use apnotes_lib::error::ErrorCode;
+use apnotes_lib::error::{ErrorCode, NoteError};
+use apnotes_lib::tags::{extract_tags, normalize_tag};
+use crate::folders::FolderTree;
+use crate::editor::{Editor, EditorAction};
+use crate::markdown;
+use crate::merge_view::{MergeView, MergeAction};
+
+/// Longest time the input thread blocks, so that it can be paused quickly
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct UiState {
     pub(crate) action_sender: Sender<Task>,
     pub(crate) event_receiver: Receiver<Event<KeyEvent>>,
-    pub(crate) event_sender: Arc<Mutex<Sender<Event<KeyEvent>>>>
+    pub(crate) event_sender: Arc<Mutex<Sender<Event<KeyEvent>>>>,
+    /// Set while an external editor owns the terminal
+    pub(crate) input_paused: Arc<AtomicBool>
+}
+
+/// Pane that receives j and k
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    Folders,
+    Notes,
 }
 
 pub struct Ui<'u> {
@@ -32,15 +53,24 @@ pub struct Ui<'u> {
     pub color: Color,
     pub status: String,
     pub app: Arc<Mutex<AppleNotes>>,
+    pub settings: TuiSettings,
     pub ui_state: UiState,
     pub entries: Vec<LocalNote>,
     pub keyword: Option<String>,
     pub items: Vec<ListItem<'u>>,
     pub list: List<'u>,
-    pub text: String,
+    pub text: Text<'u>,
+    /// Shows the markdown of the note styled instead of the raw text
+    pub rendered: bool,
     pub scroll_amount: u16,
     pub in_search_mode: bool,
-    pub new_note_mode: bool
+    pub new_note_mode: bool,
+    pub folder_tree: FolderTree,
+    pub focus: Focus,
+    /// Uuid of the note that is open inside the built-in editor
+    pub editor: Option<(String, Editor)>,
+    /// Uuid of the note whose conflicts get resolved
+    pub merge_view: Option<(String, MergeView)>
 }
 
 impl<'u> Ui<'u> {
@@ -49,13 +79,15 @@ impl<'u> Ui<'u> {
 
         enable_raw_mode().expect("can run in raw mode");
 
-        let stdout = io::stdout();
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
 
         terminal.clear().unwrap();
 
         let sender = Arc::clone(&self.ui_state.event_sender);
+        let paused = Arc::clone(&self.ui_state.input_paused);
 
         thread::spawn(move || {
             let tick_rate = Duration::from_millis(1000);
@@ -64,9 +96,17 @@ impl<'u> Ui<'u> {
 
                 let sender = sender.lock().unwrap();
 
+                // Leaves the keys to the external editor
+                if paused.load(Ordering::SeqCst) {
+                    drop(sender);
+                    thread::sleep(INPUT_POLL_INTERVAL);
+                    continue;
+                }
+
                 let timeout = tick_rate
                     .checked_sub(last_tick.elapsed())
-                    .unwrap_or_else(|| Duration::from_secs(0));
+                    .unwrap_or_else(|| Duration::from_secs(0))
+                    .min(INPUT_POLL_INTERVAL);
 
                 if event::poll(timeout).expect("poll works") {
                     if let CEvent::Key(key) = event::read().expect("can read events") {
@@ -91,10 +131,12 @@ impl<'u> Ui<'u> {
         self.reload_text();
         self.scroll_amount = 0;
 
-        self.status = "Syncing".to_string();
-        self.color = Color::Yellow;
+        if self.settings.sync_on_start {
+            self.status = "Syncing".to_string();
+            self.color = Color::Yellow;
 
-        self.ui_state.action_sender.send(Task::Sync).unwrap();
+            self.ui_state.action_sender.send(Task::Sync).unwrap();
+        }
 
         loop {
 
@@ -117,37 +159,110 @@ impl<'u> Ui<'u> {
 
                 let chunks = lay.split(f.size());
 
-                let noteslayout = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .margin(0)
-                    .constraints(
-                        [
-                            Constraint::Percentage(20),
-                            Constraint::Percentage(80),
-                        ].as_ref()
-                    ).split(chunks[0]);
-
-                f.render_stateful_widget(
-                    self.list.clone(),
-                    noteslayout[0],
-                    &mut self.note_list_state.clone()
-                );
-
-                let t  = Paragraph::new(self.text.clone())
-                    .block(Block::default().title("Content").borders(Borders::ALL))
-                    .style(Style::default().fg(Color::White))
-                    .alignment(Alignment::Left)
-                    .scroll((self.scroll_amount,self.scroll_amount))
-                    .wrap(Wrap { trim: false });
+                if let Some((_, merge_view)) = &self.merge_view {
+                    merge_view.render(f, chunks[0]);
+                } else {
+                    let noteslayout = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .margin(0)
+                        .constraints(
+                            [
+                                Constraint::Percentage(20),
+                                Constraint::Percentage(20),
+                                Constraint::Percentage(60),
+                            ].as_ref()
+                        ).split(chunks[0]);
+
+                    let mut folder_state = ListState::default();
+                    folder_state.select(Some(self.folder_tree.selected()));
+                    f.render_stateful_widget(
+                        self.gen_folder_list(),
+                        noteslayout[0],
+                        &mut folder_state
+                    );
 
+                    f.render_stateful_widget(
+                        self.list.clone(),
+                        noteslayout[1],
+                        &mut self.note_list_state.clone()
+                    );
 
-                f.render_widget(t, noteslayout[1]);
+                    if let Some((_, editor)) = &self.editor {
+                        editor.render(f, noteslayout[2], "Edit");
+                    } else {
+                        let title = if self.rendered { "Content" } else { "Content (raw)" };
+                        let t  = Paragraph::new(self.text.clone())
+                            .block(Block::default().title(title).borders(Borders::ALL))
+                            .style(Style::default().fg(Color::White))
+                            .alignment(Alignment::Left)
+                            .scroll((self.scroll_amount,self.scroll_amount))
+                            .wrap(Wrap { trim: false });
+
+                        f.render_widget(t, noteslayout[2]);
+                    }
+                }
                 f.render_widget(t2.clone(), chunks[1]);
             }).unwrap();
 
             let received_keystroke = self.ui_state.event_receiver.recv()?;
 
-            if self.in_search_mode {
+            if self.merge_view.is_some() {
+                match received_keystroke {
+                    Event::Input(event) => {
+                        let action = self.merge_view.as_mut().unwrap().1.handle_key(event);
+                        match action {
+                            MergeAction::Save => self.save_merge(),
+                            MergeAction::Cancel => {
+                                self.merge_view = None;
+                                self.status = "Merge cancelled".to_string();
+                                self.color = Color::White;
+                            }
+                            MergeAction::Continue => {}
+                        }
+                    }
+                    // The merge view keeps its choices, so the list can be refreshed below it
+                    Event::OutCome(Outcome::Success(s)) => {
+                        self.color = Color::Green;
+                        self.status = s;
+                        let old_uuid = self.get_old_selected_entry_uuid();
+                        self.refresh();
+                        self.select_entry(old_uuid);
+                    }
+                    Event::OutCome(Outcome::Failure(s)) => {
+                        self.color = Color::Red;
+                        self.status = s;
+                    }
+                    _ => {}
+                }
+            } else if self.editor.is_some() {
+                match received_keystroke {
+                    Event::Input(event) => {
+                        let action = self.editor.as_mut().unwrap().1.handle_key(event);
+                        match action {
+                            EditorAction::Save => self.save_editor(),
+                            EditorAction::Discard => {
+                                self.editor = None;
+                                self.status = "Edit discarded".to_string();
+                                self.color = Color::White;
+                            }
+                            EditorAction::Continue => {}
+                        }
+                    }
+                    // The editor keeps its text, so the list can be refreshed below it
+                    Event::OutCome(Outcome::Success(s)) => {
+                        self.color = Color::Green;
+                        self.status = s;
+                        let old_uuid = self.get_old_selected_entry_uuid();
+                        self.refresh();
+                        self.select_entry(old_uuid);
+                    }
+                    Event::OutCome(Outcome::Failure(s)) => {
+                        self.color = Color::Red;
+                        self.status = s;
+                    }
+                    _ => {}
+                }
+            } else if self.in_search_mode {
                 match received_keystroke {
                     Event::Input(event) => match event.code {
                         KeyCode::Esc => {
@@ -202,6 +317,24 @@ impl<'u> Ui<'u> {
             } else {
                 match received_keystroke {
                     Event::Input(event) => match event.code {
+                        KeyCode::Tab => {
+                            self.focus = match self.focus {
+                                Focus::Folders => Focus::Notes,
+                                Focus::Notes => Focus::Folders
+                            };
+                            self.list = self.gen_list();
+                        },
+                        KeyCode::Char('j') if self.focus == Focus::Folders => {
+                            self.folder_tree.next();
+                            self.show_selected_folder();
+                        },
+                        KeyCode::Char('k') if self.focus == Focus::Folders => {
+                            self.folder_tree.previous();
+                            self.show_selected_folder();
+                        },
+                        KeyCode::Enter | KeyCode::Char(' ') if self.focus == Focus::Folders => {
+                            self.folder_tree.toggle();
+                        },
                         KeyCode::Char('j') => {
                             let selected = self.note_list_state.selected();
                             if self.entries.len() > 0 && selected.unwrap_or(0) < self.entries.len() -1 {
@@ -236,18 +369,31 @@ impl<'u> Ui<'u> {
                             self.new_note_mode = true;
                         },
                         KeyCode::Char('m') => {
-                            let note = self.entries.get(self.note_list_state.selected().unwrap()).unwrap();
-                            let result =  {
+                            if let Some(note) = self.note_list_state.selected().and_then(|index| self.entries.get(index)) {
+                                match note.body.as_slice() {
+                                    [left, right] if note.mergeable() => {
+                                        let left = left.text.clone().unwrap_or_default();
+                                        let right = right.text.clone().unwrap_or_default();
+                                        self.merge_view = Some((note.metadata.uuid.clone(), MergeView::new(&left, &right)));
+                                        self.color = Color::Cyan;
+                                        self.status = "j/k conflict, h left, l right, b both, u undecided, e edit, Ctrl-s save, Esc cancel".to_string();
+                                    }
+                                    _ => {
+                                        self.color = Color::Red;
+                                        self.status = "Only notes with two versions can be merged".to_string();
+                                    }
+                                }
+                            }
+                        }
+                        KeyCode::Char('M') => {
+                            let note = self.entries.get(self.note_list_state.selected().unwrap()).unwrap().clone();
+                            let result = self.suspended(&mut terminal, || {
                                 a.lock().unwrap().merge(&note.metadata.uuid)
-                            };
+                            });
                             match result {
                                 Ok(_) => {
-                                    let old_uuid = self.entries.get(self.note_list_state.selected().unwrap()).unwrap().metadata.uuid.clone();
                                     self.refresh();
-
-                                    let old_note_idx = self.get_note_index(old_uuid);
-
-                                    self.note_list_state.select(Some(old_note_idx));
+                                    self.select_entry(Some(note.metadata.uuid.clone()));
                                     self.reload_text();
                                 }
                                 Err(e) => {
@@ -257,12 +403,25 @@ impl<'u> Ui<'u> {
                             }
                         }
                         KeyCode::Char('e') => {
-                            let note = self.entries.get(self.note_list_state.selected().unwrap()).unwrap();
-                            let result: Result<LocalNote,Box<dyn ErrorCode>> = {
+                            if let Some(note) = self.note_list_state.selected().and_then(|index| self.entries.get(index)) {
+                                if note.needs_merge() {
+                                    self.color = Color::Red;
+                                    self.status = "The note has to be merged first".to_string();
+                                } else {
+                                    let text = note.body[0].text.clone().unwrap_or_default();
+                                    self.editor = Some((note.metadata.uuid.clone(), Editor::new(&text)));
+                                    self.color = Color::Cyan;
+                                    self.status = "i insert, Esc normal mode, u undo, Ctrl-s save, Esc Esc discard".to_string();
+                                }
+                            }
+                        },
+                        KeyCode::Char('E') => {
+                            let note = self.entries.get(self.note_list_state.selected().unwrap()).unwrap().clone();
+                            let result: Result<LocalNote,Box<dyn ErrorCode>> = self.suspended(&mut terminal, || {
                                 let app = a.lock().unwrap();
                                 app.edit_note(&note, false)
                                     .and_then(|note| app.update_note(&note).map(|_n| note).map_err(|e| e.into()))
-                            };
+                            });
 
                             match result {
                                 Ok(_note) => {
@@ -284,8 +443,7 @@ impl<'u> Ui<'u> {
                             let mut note = self.entries.get(self.note_list_state.selected().unwrap()).unwrap().clone();
                             note.metadata.locally_deleted = !note.metadata.locally_deleted ;
 
-                            let db_connection = apnotes_lib::db::SqliteDBConnection::new();
-                            db_connection.update(&note).unwrap();
+                            self.app.lock().unwrap().update_note(&note).unwrap();
 
                             self.refresh();
 
@@ -303,6 +461,12 @@ impl<'u> Ui<'u> {
 
                             self.ui_state.action_sender.send(Task::Test).unwrap();
                         },
+                        KeyCode::Char('r') => {
+                            self.rendered = !self.rendered;
+                            self.status = if self.rendered { "Rendered view" } else { "Raw view" }.to_string();
+                            self.color = Color::White;
+                            self.reload_text();
+                        },
                         KeyCode::Char('q') => {
                             self.end = true;
 
@@ -314,6 +478,13 @@ impl<'u> Ui<'u> {
                             self.color = Color::Cyan;
                             self.in_search_mode = true;
                         },
+                        KeyCode::Char('#') => {
+                            self.keyword = Some("#".to_string());
+                            self.status = self.keyword.as_ref().unwrap().clone();
+                            self.color = Color::Cyan;
+                            self.in_search_mode = true;
+                            self.refresh();
+                        },
                         KeyCode::Char('c') => {
                             self.status = format!("Filter Cleared");
                             self.color = Color::White;
@@ -368,11 +539,96 @@ impl<'u> Ui<'u> {
         }
 
         terminal.clear().unwrap();
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
         disable_raw_mode().unwrap();
 
         Ok(())
     }
 
+    /// Hands the terminal over to another program, e.g. an external editor,
+    /// and restores the TUI once it finished
+    fn suspended<T>(&self, terminal: &mut Terminal<CrosstermBackend<Stdout>>, run: impl FnOnce() -> T) -> T {
+        self.ui_state.input_paused.store(true, Ordering::SeqCst);
+        // Waits until the input thread stopped polling
+        let input = self.ui_state.event_sender.lock().unwrap();
+
+        disable_raw_mode().unwrap();
+        execute!(terminal.backend_mut(), LeaveAlternateScreen).unwrap();
+        terminal.show_cursor().unwrap();
+
+        let result = run();
+
+        execute!(terminal.backend_mut(), EnterAlternateScreen).unwrap();
+        enable_raw_mode().unwrap();
+        terminal.clear().unwrap();
+
+        drop(input);
+        self.ui_state.input_paused.store(false, Ordering::SeqCst);
+        result
+    }
+
+    /// Saves the text of the built-in editor like an edit inside the external editor
+    fn save_editor(&mut self) {
+        let (uuid, editor) = self.editor.as_ref().unwrap();
+        let result = {
+            let app = self.app.lock().unwrap();
+            app.find_note(uuid)
+                .and_then(|note| app.replace_text(&note, &editor.text()))
+                .and_then(|note| app.update_note(&note))
+        };
+
+        match result {
+            Ok(_) => {
+                self.status = "Saved".to_string();
+                self.color = Color::Green;
+            }
+            Err(e) if e.error_code() == NoteError::ContentNotChanged.error_code() => {
+                self.status = "No changes".to_string();
+                self.color = Color::White;
+            }
+            Err(e) => {
+                // Keeps the editor open, so that the text does not get lost
+                self.status = format!("Could not save: {}", e);
+                self.color = Color::Red;
+                return;
+            }
+        }
+
+        let uuid = uuid.clone();
+        self.editor = None;
+        self.refresh();
+        self.select_entry(Some(uuid));
+        self.reload_text();
+    }
+
+    /// Saves the merged text of the merge view, the view stays open if conflicts
+    /// are left or saving failed
+    fn save_merge(&mut self) {
+        let (uuid, merge_view) = self.merge_view.as_ref().unwrap();
+
+        let unresolved = merge_view.unresolved();
+        if unresolved > 0 {
+            self.status = format!("{} conflicts left, pick a version or press b for both", unresolved);
+            self.color = Color::Yellow;
+            return;
+        }
+
+        let result = self.app.lock().unwrap().resolve_merge(uuid, &merge_view.text());
+        if let Err(e) = result {
+            self.status = format!("Could not merge: {}", e);
+            self.color = Color::Red;
+            return;
+        }
+
+        let uuid = uuid.clone();
+        self.merge_view = None;
+        self.status = "Merged".to_string();
+        self.color = Color::Green;
+        self.refresh();
+        self.select_entry(Some(uuid));
+        self.reload_text();
+    }
+
     fn delete_character(&mut self) -> String {
         if self.keyword.is_some() {
             let len = self.keyword.as_ref().unwrap().len();
@@ -401,52 +657,99 @@ impl<'u> Ui<'u> {
         };
 
         List::new(self.items.clone())
-            .block(Block::default().title(title).borders(Borders::ALL))
+            .block(Block::default().title(title).borders(Borders::ALL).border_style(self.border_style(Focus::Notes)))
             .style(Style::default().fg(Color::White))
             .highlight_style(Style::default().add_modifier(Modifier::ITALIC))
             .highlight_symbol(">>")
     }
 
+    fn gen_folder_list(&self) -> List<'u> {
+        List::new(self.folder_tree.items())
+            .block(Block::default().title("Folders").borders(Borders::ALL).border_style(self.border_style(Focus::Folders)))
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().add_modifier(Modifier::ITALIC))
+            .highlight_symbol(">>")
+    }
+
+    fn border_style(&self, pane: Focus) -> Style {
+        if self.focus == pane {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        }
+    }
+
     fn generate_list_items(&mut self) -> Vec<ListItem<'u>> {
+        // The folder is only interesting if the notes of all folders are listed
+        let show_folder = self.folder_tree.selected_folder().is_none();
         self.entries.iter()
-            .filter(|entry| {
-                if self.keyword.is_some() {
-                    entry.body[0].text.as_ref().unwrap().to_lowercase().contains(&self.keyword.as_ref().unwrap().to_lowercase())
-                } else {
-                    return true
-                }
-            })
+            .filter(|entry| matches_filter(entry, &self.keyword))
             .map(|e| {
+                let label = if show_folder {
+                    format!("{} {}", e.metadata.folder(), e.first_subject())
+                } else {
+                    e.first_subject()
+                };
                 if e.needs_merge() {
-                    ListItem::new(format!("[M] {} {}", e.metadata.folder(), e.first_subject()).to_string()).style(Style::default().fg(Color::LightBlue))
+                    ListItem::new(format!("[M] {}", label)).style(Style::default().fg(Color::LightBlue))
                 } else if e.content_changed_locally() {
-                    ListItem::new(format!("{} {}", e.metadata.folder(), e.first_subject()).to_string()).style(Style::default().fg(Color::LightYellow))
+                    ListItem::new(label).style(Style::default().fg(Color::LightYellow))
                 } else if e.metadata.locally_deleted {
-                    ListItem::new(format!("{} {}", e.metadata.folder(), e.first_subject()).to_string()).style(Style::default().fg(Color::LightRed))
+                    ListItem::new(label).style(Style::default().fg(Color::LightRed))
                 } else if e.metadata.new {
-                    ListItem::new(format!("{} {}", e.metadata.folder(), e.first_subject()).to_string()).style(Style::default().fg(Color::LightGreen))
+                    ListItem::new(label).style(Style::default().fg(Color::LightGreen))
                 } else {
-                    ListItem::new(format!("{} {}", e.metadata.folder(), e.first_subject()).to_string())
+                    ListItem::new(label)
                 }
             }).collect()
     }
 
+    /// The folder tree always contains every folder, the note list only the
+    /// notes of the selected folder that match the filter
     fn refresh(&mut self) {
-        self.entries = self.refetch_notes(&self.app.lock().unwrap(), &self.keyword);
+        let notes = self.refetch_notes(&self.app.lock().unwrap());
+        self.folder_tree.update(&notes);
+        self.entries = notes.into_iter()
+            .filter(|entry| self.folder_tree.contains(entry) && matches_filter(entry, &self.keyword))
+            .collect();
         self.items = self.generate_list_items( );
         self.list = self.gen_list();
     }
 
+    /// Lists the notes of the folder that got selected inside the tree
+    fn show_selected_folder(&mut self) {
+        self.refresh();
+        self.select_entry(None);
+        self.reload_text();
+        self.scroll_amount = 0;
+    }
+
     fn reload_text(&mut self) {
         // self.note_list_state.select(Some(0));
 
         match self.note_list_state.selected() {
             Some(index) if matches!(self.entries.get(index), Some(_)) => {
                 let entry = self.entries.get(index).unwrap();
-                self.text = entry.body[0].text.as_ref().unwrap().clone();
+                let mut text = Text::default();
+                for (index, body) in entry.body.iter().enumerate() {
+                    // Unmerged notes show every version below each other
+                    if entry.needs_merge() {
+                        if index > 0 {
+                            text.extend(vec![Spans::default()]);
+                        }
+                        text.extend(vec![markdown::separator(&format!("Version {} of {}", index + 1, entry.body.len()))]);
+                    }
+                    let body_text = body.text.as_deref().unwrap_or("");
+                    if self.rendered {
+                        text.extend(markdown::render(body_text));
+                    } else {
+                        text.extend(Text::raw(body_text.to_string()));
+                    }
+                }
+                self.text = text;
             }
             _ => {
-                self.text = "".to_string();
+                self.text = Text::default();
             }
         }
     }
@@ -491,18 +794,28 @@ impl<'u> Ui<'u> {
         old_uuid
     }
 
-    fn refetch_notes(&self, app: &AppleNotes, filter_word: &Option<String>) -> Vec<LocalNote> {
+    fn refetch_notes(&self, app: &AppleNotes) -> Vec<LocalNote> {
         app.get_notes().unwrap()
             .into_iter()
-            .filter(|entry| {
-                if filter_word.is_some() {
-                    entry.body[0].text.as_ref().unwrap().to_lowercase().contains(&filter_word.as_ref().unwrap().to_lowercase())
-                } else {
-                    return true
-                }
+            .sorted_by(|a, b| match self.settings.sort {
+                NoteOrder::Modified => b.metadata.timestamp().cmp(&a.metadata.timestamp()),
+                NoteOrder::Created => b.metadata.created_or_modified().cmp(&a.metadata.created_or_modified()),
+                NoteOrder::Folder => (&a.metadata.subfolder, a.first_subject()).cmp(&(&b.metadata.subfolder, b.first_subject()))
             })
-            .sorted_by_key(|note| note.metadata.timestamp())
-            .rev()
             .collect()
     }
-}
\ No newline at end of file
+}
+
+/// Keywords starting with # match notes that contain a hashtag starting with the
+/// keyword, every other keyword matches the note text
+fn matches_filter(entry: &LocalNote, keyword: &Option<String>) -> bool {
+    let text = entry.body[0].text.as_deref().unwrap_or("");
+    match keyword {
+        Some(keyword) if keyword.starts_with('#') => {
+            let tag = normalize_tag(keyword);
+            extract_tags(text).iter().any(|entry_tag| entry_tag.starts_with(&tag))
+        }
+        Some(keyword) => text.to_lowercase().contains(&keyword.to_lowercase()),
+        None => true
+    }
+}
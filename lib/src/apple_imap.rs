@@ -62,21 +62,22 @@ impl ImapSession<Session<TlsStream<TcpStream>>> for TlsImapSession {
 }
 
 #[cfg_attr(test, automock)]
-pub trait MailService<T> {
+pub trait MailService {
     /// Iterates through all Note-Imap folders and fetches the mail header content plus
     /// the folder name.
     ///
     /// The generated dataset can be used to check for duplicated notes that needs
     /// to be merged
     fn fetch_headers(&mut self) -> Result<RemoteNoteHeaderCollection>;
+    /// Iterates through all backup folders (Backup_Notes*) and fetches the mail header
+    /// content plus the folder name.
+    fn fetch_backup_headers(&mut self) -> Result<RemoteNoteHeaderCollection>;
     /// Creates a new Subfolder for storing notes
     fn create_mailbox(&mut self, note: &NotesMetadata) -> Result<()>;
     /// Fetches mail headers for passed uuid
     fn fetch_header(&mut self, subfolder: &str, uid: i64) -> Result<RemoteNoteMetaData>;
     /// Fetches the actual content from a note
     fn fetch_note_content(&mut self, subfolder: &str, uid: i64) -> Result<String>;
-    /// Exposes the active imap connection
-    fn get_session(&self) -> T;
     /// Updates a local message, either if it got updated or if it is a new localnote
     /// This App should only support "merged" notes, notes that only have one body.
     ///
@@ -89,6 +90,14 @@ pub trait MailService<T> {
     fn logout(&mut self) -> Result<()>;
 }
 
+/// Connects to the mail server, AppleNotes calls it once per sync or backup listing
+pub type MailServiceFactory = Box<dyn for<'a> Fn(&'a Profile) -> Result<Box<dyn MailService + 'a>> + Send>;
+
+/// Factory of the imap connection that is used unless another one gets injected
+pub fn connect_imap(profile: &Profile) -> Result<Box<dyn MailService + '_>> {
+    MailServiceImpl::new_with_login(profile).map(|service| Box::new(service) as Box<dyn MailService>)
+}
+
 pub struct MailServiceImpl<'a> {
     session: TlsImapSession,
     profile: &'a Profile
@@ -158,7 +167,21 @@ impl <'a>MailServiceImpl<'a> {
     }
 
     pub fn list_note_folders(&mut self) -> Result<Vec<String>> {
-        let folders_result = self.session.session.list(None, Some("Notes*"));
+        self.list_folders("Notes*")
+    }
+
+    /// Fetches the headers of all messages inside the folders that match the passed pattern
+    fn fetch_headers_in_folders(&mut self, pattern: &str) -> Result<Vec<RemoteNoteMetaData>> {
+        let folders = self.list_folders(pattern)?;
+        let header = folders.iter().flat_map(|folder_name| {
+            self.fetch_headers_in_folder(folder_name.to_string())
+        })
+            .collect();
+        Ok(header)
+    }
+
+    pub fn list_folders(&mut self, pattern: &str) -> Result<Vec<String>> {
+        let folders_result = self.session.session.list(None, Some(pattern));
         match folders_result {
             Ok(result) => {
                 let names: Vec<String> = result.iter().map(|name| name.name().to_string()).collect();
@@ -203,17 +226,16 @@ impl <'a>MailServiceImpl<'a> {
     }
 }
 
-impl <'a>MailService<Session<TlsStream<TcpStream>>> for MailServiceImpl<'a> {
+impl <'a>MailService for MailServiceImpl<'a> {
 
     fn fetch_headers(&mut self) -> Result<Vec<RemoteNoteMetaData>> {
         info!("Fetching Headers of Remote Notes...");
-        let folders = self.list_note_folders()?;
-        let header = folders.iter().map(|folder_name| {
-            self.fetch_headers_in_folder(folder_name.to_string())
-        })
-            .flatten()
-            .collect();
-        Ok(header)
+        self.fetch_headers_in_folders("Notes*")
+    }
+
+    fn fetch_backup_headers(&mut self) -> Result<Vec<RemoteNoteMetaData>> {
+        info!("Fetching Headers of Backup Notes...");
+        self.fetch_headers_in_folders("Backup_Notes*")
     }
 
     fn create_mailbox(&mut self, note: &NotesMetadata) -> Result<()> {
@@ -259,10 +281,6 @@ impl <'a>MailService<Session<TlsStream<TcpStream>>> for MailServiceImpl<'a> {
         }
     }
 
-    fn get_session(&self) -> Session<TlsStream<TcpStream>> {
-        unimplemented!()
-    }
-
     fn update_message(&mut self, localnote: &LocalNote) -> Result<u32> {
         //Todo check >1
 
@@ -1,10 +1,11 @@
 use model::{Body, NotesMetadata};
 use util::generate_uuid;
-use chrono::Utc;
+use chrono::{Utc, NaiveDateTime};
 use notes::note_headers::NoteHeaders;
 use notes::traits::header_parser::HeaderParser;
-#[cfg(not(test))]
 use profile::Profile;
+use config::{TuiSettings, ServerSettings, HookSettings, GitSettings};
+use std::path::PathBuf;
 
 pub struct BodyMetadataBuilder {
     body: Body
@@ -73,6 +74,108 @@ impl BodyMetadataBuilder {
     }
 }
 
+pub struct ProfileBuilder {
+    profile: Profile
+}
+
+/// Builder for profiles that are not read from a config file, e.g. if
+/// the library gets embedded into another application
+///
+/// Without another password source the password is expected as plain text,
+/// the editor defaults to vi
+impl ProfileBuilder {
+    pub fn new(username: &str, email: &str, imap_server: &str) -> ProfileBuilder {
+        ProfileBuilder {
+            profile: Profile {
+                username: username.to_string(),
+                password_type: "PLAIN".to_string(),
+                imap_server: imap_server.to_string(),
+                email: email.to_string(),
+                editor: "vi".to_string(),
+                editor_arguments: vec![],
+                secret_service_attribute: None,
+                secret_service_value: None,
+                password_command: None,
+                domain: email.split_once('@').map(|(_, domain)| domain.to_string()).unwrap_or_default(),
+                trash_retention_days: ::trash::DEFAULT_RETENTION_DAYS,
+                db_path: None,
+                tui: TuiSettings::default(),
+                server: ServerSettings::default(),
+                hooks: HookSettings::default(),
+                git: GitSettings::default(),
+                password: None
+            }
+        }
+    }
+
+    pub fn with_password(mut self, password: &str) -> Self {
+        self.profile.password_type = "PLAIN".to_string();
+        self.profile.password = Some(password.to_string());
+        self
+    }
+
+    /// The first line the shell command prints is used as password
+    pub fn with_password_command(mut self, command: &str) -> Self {
+        self.profile.password_type = "COMMAND".to_string();
+        self.profile.password_command = Some(command.to_string());
+        self
+    }
+
+    /// Queries the password from the secret service with the attribute and value
+    pub fn with_secret_service(mut self, attribute: &str, value: &str) -> Self {
+        self.profile.password_type = "SECRET_SERVICE".to_string();
+        self.profile.secret_service_attribute = Some(attribute.to_string());
+        self.profile.secret_service_value = Some(value.to_string());
+        self
+    }
+
+    pub fn with_editor(mut self, command: &str, arguments: &[&str]) -> Self {
+        self.profile.editor = command.to_string();
+        self.profile.editor_arguments = arguments.iter().map(|argument| argument.to_string()).collect();
+        self
+    }
+
+    pub fn with_db_path(mut self, path: PathBuf) -> Self {
+        self.profile.db_path = Some(path);
+        self
+    }
+
+    pub fn with_trash_retention_days(mut self, days: i64) -> Self {
+        self.profile.trash_retention_days = days;
+        self
+    }
+
+    pub fn with_tui_settings(mut self, settings: TuiSettings) -> Self {
+        self.profile.tui = settings;
+        self
+    }
+
+    pub fn with_server_settings(mut self, settings: ServerSettings) -> Self {
+        self.profile.server = settings;
+        self
+    }
+
+    pub fn with_hook_settings(mut self, settings: HookSettings) -> Self {
+        self.profile.hooks = settings;
+        self
+    }
+
+    pub fn with_git_settings(mut self, settings: GitSettings) -> Self {
+        self.profile.git = settings;
+        self
+    }
+
+    pub fn build(self) -> Profile {
+        self.profile
+    }
+
+    /// Profile that the unit tests share, with a plain text password and the test@test.de account
+    #[cfg(test)]
+    pub(crate) fn for_tests() -> ProfileBuilder {
+        ProfileBuilder::new("test", "test@test.de", "test").with_password("test")
+    }
+}
+
 pub struct NotesMetadataBuilder {
     notes_metadata: NotesMetadata
 }
@@ -84,15 +187,16 @@ pub struct NotesMetadataBuilder {
 /// generated
 impl NotesMetadataBuilder {
     pub fn new() -> NotesMetadataBuilder {
-        let date = Utc::now().to_rfc2822();
+        let date = Utc::now().naive_utc();
         NotesMetadataBuilder {
             notes_metadata:  NotesMetadata {
                 subfolder: "".to_string(),
                 locally_deleted: false,
                 new: false,
-                date,
+                date: Some(date),
                 uuid: generate_uuid(),
-                mime_version: "1.0 (Mac OS X Notes 4.6 \\(879.10\\))".to_string()
+                mime_version: "1.0 (Mac OS X Notes 4.6 \\(879.10\\))".to_string(),
+                created_date: Some(date)
             }
         }
 
@@ -104,6 +208,13 @@ impl NotesMetadataBuilder {
         self
     }
 
+    /// Sets the modification and the creation date
+    pub fn with_date(mut self, date: NaiveDateTime) -> Self {
+        self.notes_metadata.date = Some(date);
+        self.notes_metadata.created_date = Some(date);
+        self
+    }
+
     pub fn is_new(mut self, new: bool) -> Self {
         self.notes_metadata.new = new;
         self
@@ -0,0 +1,761 @@
+extern crate toml;
+
+use self::toml::Value;
+use self::toml::value::Table;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use error::Result;
+use profile::Profile;
+use trash::DEFAULT_RETENTION_DAYS;
+
+#[cfg(target_family = "unix")]
+use xdg::BaseDirectories;
+
+/// Name of the structured config file
+pub const CONFIG_FILE: &str = "config.toml";
+/// Name of the key=value config file older versions used
+pub const LEGACY_CONFIG_FILE: &str = "config";
+
+/// Placeholder that is shown instead of the plain text password
+const MASKED_PASSWORD: &str = "********";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    /// TOML file with account, editor, sync, tui, server, hooks and git sections
+    Toml,
+    /// One key=value pair per line
+    Legacy,
+}
+
+impl ConfigFormat {
+    /// Files ending with .toml are read as TOML, everything else as legacy config
+    pub fn of(path: &Path) -> ConfigFormat {
+        match path.extension() {
+            Some(extension) if extension == "toml" => ConfigFormat::Toml,
+            _ => ConfigFormat::Legacy
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Legacy => "legacy",
+        }
+    }
+}
+
+/// The config file apnotes reads its settings from
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub format: ConfigFormat,
+    pub exists: bool,
+}
+
+/// A key of the config file that is missing or has an invalid value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub key: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// Order in which the TUI lists notes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteOrder {
+    /// Recently modified notes first
+    Modified,
+    /// Recently created notes first
+    Created,
+    /// Alphabetically by folder and subject
+    Folder,
+}
+
+impl NoteOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoteOrder::Modified => "modified",
+            NoteOrder::Created => "created",
+            NoteOrder::Folder => "folder",
+        }
+    }
+
+    fn parse(name: &str) -> Option<NoteOrder> {
+        match name {
+            "modified" => Some(NoteOrder::Modified),
+            "created" => Some(NoteOrder::Created),
+            "folder" => Some(NoteOrder::Folder),
+            _ => None
+        }
+    }
+}
+
+/// Settings of the [tui] section
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuiSettings {
+    pub sort: NoteOrder,
+    /// Syncs the notes as soon as the TUI got started
+    pub sync_on_start: bool,
+}
+
+impl Default for TuiSettings {
+    fn default() -> Self {
+        TuiSettings {
+            sort: NoteOrder::Modified,
+            sync_on_start: true
+        }
+    }
+}
+
+/// Settings of the [server] section
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ServerSettings {
+    /// Bearer token clients of apnotes serve have to send, the server
+    /// refuses to start without one
+    pub token: Option<String>,
+}
+
+/// Settings of the [hooks] section, every hook is a shell command
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HookSettings {
+    /// Runs before the sync connects to the mail server, the sync gets aborted if it fails
+    pub pre_sync: Option<String>,
+    pub post_sync: Option<String>,
+    pub note_created: Option<String>,
+    pub note_updated: Option<String>,
+    pub note_deleted: Option<String>,
+    /// Runs if a sync found remote changes of a note that got edited locally
+    pub merge_needed: Option<String>,
+}
+
+/// Settings of the [git] section
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GitSettings {
+    /// Repository the notes get written to and committed after every sync and
+    /// local edit, the mirror is disabled if not set
+    pub repository: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    TextList,
+    Integer,
+    Boolean,
+}
+
+/// A supported config key, legacy is the name inside the key=value format
+struct Key {
+    section: &'static str,
+    name: &'static str,
+    legacy: Option<&'static str>,
+    kind: Kind,
+}
+
+const SECTIONS: [&str; 7] = ["account", "editor", "sync", "tui", "server", "hooks", "git"];
+
+const KEYS: [Key; 22] = [
+    Key { section: "account", name: "username", legacy: Some("username"), kind: Kind::Text },
+    Key { section: "account", name: "email", legacy: Some("email"), kind: Kind::Text },
+    Key { section: "account", name: "imap_server", legacy: Some("imap_server"), kind: Kind::Text },
+    Key { section: "account", name: "password_type", legacy: Some("password_type"), kind: Kind::Text },
+    Key { section: "account", name: "password", legacy: Some("password"), kind: Kind::Text },
+    Key { section: "account", name: "secret_service_attribute", legacy: Some("secret_service_attribute"), kind: Kind::Text },
+    Key { section: "account", name: "secret_service_value", legacy: Some("secret_service_value"), kind: Kind::Text },
+    Key { section: "account", name: "password_command", legacy: Some("password_command"), kind: Kind::Text },
+    Key { section: "editor", name: "command", legacy: Some("editor"), kind: Kind::Text },
+    Key { section: "editor", name: "arguments", legacy: Some("editor_arguments"), kind: Kind::TextList },
+    Key { section: "sync", name: "trash_retention_days", legacy: Some("trash_retention_days"), kind: Kind::Integer },
+    Key { section: "sync", name: "db_path", legacy: Some("db_path"), kind: Kind::Text },
+    Key { section: "tui", name: "sort", legacy: None, kind: Kind::Text },
+    Key { section: "tui", name: "sync_on_start", legacy: None, kind: Kind::Boolean },
+    Key { section: "server", name: "token", legacy: None, kind: Kind::Text },
+    Key { section: "hooks", name: "pre_sync", legacy: None, kind: Kind::Text },
+    Key { section: "hooks", name: "post_sync", legacy: None, kind: Kind::Text },
+    Key { section: "hooks", name: "note_created", legacy: None, kind: Kind::Text },
+    Key { section: "hooks", name: "note_updated", legacy: None, kind: Kind::Text },
+    Key { section: "hooks", name: "note_deleted", legacy: None, kind: Kind::Text },
+    Key { section: "hooks", name: "merge_needed", legacy: None, kind: Kind::Text },
+    Key { section: "git", name: "repository", legacy: None, kind: Kind::Text },
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Setting {
+    Text(String),
+    TextList(Vec<String>),
+    Integer(i64),
+    Boolean(bool),
+}
+
+/// Values of a config file that have the expected type, keyed by section and name
+struct Settings {
+    format: ConfigFormat,
+    values: HashMap<(&'static str, &'static str), Setting>,
+    issues: Vec<ConfigIssue>,
+}
+
+impl Settings {
+    fn new(format: ConfigFormat) -> Settings {
+        Settings { format, values: HashMap::new(), issues: vec![] }
+    }
+
+    /// Name of the key like it is written inside the config file
+    fn key_name(&self, section: &str, name: &str) -> String {
+        let legacy = KEYS.iter()
+            .find(|key| key.section == section && key.name == name)
+            .and_then(|key| key.legacy);
+
+        match (self.format, legacy) {
+            (ConfigFormat::Legacy, Some(legacy)) => legacy.to_string(),
+            _ => format!("{}.{}", section, name)
+        }
+    }
+
+    fn issue(&mut self, key: String, message: &str) {
+        self.issues.push(ConfigIssue { key, message: message.to_string() });
+    }
+
+    fn text(&self, section: &str, name: &str) -> Option<String> {
+        match self.values.get(&(section, name)) {
+            Some(Setting::Text(text)) => Some(text.clone()),
+            _ => None
+        }
+    }
+
+    fn required_text(&mut self, section: &str, name: &str) -> String {
+        match self.text(section, name).filter(|text| !text.trim().is_empty()) {
+            Some(text) => text,
+            None => {
+                let key = self.key_name(section, name);
+                self.issue(key, "missing");
+                String::new()
+            }
+        }
+    }
+}
+
+/// Finds the config file, config.toml is preferred over the legacy config file.
+/// If none exists the location of a new config.toml is returned
+#[cfg(target_family = "unix")]
+pub fn locate() -> Result<ConfigFile> {
+    let xdg_dir = BaseDirectories::with_prefix("apnotes")?;
+    let found = xdg_dir.find_config_file(CONFIG_FILE)
+        .or_else(|| xdg_dir.find_config_file(LEGACY_CONFIG_FILE));
+
+    Ok(match found {
+        Some(path) => ConfigFile { format: ConfigFormat::of(&path), path, exists: true },
+        None => ConfigFile {
+            path: xdg_dir.get_config_home().join(CONFIG_FILE),
+            format: ConfigFormat::Toml,
+            exists: false
+        }
+    })
+}
+
+#[cfg(target_family = "windows")]
+pub fn locate() -> Result<ConfigFile> {
+    let dir = PathBuf::from(format!("{}\\{}", env!("APPDATA"), "apnotes"));
+    let found = [CONFIG_FILE, LEGACY_CONFIG_FILE].iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists());
+
+    Ok(match found {
+        Some(path) => ConfigFile { format: ConfigFormat::of(&path), path, exists: true },
+        None => ConfigFile { path: dir.join(CONFIG_FILE), format: ConfigFormat::Toml, exists: false }
+    })
+}
+
+/// Reads the passed config file and returns every key that is wrong
+pub fn check(file: &ConfigFile) -> Result<Vec<ConfigIssue>> {
+    let content = std::fs::read_to_string(&file.path)?;
+    Ok(parse(&content, file.format).err().unwrap_or_default())
+}
+
+/// Builds a profile from the content of a config file, all wrong keys
+/// get collected instead of stopping at the first one
+pub(crate) fn parse(content: &str, format: ConfigFormat) -> std::result::Result<Profile, Vec<ConfigIssue>> {
+    let settings = match format {
+        ConfigFormat::Toml => read_toml(content).map_err(|issue| vec![issue])?,
+        ConfigFormat::Legacy => read_legacy(content),
+    };
+
+    to_profile(settings)
+}
+
+/// Fails if the content is no valid TOML at all
+fn read_toml(content: &str) -> std::result::Result<Settings, ConfigIssue> {
+    let mut settings = Settings::new(ConfigFormat::Toml);
+
+    let root = match content.parse::<Value>() {
+        Ok(Value::Table(root)) => root,
+        Ok(_) => Table::new(),
+        Err(e) => return Err(ConfigIssue { key: CONFIG_FILE.to_string(), message: format!("invalid TOML: {}", e) })
+    };
+
+    for (section_name, section) in &root {
+        let section_name = match SECTIONS.iter().find(|known| *known == section_name) {
+            Some(known) => *known,
+            None => {
+                settings.issue(section_name.clone(), "unknown section");
+                continue;
+            }
+        };
+
+        let section = match section.as_table() {
+            Some(section) => section,
+            None => {
+                settings.issue(section_name.to_string(), &format!("has to be a section, got {}", section.type_str()));
+                continue;
+            }
+        };
+
+        for (name, value) in section {
+            let key_name = format!("{}.{}", section_name, name);
+            let key = match KEYS.iter().find(|key| key.section == section_name && key.name == name) {
+                Some(key) => key,
+                None => {
+                    settings.issue(key_name, "unknown key");
+                    continue;
+                }
+            };
+
+            match toml_setting(key.kind, value) {
+                Some(setting) => { settings.values.insert((key.section, key.name), setting); }
+                None => settings.issue(key_name, &format!("expected {}, got {}", kind_name(key.kind), value.type_str()))
+            }
+        }
+    }
+
+    Ok(settings)
+}
+
+fn toml_setting(kind: Kind, value: &Value) -> Option<Setting> {
+    match (kind, value) {
+        (Kind::Text, Value::String(text)) => Some(Setting::Text(text.clone())),
+        (Kind::TextList, Value::Array(values)) => values.iter()
+            .map(|value| value.as_str().map(|text| text.to_string()))
+            .collect::<Option<Vec<String>>>()
+            .map(Setting::TextList),
+        (Kind::Integer, Value::Integer(number)) => Some(Setting::Integer(*number)),
+        (Kind::Boolean, Value::Boolean(flag)) => Some(Setting::Boolean(*flag)),
+        _ => None
+    }
+}
+
+fn kind_name(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Text => "a string",
+        Kind::TextList => "an array of strings",
+        Kind::Integer => "an integer",
+        Kind::Boolean => "true or false",
+    }
+}
+
+/// Reads the key=value format, empty lines and lines starting with # are skipped,
+/// unknown keys only cause a warning
+fn read_legacy(content: &str) -> Settings {
+    let mut settings = Settings::new(ConfigFormat::Legacy);
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, value) = match line.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => {
+                settings.issue(format!("line {}", number + 1), "expected key=value");
+                continue;
+            }
+        };
+
+        // Older versions ignored keys they did not know, such configs have to keep working
+        let key = match KEYS.iter().find(|key| key.legacy == Some(name)) {
+            Some(key) => key,
+            None => {
+                warn!("Ignoring unknown key {} in line {} of the config", name, number + 1);
+                continue;
+            }
+        };
+
+        let setting = match key.kind {
+            Kind::Text => Some(Setting::Text(value.to_string())),
+            Kind::TextList => Some(Setting::TextList(value.split_whitespace().map(|s| s.to_string()).collect())),
+            Kind::Integer => value.parse::<i64>().ok().map(Setting::Integer),
+            Kind::Boolean => value.parse::<bool>().ok().map(Setting::Boolean),
+        };
+
+        match setting {
+            Some(setting) => { settings.values.insert((key.section, key.name), setting); }
+            None => settings.issue(name.to_string(), &format!("expected {}, got \"{}\"", kind_name(key.kind), value))
+        }
+    }
+
+    settings
+}
+
+fn to_profile(mut settings: Settings) -> std::result::Result<Profile, Vec<ConfigIssue>> {
+    let username = settings.required_text("account", "username");
+    let imap_server = settings.required_text("account", "imap_server");
+    let email = settings.required_text("account", "email");
+    let editor = settings.required_text("editor", "command");
+
+    let domain = match email.split_once('@') {
+        Some((_, domain)) if !domain.is_empty() => domain.to_string(),
+        _ => {
+            if !email.is_empty() {
+                let key = settings.key_name("account", "email");
+                settings.issue(key, &format!("\"{}\" is not an email address", email));
+            }
+            String::new()
+        }
+    };
+
+    let editor_arguments = match settings.values.get(&("editor", "arguments")) {
+        Some(Setting::TextList(arguments)) => arguments.clone(),
+        _ => vec![]
+    };
+
+    let password = settings.text("account", "password");
+    let password_type = settings.text("account", "password_type")
+        .unwrap_or_else(|| "PLAIN".to_string())
+        .to_uppercase();
+
+    let mut password_command = None;
+    let (secret_service_attribute, secret_service_value) = match password_type.as_str() {
+        "PLAIN" => {
+            if password.is_none() {
+                let key = settings.key_name("account", "password");
+                settings.issue(key, "missing, required for password_type plain");
+            }
+            (None, None)
+        }
+        "SECRET_SERVICE" => (
+            Some(settings.required_text("account", "secret_service_attribute")),
+            Some(settings.required_text("account", "secret_service_value"))
+        ),
+        "COMMAND" => {
+            password_command = Some(settings.required_text("account", "password_command"));
+            (None, None)
+        }
+        _ => {
+            let key = settings.key_name("account", "password_type");
+            settings.issue(key, &format!("has to be plain, secret_service or command, got \"{}\"", password_type.to_lowercase()));
+            (None, None)
+        }
+    };
+
+    let trash_retention_days = match settings.values.get(&("sync", "trash_retention_days")) {
+        Some(Setting::Integer(days)) if *days < 0 => {
+            let key = settings.key_name("sync", "trash_retention_days");
+            settings.issue(key, "must not be negative");
+            DEFAULT_RETENTION_DAYS
+        }
+        Some(Setting::Integer(days)) => *days,
+        _ => DEFAULT_RETENTION_DAYS
+    };
+
+    let db_path = settings.text("sync", "db_path")
+        .filter(|path| !path.trim().is_empty())
+        .map(PathBuf::from);
+
+    let mut tui = TuiSettings::default();
+    if let Some(sort) = settings.text("tui", "sort") {
+        match NoteOrder::parse(&sort) {
+            Some(order) => tui.sort = order,
+            None => {
+                let key = settings.key_name("tui", "sort");
+                settings.issue(key, &format!("has to be modified, created or folder, got \"{}\"", sort));
+            }
+        }
+    }
+    if let Some(Setting::Boolean(sync_on_start)) = settings.values.get(&("tui", "sync_on_start")) {
+        tui.sync_on_start = *sync_on_start;
+    }
+
+    let server = ServerSettings { token: settings.text("server", "token") };
+    if server.token.as_ref().map_or(false, |token| token.trim().is_empty()) {
+        let key = settings.key_name("server", "token");
+        settings.issue(key, "must not be empty");
+    }
+
+    let mut hook = |name: &str| {
+        let command = settings.text("hooks", name);
+        if command.as_ref().map_or(false, |command| command.trim().is_empty()) {
+            let key = settings.key_name("hooks", name);
+            settings.issue(key, "must not be empty");
+        }
+        command
+    };
+    let hooks = HookSettings {
+        pre_sync: hook("pre_sync"),
+        post_sync: hook("post_sync"),
+        note_created: hook("note_created"),
+        note_updated: hook("note_updated"),
+        note_deleted: hook("note_deleted"),
+        merge_needed: hook("merge_needed"),
+    };
+
+    let git = GitSettings {
+        repository: settings.text("git", "repository")
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from)
+    };
+
+    if !settings.issues.is_empty() {
+        return Err(settings.issues);
+    }
+
+    Ok(
+        Profile {
+            username,
+            password,
+            password_type,
+            imap_server,
+            email,
+            editor,
+            editor_arguments,
+            secret_service_attribute,
+            secret_service_value,
+            password_command,
+            domain,
+            trash_retention_days,
+            db_path,
+            tui,
+            server,
+            hooks,
+            git
+        }
+    )
+}
+
+/// Renders the effective settings of the profile as TOML config, the password is masked
+pub fn render(profile: &Profile) -> String {
+    let mut account = Table::new();
+    account.insert("username".to_string(), Value::String(profile.username.clone()));
+    account.insert("email".to_string(), Value::String(profile.email.clone()));
+    account.insert("imap_server".to_string(), Value::String(profile.imap_server.clone()));
+    account.insert("password_type".to_string(), Value::String(profile.password_type.to_lowercase()));
+    if profile.password.is_some() {
+        account.insert("password".to_string(), Value::String(MASKED_PASSWORD.to_string()));
+    }
+    if let Some(attribute) = &profile.secret_service_attribute {
+        account.insert("secret_service_attribute".to_string(), Value::String(attribute.clone()));
+    }
+    if let Some(value) = &profile.secret_service_value {
+        account.insert("secret_service_value".to_string(), Value::String(value.clone()));
+    }
+    if let Some(command) = &profile.password_command {
+        account.insert("password_command".to_string(), Value::String(command.clone()));
+    }
+
+    let mut editor = Table::new();
+    editor.insert("command".to_string(), Value::String(profile.editor.clone()));
+    editor.insert("arguments".to_string(), Value::Array(
+        profile.editor_arguments.iter().map(|argument| Value::String(argument.clone())).collect()
+    ));
+
+    let mut sync = Table::new();
+    sync.insert("trash_retention_days".to_string(), Value::Integer(profile.trash_retention_days));
+    if let Some(db_path) = &profile.db_path {
+        sync.insert("db_path".to_string(), Value::String(db_path.to_string_lossy().to_string()));
+    }
+
+    let mut tui = Table::new();
+    tui.insert("sort".to_string(), Value::String(profile.tui.sort.as_str().to_string()));
+    tui.insert("sync_on_start".to_string(), Value::Boolean(profile.tui.sync_on_start));
+
+    let mut server = Table::new();
+    if profile.server.token.is_some() {
+        server.insert("token".to_string(), Value::String(MASKED_PASSWORD.to_string()));
+    }
+
+    let mut hooks = Table::new();
+    let commands = [
+        ("pre_sync", &profile.hooks.pre_sync),
+        ("post_sync", &profile.hooks.post_sync),
+        ("note_created", &profile.hooks.note_created),
+        ("note_updated", &profile.hooks.note_updated),
+        ("note_deleted", &profile.hooks.note_deleted),
+        ("merge_needed", &profile.hooks.merge_needed),
+    ];
+    for (name, command) in commands.iter() {
+        if let Some(command) = command {
+            hooks.insert(name.to_string(), Value::String(command.clone()));
+        }
+    }
+
+    let mut git = Table::new();
+    if let Some(repository) = &profile.git.repository {
+        git.insert("repository".to_string(), Value::String(repository.to_string_lossy().to_string()));
+    }
+
+    let mut root = Table::new();
+    root.insert("account".to_string(), Value::Table(account));
+    root.insert("editor".to_string(), Value::Table(editor));
+    root.insert("sync".to_string(), Value::Table(sync));
+    root.insert("tui".to_string(), Value::Table(tui));
+    root.insert("server".to_string(), Value::Table(server));
+    root.insert("hooks".to_string(), Value::Table(hooks));
+    root.insert("git".to_string(), Value::Table(git));
+
+    toml::to_string(&Value::Table(root)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    const PLAIN_TOML: &str = r#"
+        [account]
+        username = "test@test.de"
+        email = "test@test.de"
+        imap_server = "test.test.de"
+        password = "secret"
+
+        [editor]
+        command = "nvim"
+        arguments = ["-c", "startinsert"]
+
+        [sync]
+        trash_retention_days = 7
+
+        [tui]
+        sort = "created"
+
+        [server]
+        token = "letmein"
+
+        [hooks]
+        post_sync = "notify-send synced"
+
+        [git]
+        repository = "/tmp/notes-history"
+        "#;
+
+    #[test]
+    fn toml_config() {
+        let profile = parse(PLAIN_TOML, ConfigFormat::Toml).unwrap();
+        assert_eq!(profile.password_type, "PLAIN");
+        assert_eq!(profile.domain, "test.de");
+        assert_eq!(profile.editor_arguments, vec!["-c", "startinsert"]);
+        assert_eq!(profile.trash_retention_days, 7);
+        assert_eq!(profile.tui, TuiSettings { sort: NoteOrder::Created, sync_on_start: true });
+        assert_eq!(profile.server.token, Some("letmein".to_string()));
+        assert_eq!(profile.hooks, HookSettings { post_sync: Some("notify-send synced".to_string()), ..HookSettings::default() });
+        assert_eq!(profile.git.repository, Some(PathBuf::from("/tmp/notes-history")));
+    }
+
+    /// Every wrong key should be reported, not only the first one
+    #[test]
+    fn toml_issues() {
+        let config = r#"
+            [account]
+            username = "test"
+            email = "test"
+            password_type = "keyring"
+            color = "red"
+
+            [editor]
+            arguments = "-c"
+
+            [sync]
+            trash_retention_days = -2
+
+            [tui]
+            sort = "size"
+
+            [hooks]
+            pre_sync = " "
+            "#;
+
+        let issues = parse(config, ConfigFormat::Toml).err().unwrap();
+        let keys: Vec<&str> = issues.iter().map(|issue| issue.key.as_str()).collect();
+        assert_eq!(keys.len(), 9);
+        for key in ["account.color", "editor.arguments", "account.imap_server", "editor.command",
+                    "account.email", "account.password_type", "sync.trash_retention_days", "tui.sort",
+                    "hooks.pre_sync"].iter() {
+            assert!(keys.contains(key), "{} not reported", key);
+        }
+    }
+
+    #[test]
+    fn invalid_toml() {
+        let issues = parse("[account\nusername=", ConfigFormat::Toml).err().unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].key, CONFIG_FILE);
+    }
+
+    /// Comments and similar keys must not be picked up as values
+    #[test]
+    fn legacy_config() {
+        let config = "
+            # password=commented
+            username=test@test.de
+            imap_server=test.test.de
+            email=test@test.de
+            editor=nvim
+            editor_arguments=-c startinsert
+            password=f
+            ";
+
+        let profile = parse(config, ConfigFormat::Legacy).unwrap();
+        assert_eq!(profile.password, Some("f".to_string()));
+        assert_eq!(profile.editor, "nvim");
+        assert_eq!(profile.editor_arguments, vec!["-c", "startinsert"]);
+        assert_eq!(profile.tui, TuiSettings::default());
+    }
+
+    #[test]
+    fn legacy_issues() {
+        let config = "
+            username=test@test.de
+            imap_server=test.test.de
+            email=test@test.de
+            editor=nvim
+            password=f
+            trash_retention_days=month
+            colour=red
+            ";
+
+        let issues = parse(config, ConfigFormat::Legacy).err().unwrap();
+        assert_eq!(issues, vec![
+            ConfigIssue { key: "trash_retention_days".to_string(), message: "expected an integer, got \"month\"".to_string() },
+        ]);
+    }
+
+    /// Keys older versions ignored must not break existing configs
+    #[test]
+    fn legacy_unknown_keys() {
+        let config = "
+            username=test@test.de
+            imap_server=test.test.de
+            email=test@test.de
+            editor=nvim
+            password=f
+            colour=red
+            ";
+
+        assert_eq!(parse(config, ConfigFormat::Legacy).unwrap().editor, "nvim");
+    }
+
+    /// The rendered config should be readable again, without exposing the password
+    #[test]
+    fn render_config() {
+        let rendered = render(&parse(PLAIN_TOML, ConfigFormat::Toml).unwrap());
+        assert!(!rendered.contains("secret"));
+        assert!(!rendered.contains("letmein"));
+
+        let profile = parse(&rendered, ConfigFormat::Toml).unwrap();
+        assert_eq!(profile.password, Some(MASKED_PASSWORD.to_string()));
+        assert_eq!(profile.tui.sort, NoteOrder::Created);
+        assert_eq!(profile.hooks.post_sync, Some("notify-send synced".to_string()));
+        assert_eq!(profile.git.repository, Some(PathBuf::from("/tmp/notes-history")));
+    }
+}
@@ -12,7 +12,7 @@ use ::{schema};
 use diesel::{SqliteConnection, Connection};
 use diesel::*;
 use diesel::result::Error;
-use model::{NotesMetadata, Body};
+use model::{NotesMetadata, NewMetadata, Body, METADATA_COLUMNS};
 use schema::metadata::dsl::metadata;
 use schema::body::dsl::body;
 use self::log::*;
@@ -21,9 +21,21 @@ use std::collections::HashSet;
 use std::collections::hash_map::RandomState;
 use schema::metadata::columns::subfolder;
 use notes::localnote::LocalNote;
+use search::{SearchHit, MATCH_START, MATCH_END};
+use diesel::sql_types::{Text, Double, BigInt};
+use model::Tag;
+use tags::extract_tags;
+use revisions::{Revision, NewRevision, RevisionKind};
+use trash::{TrashOrigin, TrashedNote, TrashEntry, NewTrashEntry, group_entries};
+use util::parse_date;
+use chrono::{NaiveDateTime, Utc};
+use std::path::Path;
 
 embed_migrations!("../migrations/");
 
+/// Recorded once the RFC 2822 dates of older versions got converted into timestamps
+const LEGACY_DATES_UPGRADE: &str = "legacy_dates";
+
 pub trait DatabaseService {
     /// Deletes everything
     fn delete_everything(&self) -> Result<(), Error>;
@@ -66,6 +78,30 @@ pub trait DatabaseService {
     /// Deletes a single metadata object, needed to delete widow_metadata_entries
     fn delete_metadata(&self, uuid: &str) -> Result<(), Error>;
     fn replace_notes(&self, notes: &Vec<Body>, uuid: String) -> Result<(), Error>;
+    /// Returns the note bodies whose text matches the passed FTS5 query, best matches first.
+    /// Notes that are flagged for deletion are left out
+    fn search(&self, fts_query: &str) -> Result<Vec<SearchHit>, Error>;
+    /// Returns every hashtag together with the number of notes that contain it,
+    /// most used tags first
+    fn fetch_tags(&self) -> Result<Vec<(String, i64)>, Error>;
+    /// Returns the uuids of all notes that contain the passed hashtag
+    fn fetch_uuids_with_tag(&self, tag: &str) -> Result<HashSet<String>, Error>;
+    /// Returns the uuids of all notes that got modified at or after the passed UTC date
+    fn fetch_uuids_modified_since(&self, since: &NaiveDateTime) -> Result<HashSet<String>, Error>;
+    /// Returns all snapshots of replaced bodies of the note with the passed uuid,
+    /// newest first
+    fn fetch_revisions(&self, uuid: &str) -> Result<Vec<Revision>, Error>;
+    /// Returns the snapshot with the passed id
+    fn fetch_revision(&self, id: i64) -> Result<Option<Revision>, Error>;
+    /// Deletes the passed local_note and keeps a copy of it inside the trash
+    fn move_to_trash(&self, local_note: &LocalNote, origin: TrashOrigin) -> Result<(), Error>;
+    /// Returns all notes inside the trash, latest deletions first
+    fn fetch_trash(&self) -> Result<Vec<TrashedNote>, Error>;
+    /// Removes the note with the passed uuid from the trash
+    fn delete_from_trash(&self, uuid: &str) -> Result<(), Error>;
+    /// Removes all notes from the trash that got deleted before the passed RFC 3339 date,
+    /// or every note if no date is passed. Returns the number of removed notes
+    fn purge_trash(&self, deleted_before: Option<&str>) -> Result<usize, Error>;
 }
 
 struct SqLiteConnector {
@@ -73,32 +109,178 @@ struct SqLiteConnector {
 }
 
 impl SqLiteConnector {
-    fn connect() -> SqliteConnection {
-        let database_url = ::profile::get_db_path().into_os_string().to_string_lossy().to_string();
-
+    fn connect_to(database_url: &str) -> ::error::Result<SqliteConnection> {
         #[cfg(debug)]
         info!("Database Path: {}", database_url);
 
-        let connection = SqliteConnection::establish(&database_url)
-            .expect(&format!("Error connecting to {}", database_url));
+        let connection = SqliteConnection::establish(database_url)?;
 
-        &connection.execute("PRAGMA foreign_keys = ON").unwrap();
+        connection.execute("PRAGMA foreign_keys = ON")?;
         // This will run the necessary migrations.
-        embedded_migrations::run(&connection).unwrap();
+        embedded_migrations::run(&connection)?;
 
-        connection
+        Ok(connection)
     }
 }
 
+#[derive(QueryableByName)]
+struct SearchRow {
+    #[sql_type = "Text"]
+    uuid: String,
+    #[sql_type = "Text"]
+    subfolder: String,
+    #[sql_type = "Text"]
+    text: String,
+    #[sql_type = "Text"]
+    snippet: String,
+    #[sql_type = "Double"]
+    rank: f64,
+}
+
+#[derive(QueryableByName)]
+struct TagCountRow {
+    #[sql_type = "Text"]
+    tag: String,
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
 pub struct SqliteDBConnection {
     connection: ::diesel::sqlite::SqliteConnection
 }
 
 impl SqliteDBConnection {
-    pub fn new() -> SqliteDBConnection {
-        SqliteDBConnection {
-            connection: SqLiteConnector::connect()
+    /// Opens the database that is stored at the given path, the database gets
+    /// created if it does not exist yet
+    pub fn open(path: &Path) -> ::error::Result<SqliteDBConnection> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Ok(SqliteDBConnection {
+            connection: SqLiteConnector::connect_to(&path.to_string_lossy())?
+        }.with_indexed_tags().with_parsed_dates())
+    }
+
+    /// Creates an empty database that only lives as long as the connection
+    pub fn in_memory() -> ::error::Result<SqliteDBConnection> {
+        Ok(SqliteDBConnection {
+            connection: SqLiteConnector::connect_to(":memory:")?
+        })
+    }
+
+    /// Bodies that got inserted before the tags table existed have no tags yet
+    fn with_indexed_tags(self) -> SqliteDBConnection {
+        if let Err(e) = self.index_untagged_bodies() {
+            warn!("Could not index hashtags: {}", e);
+        }
+        self
+    }
+
+    fn index_untagged_bodies(&self) -> Result<(), Error> {
+        let tagged = schema::tags::dsl::tags.select(schema::tags::dsl::message_id);
+        let untagged: Vec<Body> = body
+            .filter(schema::body::dsl::text.like("%#%"))
+            .filter(schema::body::dsl::message_id.ne_all(tagged))
+            .load::<Body>(&self.connection)?;
+
+        self.connection.transaction::<_, Error, _>(|| {
+            for note_body in &untagged {
+                self.insert_tags(note_body)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Notes that got inserted before the timestamp columns existed only have
+    /// the RFC 2822 date column. Their dates are converted once, the conversion
+    /// is recorded inside the upgrades table
+    fn with_parsed_dates(self) -> SqliteDBConnection {
+        if let Err(e) = self.run_upgrade(LEGACY_DATES_UPGRADE, || self.convert_legacy_dates()) {
+            warn!("Could not parse the dates of the notes: {}", e);
+        }
+        self
+    }
+
+    /// Runs the conversion if it is not recorded yet and records it
+    fn run_upgrade<F>(&self, name: &str, conversion: F) -> Result<(), Error>
+        where F: FnOnce() -> Result<(), Error> {
+        let applied = schema::upgrades::dsl::upgrades
+            .find(name)
+            .count()
+            .get_result::<i64>(&self.connection)? > 0;
+        if applied {
+            return Ok(());
         }
+
+        self.connection.transaction::<_, Error, _>(|| {
+            conversion()?;
+            diesel::insert_into(schema::upgrades::table)
+                .values((schema::upgrades::dsl::name.eq(name),
+                         schema::upgrades::dsl::applied_at.eq(Utc::now().to_rfc3339())))
+                .execute(&self.connection)?;
+            Ok(())
+        })
+    }
+
+    /// Parses the RFC 2822 dates into the timestamp columns. The creation date of
+    /// these notes is unknown, the last known modification date is the closest
+    /// guess and unlike the fallback it does not move with the next edit
+    fn convert_legacy_dates(&self) -> Result<(), Error> {
+        let unparsed: Vec<(String, String)> = metadata
+            .filter(schema::metadata::dsl::modified_at.is_null())
+            .filter(schema::metadata::dsl::date.ne(""))
+            .select((schema::metadata::dsl::uuid, schema::metadata::dsl::date))
+            .load::<(String, String)>(&self.connection)?;
+
+        for (note_uuid, legacy_date) in &unparsed {
+            match parse_date(legacy_date) {
+                Some(modified) => {
+                    diesel::update(metadata.find(note_uuid))
+                        .set(schema::metadata::dsl::modified_at.eq(modified))
+                        .execute(&self.connection)?;
+                }
+                None => warn!("Malformed date \"{}\" of note {}", legacy_date, note_uuid)
+            }
+        }
+
+        diesel::update(metadata.filter(schema::metadata::dsl::created_at.is_null()))
+            .set(schema::metadata::dsl::created_at.eq(schema::metadata::dsl::modified_at))
+            .execute(&self.connection)?;
+
+        Ok(())
+    }
+
+    fn insert_tags(&self, note_body: &Body) -> Result<(), Error> {
+        let tags: Vec<Tag> = extract_tags(note_body.text.as_deref().unwrap_or(""))
+            .into_iter()
+            .map(|tag| Tag { tag, message_id: note_body.message_id.clone() })
+            .collect();
+
+        diesel::insert_into(schema::tags::table)
+            .values(&tags)
+            .execute(&self.connection)?;
+
+        Ok(())
+    }
+
+    /// Snapshots all bodies of the note with the passed uuid that are not part of
+    /// the new bodies, so that replaced content can be restored later on
+    fn snapshot_replaced_bodies(&self, uuid: &str, new_bodies: &[Body], kind: RevisionKind) -> Result<(), Error> {
+        let replaced: Vec<Body> = body
+            .filter(metadata_uuid.eq(uuid))
+            .load::<Body>(&self.connection)?
+            .into_iter()
+            .filter(|old_body| new_bodies.iter().all(|new_body| new_body.message_id != old_body.message_id))
+            .collect();
+
+        for old_body in &replaced {
+            diesel::insert_into(schema::revisions::table)
+                .values(&NewRevision::from_body(old_body, kind))
+                .execute(&self.connection)?;
+        }
+
+        Ok(())
     }
 
     pub fn connection(&self) -> &::diesel::sqlite::SqliteConnection {
@@ -117,6 +299,11 @@ impl DatabaseService for SqliteDBConnection {
             diesel::delete(schema::metadata::dsl::metadata)
                 .execute(&self.connection)?;
 
+            diesel::delete(schema::revisions::dsl::revisions)
+                .execute(&self.connection)?;
+
+            diesel::delete(schema::trash::dsl::trash)
+                .execute(&self.connection)?;
 
             Ok(())
         })
@@ -128,12 +315,15 @@ impl DatabaseService for SqliteDBConnection {
                 .values(model)
                 .execute(&self.connection)?;
 
+            self.insert_tags(model)?;
+
             Ok(())
         })
     }
 
     fn update_merged_note(&self, note_body: &Body) -> Result<(), Error> {
         self.connection.transaction::<_,Error,_>(|| {
+            self.snapshot_replaced_bodies(&note_body.metadata_uuid, std::slice::from_ref(note_body), RevisionKind::Merge)?;
             diesel::delete(schema::body::dsl::body
                 .filter(metadata_uuid.eq(note_body.metadata_uuid.clone()))
             )
@@ -190,6 +380,16 @@ impl DatabaseService for SqliteDBConnection {
     fn update(&self, local_note: &LocalNote) -> Result<(), Error> {
         self.connection.transaction::<_, Error, _>(|| {
             //TODO replace with upsert with diesel 2.0
+            let bodies_before = body
+                .filter(metadata_uuid.eq(&local_note.metadata.uuid))
+                .count()
+                .get_result::<i64>(&self.connection)?;
+            let kind = if bodies_before > 1 && local_note.body.len() == 1 {
+                RevisionKind::Merge
+            } else {
+                RevisionKind::Edit
+            };
+            self.snapshot_replaced_bodies(&local_note.metadata.uuid, &local_note.body, kind)?;
             self.delete( local_note)?;
             self.insert_into_db(local_note)?;
             Ok(())
@@ -199,13 +399,11 @@ impl DatabaseService for SqliteDBConnection {
     fn insert_into_db(&self, note: &LocalNote) -> Result<(), Error> {
         self.connection.transaction::<_,Error,_>(|| {
             diesel::insert_into(schema::metadata::table)
-                .values(&note.metadata)
+                .values(&NewMetadata::from_metadata(&note.metadata))
                 .execute(&self.connection)?;
 
             for note_content in &note.body {
-                diesel::insert_into(schema::body::table)
-                    .values(note_content)
-                    .execute(&self.connection)?;
+                self.append_note(note_content)?;
             }
 
             Ok(())
@@ -215,6 +413,7 @@ impl DatabaseService for SqliteDBConnection {
     fn fetch_all_notes(&self) -> Result<HashSet<LocalNote, RandomState>, Error> {
         let notes: Vec<NotesMetadata> = metadata
             .order(subfolder.asc())
+            .select(METADATA_COLUMNS)
             .load::<NotesMetadata>(&self.connection)?;
 
         let note_bodies: Vec<Body> = ::model::Body::belonging_to(&notes)
@@ -250,6 +449,7 @@ impl DatabaseService for SqliteDBConnection {
                 .eq(&note_bodies.first().unwrap().metadata_uuid)
             )
             .limit(1)
+            .select(METADATA_COLUMNS)
             .load::<NotesMetadata>(&self.connection)?;
 
         let first_metadata = m_data.first()
@@ -266,6 +466,7 @@ impl DatabaseService for SqliteDBConnection {
     fn fetch_single_note(&self, id: &str) -> Result<Option<LocalNote>, Error> {
         let mut notes: Vec<NotesMetadata> = metadata
             .filter(schema::metadata::dsl::uuid.eq(&id))
+            .select(METADATA_COLUMNS)
             .load::<NotesMetadata>(&self.connection)?;
 
         assert!(notes.len() <= 1);
@@ -292,6 +493,7 @@ impl DatabaseService for SqliteDBConnection {
     fn is_widow(&self, metadata_unique_id: &str) -> Result<bool, Error> {
         let first_note = metadata
             .filter(schema::metadata::dsl::uuid.eq(&metadata_unique_id))
+            .select(METADATA_COLUMNS)
             .load::<NotesMetadata>(&self.connection)?;
 
         let first_note = first_note.first();
@@ -318,6 +520,7 @@ impl DatabaseService for SqliteDBConnection {
 
     fn replace_notes(&self, notes: &Vec<Body>, uuid: String) -> Result<(), Error> {
         self.connection.transaction::<_, Error, _>(|| {
+            self.snapshot_replaced_bodies(&uuid, notes, RevisionKind::Sync)?;
 
             diesel::delete(schema::body::dsl::body)
                 .filter(schema::body::dsl::metadata_uuid.eq(uuid))
@@ -330,6 +533,131 @@ impl DatabaseService for SqliteDBConnection {
             Ok(())
         })
     }
+
+    fn search(&self, fts_query: &str) -> Result<Vec<SearchHit>, Error> {
+        let rows: Vec<SearchRow> = diesel::sql_query(
+            "SELECT body.metadata_uuid AS uuid, metadata.subfolder AS subfolder, \
+                    COALESCE(body.text, '') AS text, \
+                    snippet(body_fts, 1, ?, ?, '…', 12) AS snippet, \
+                    bm25(body_fts) AS rank \
+             FROM body_fts \
+             JOIN body ON body.message_id = body_fts.message_id \
+             JOIN metadata ON metadata.uuid = body.metadata_uuid \
+             WHERE body_fts MATCH ? AND metadata.locally_deleted = 0 \
+             ORDER BY rank")
+            .bind::<Text, _>(MATCH_START)
+            .bind::<Text, _>(MATCH_END)
+            .bind::<Text, _>(fts_query)
+            .load(&self.connection)?;
+
+        Ok(rows.into_iter().map(|row| SearchHit {
+            subject: row.text.lines().next().unwrap_or("").to_string(),
+            uuid: row.uuid,
+            folder: row.subfolder,
+            snippet: row.snippet,
+            // bm25 returns lower values for better matches
+            rank: -row.rank,
+        }).collect())
+    }
+
+    fn fetch_tags(&self) -> Result<Vec<(String, i64)>, Error> {
+        let rows: Vec<TagCountRow> = diesel::sql_query(
+            "SELECT tags.tag AS tag, COUNT(DISTINCT body.metadata_uuid) AS count \
+             FROM tags \
+             JOIN body ON body.message_id = tags.message_id \
+             GROUP BY tags.tag \
+             ORDER BY count DESC, tags.tag ASC")
+            .load(&self.connection)?;
+
+        Ok(rows.into_iter().map(|row| (row.tag, row.count)).collect())
+    }
+
+    fn fetch_uuids_with_tag(&self, tag: &str) -> Result<HashSet<String>, Error> {
+        let uuids: Vec<String> = schema::tags::dsl::tags
+            .inner_join(body)
+            .filter(schema::tags::dsl::tag.eq(tag))
+            .select(metadata_uuid)
+            .distinct()
+            .load::<String>(&self.connection)?;
+
+        Ok(uuids.into_iter().collect())
+    }
+
+    fn fetch_uuids_modified_since(&self, since: &NaiveDateTime) -> Result<HashSet<String>, Error> {
+        let uuids: Vec<String> = metadata
+            .filter(schema::metadata::dsl::modified_at.ge(since))
+            .select(schema::metadata::dsl::uuid)
+            .load::<String>(&self.connection)?;
+
+        Ok(uuids.into_iter().collect())
+    }
+
+    fn fetch_revisions(&self, uuid: &str) -> Result<Vec<Revision>, Error> {
+        schema::revisions::dsl::revisions
+            .filter(schema::revisions::dsl::metadata_uuid.eq(uuid))
+            .order(schema::revisions::dsl::id.desc())
+            .load::<Revision>(&self.connection)
+    }
+
+    fn fetch_revision(&self, id: i64) -> Result<Option<Revision>, Error> {
+        schema::revisions::dsl::revisions
+            .find(id)
+            .first::<Revision>(&self.connection)
+            .optional()
+    }
+
+    fn move_to_trash(&self, local_note: &LocalNote, origin: TrashOrigin) -> Result<(), Error> {
+        self.connection.transaction::<_, Error, _>(|| {
+            // Only the latest deletion of a note is kept
+            self.delete_from_trash(&local_note.metadata.uuid)?;
+
+            diesel::insert_into(schema::trash::table)
+                .values(&NewTrashEntry::from_note(local_note, origin))
+                .execute(&self.connection)?;
+
+            self.delete(local_note)
+        })
+    }
+
+    fn fetch_trash(&self) -> Result<Vec<TrashedNote>, Error> {
+        use schema::trash::dsl::*;
+
+        let entries = trash
+            .select((uuid, subfolder, date, mime_version, message_id, text, origin, deleted_at, created_date))
+            .order((deleted_at.desc(), id.asc()))
+            .load::<TrashEntry>(&self.connection)?;
+
+        Ok(group_entries(entries))
+    }
+
+    fn delete_from_trash(&self, uuid: &str) -> Result<(), Error> {
+        diesel::delete(schema::trash::dsl::trash)
+            .filter(schema::trash::dsl::uuid.eq(uuid))
+            .execute(&self.connection)?;
+        Ok(())
+    }
+
+    fn purge_trash(&self, deleted_before: Option<&str>) -> Result<usize, Error> {
+        self.connection.transaction::<_, Error, _>(|| {
+            let uuids: Vec<String> = match deleted_before {
+                Some(date) => schema::trash::dsl::trash
+                    .filter(schema::trash::dsl::deleted_at.lt(date))
+                    .select(schema::trash::dsl::uuid)
+                    .distinct()
+                    .load::<String>(&self.connection)?,
+                None => schema::trash::dsl::trash
+                    .select(schema::trash::dsl::uuid)
+                    .distinct()
+                    .load::<String>(&self.connection)?
+            };
+
+            for uuid in &uuids {
+                self.delete_from_trash(uuid)?;
+            }
+
+            Ok(uuids.len())
+        })
+    }
 }
 
 #[cfg(test)]
@@ -339,6 +667,7 @@ mod db_tests {
     use ::model::Body;
     use super::*;
     use notes::traits::identifyable_note::IdentifiableNote;
+    use notes::traits::header_parser::HeaderParser;
 
     /// Should return an error, because this note still has child note_bodies
     #[test]
@@ -352,9 +681,8 @@ mod db_tests {
             note_body.clone()
         ];
 
-        let db_connection = ::db::SqliteDBConnection::new();
+        let db_connection = SqliteDBConnection::in_memory().unwrap();
 
-        db_connection.delete_everything().unwrap();
         db_connection.insert_into_db(&note).unwrap();
 
         match db_connection.delete_metadata(&note.uuid()) {
@@ -379,9 +707,8 @@ mod db_tests {
 
         let note_body = note.body[0].clone();
 
-        let db_connection = ::db::SqliteDBConnection::new();
+        let db_connection = SqliteDBConnection::in_memory().unwrap();
 
-        db_connection.delete_everything().unwrap();
         db_connection.insert_into_db(&note).unwrap();
 
         match db_connection.delete_note_body(&note_body) {
@@ -410,9 +737,8 @@ mod db_tests {
 
         let note_body = note.body[0].clone();
 
-        let db_connection = ::db::SqliteDBConnection::new();
+        let db_connection = SqliteDBConnection::in_memory().unwrap();
 
-        db_connection.delete_everything().unwrap();
         db_connection.insert_into_db(&note).unwrap();
 
         match db_connection.delete_note_body(&note_body) {
@@ -453,9 +779,8 @@ mod db_tests {
             BodyMetadataBuilder::new().with_text("meem\nTestTestTest").build()
         ];
 
-        let db_connection = ::db::SqliteDBConnection::new();
+        let db_connection = SqliteDBConnection::in_memory().unwrap();
 
-        db_connection.delete_everything().unwrap();
         db_connection.insert_into_db(&note).unwrap();
 
         match db_connection.fetch_single_note_with_name("meem") {
@@ -484,8 +809,7 @@ mod db_tests {
             BodyMetadataBuilder::new().build()
     ];
 
-        let con =  ::db::SqliteDBConnection::new();
-        con.delete_everything().unwrap();
+        let con =  ::db::SqliteDBConnection::in_memory().unwrap();
 
         assert_eq!(con.insert_into_db(&note).is_err(), true);
 
@@ -513,8 +837,7 @@ mod db_tests {
             body3
     ];
 
-        let con =  ::db::SqliteDBConnection::new();
-        con.delete_everything().expect("Should delete the db");
+        let con =  ::db::SqliteDBConnection::in_memory().unwrap();
         con.insert_into_db(&note).expect("Should insert note into the db");
         con.insert_into_db(&note_with_2_bodies).expect("Should insert note into the db");
 
@@ -574,8 +897,7 @@ mod db_tests {
     #[test]
     fn update_single_note() {
         use builder::HeaderBuilder;
-        let con =  ::db::SqliteDBConnection::new();
-        con.delete_everything().expect("Should delete the db");
+        let con =  ::db::SqliteDBConnection::in_memory().unwrap();
 
         let m_data: ::model::NotesMetadata =
             NotesMetadata::new(
@@ -635,8 +957,7 @@ mod db_tests {
     /// The correct note should remain in side the db
     #[test]
     fn delete_single_note() {
-        let con =  ::db::SqliteDBConnection::new();
-        con.delete_everything().expect("Should delete the db");
+        let con =  ::db::SqliteDBConnection::in_memory().unwrap();
 
         let m_data: ::model::NotesMetadata =
             NotesMetadata::new(&::builder::HeaderBuilder::new().build(),
@@ -693,8 +1014,7 @@ mod db_tests {
     #[test]
     fn insert_single_note() {
         use builder::HeaderBuilder;
-        let con =  ::db::SqliteDBConnection::new();
-        con.delete_everything().expect("Should delete the db");
+        let con =  ::db::SqliteDBConnection::in_memory().unwrap();
         let m_data: ::model::NotesMetadata =
             NotesMetadata::new(
                 &HeaderBuilder::new().build(),
@@ -731,8 +1051,7 @@ mod db_tests {
         use builder::HeaderBuilder;
         //Setup
         dotenv::dotenv().ok();
-        let con =  ::db::SqliteDBConnection::new();
-        con.delete_everything().expect("Should delete everything");
+        let con =  ::db::SqliteDBConnection::in_memory().unwrap();
 
         let m_data: ::model::NotesMetadata =
             NotesMetadata::new(&HeaderBuilder::new().build(), "test".to_string());
@@ -757,8 +1076,7 @@ mod db_tests {
         use builder::HeaderBuilder;
 
         dotenv::dotenv().ok();
-        let con =  ::db::SqliteDBConnection::new();
-        con.delete_everything().expect("Should delete everything");
+        let con =  ::db::SqliteDBConnection::in_memory().unwrap();
         let m_data: ::model::NotesMetadata =
             NotesMetadata::new(&HeaderBuilder::new().build(), "test".to_string());
 
@@ -798,8 +1116,7 @@ mod db_tests {
 
         //Setup
         dotenv::dotenv().ok();
-        let con =  ::db::SqliteDBConnection::new();
-        con.delete_everything().expect("Should delete everything");
+        let con =  ::db::SqliteDBConnection::in_memory().unwrap();
         let m_data: ::model::NotesMetadata =
             NotesMetadata::new(&HeaderBuilder::new().build(), "test".to_string());
 
@@ -850,8 +1167,7 @@ mod db_tests {
     fn test_delete_multiple_bodies() {
 
         dotenv::dotenv().ok();
-        let con = ::db::SqliteDBConnection::new();
-        con.delete_everything().expect("Should delete everything");
+        let con = ::db::SqliteDBConnection::in_memory().unwrap();
 
         let first = note![
                 NotesMetadataBuilder::new().with_uuid("1").build(),
@@ -881,4 +1197,94 @@ mod db_tests {
         assert_eq!(notes.iter().next().unwrap().metadata.uuid,"1".to_string());
 
     }
+    /// Malformed dates should not panic, creation dates should be kept and
+    /// notes of older versions should get their dates parsed on connect
+    #[test]
+    fn note_timestamps() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let mut headers = HeaderBuilder::new().with_uuid("malformed".to_string()).build();
+        headers.retain(|(key, _)| key != "Date" && key != "X-Mail-Created-Date");
+        headers.push(("Date".to_string(), "yesterday".to_string()));
+        headers.push(("X-Mail-Created-Date".to_string(), "Mon, 1 Feb 2021 08:00:00 +0100".to_string()));
+        let malformed = NotesMetadata::new(&headers, "Notes".to_string());
+        assert_eq!(malformed.date, None);
+        assert_eq!(malformed.timestamp(), 0);
+        assert_eq!(malformed.created_date, parse_date("2021-02-01T07:00:00Z"));
+
+        let mut note = note![
+            NotesMetadataBuilder::new().with_uuid("1").with_date(parse_date("2021-02-10T12:00:00Z").unwrap()).build(),
+            BodyMetadataBuilder::new().build()
+        ];
+        note.metadata.created_date = parse_date("2021-02-01T12:00:00Z");
+        con.insert_into_db(&note).unwrap();
+        con.insert_into_db(&note![malformed, BodyMetadataBuilder::new().with_metadata_uuid("malformed").build()]).unwrap();
+
+        let stored = con.fetch_single_note("1").unwrap().unwrap();
+        assert_eq!(stored.metadata.date, note.metadata.date);
+        assert_eq!(stored.metadata.created_date, note.metadata.created_date);
+        assert_eq!(
+            stored.to_remote_metadata().headers.get_header_value("X-Mail-Created-Date"),
+            Some("Mon, 01 Feb 2021 12:00:00 +0000".to_string())
+        );
+
+        let since = parse_date("2021-02-05T00:00:00Z").unwrap();
+        assert_eq!(con.fetch_uuids_modified_since(&since).unwrap(), vec!["1".to_string()].into_iter().collect());
+
+        // Rows of older versions only have the RFC 2822 date column
+        diesel::update(metadata.find("1"))
+            .set((schema::metadata::dsl::modified_at.eq(None::<NaiveDateTime>),
+                  schema::metadata::dsl::date.eq("Wed, 10 Feb 2021 12:00:00 +0000")))
+            .execute(&con.connection)
+            .unwrap();
+        assert!(con.fetch_uuids_modified_since(&since).unwrap().is_empty());
+        let con = con.with_parsed_dates();
+        assert_eq!(con.fetch_uuids_modified_since(&since).unwrap().len(), 1);
+    }
+
+    /// Legacy dates are converted once, notes without creation date get the
+    /// modification date as creation date
+    #[test]
+    fn legacy_dates_upgrade() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+        let note = note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().build()
+        ];
+        con.insert_into_db(&note).unwrap();
+
+        let reset_dates = |con: &SqliteDBConnection| diesel::update(metadata.find("1"))
+            .set((schema::metadata::dsl::modified_at.eq(None::<NaiveDateTime>),
+                  schema::metadata::dsl::created_at.eq(None::<NaiveDateTime>),
+                  schema::metadata::dsl::date.eq("Wed, 10 Feb 2021 13:00:00 +0100")))
+            .execute(&con.connection)
+            .unwrap();
+
+        reset_dates(&con);
+        let con = con.with_parsed_dates();
+        let stored = con.fetch_single_note("1").unwrap().unwrap();
+        assert_eq!(stored.metadata.date, parse_date("2021-02-10T12:00:00Z"));
+        assert_eq!(stored.metadata.created_date, stored.metadata.date);
+
+        // Recorded conversions do not run again
+        reset_dates(&con);
+        let con = con.with_parsed_dates();
+        assert_eq!(con.fetch_single_note("1").unwrap().unwrap().metadata.date, None);
+    }
+    /// Databases at a path should be created with missing directories and keep their content
+    #[test]
+    fn open_path() {
+        let dir = std::env::temp_dir().join("apnotes_db_open");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join("notes_db");
+
+        let note = note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().build()
+        ];
+        SqliteDBConnection::open(&path).unwrap().insert_into_db(&note).unwrap();
+
+        assert!(SqliteDBConnection::open(&path).unwrap().fetch_single_note("1").unwrap().is_some());
+        assert!(SqliteDBConnection::open(&dir).is_err());
+    }
 }
\ No newline at end of file
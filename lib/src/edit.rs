@@ -65,18 +65,55 @@ pub fn edit_note(local_note: &LocalNote, new: bool, profile: &Profile) -> Result
         .and_then(|localnote| remove_temp_file(&file_path).map(|_| localnote))
 }
 
+/// Replaces the text of the passed note without spawning an editor, the metadata
+/// gets altered exactly like after an editor edit
+pub fn replace_text(local_note: &LocalNote, text: &str, profile: &Profile) -> Result<LocalNote, NoteError> {
+    if local_note.needs_merge() {
+        return Err(NoteError::NeedsMerge);
+    }
+
+    let note = local_note.body.first()
+        .expect("Expected at least 1 note body");
+
+    edited_note(local_note, note, text, profile)
+}
+
+/// Appends the text as new line(s) to the end of the passed note
+pub fn append_text(local_note: &LocalNote, text: &str, profile: &Profile) -> Result<LocalNote, NoteError> {
+    let present_text = local_note.body.first()
+        .and_then(|body| body.text.clone())
+        .unwrap_or_default();
+
+    let text = if present_text.is_empty() || present_text.ends_with('\n') {
+        format!("{}{}", present_text, text)
+    } else {
+        format!("{}\n{}", present_text, text)
+    };
+
+    replace_text(local_note, &text, profile)
+}
+
 fn remove_temp_file(file_path: &String) -> Result<(), NoteError> {
     info!("Removing temp file {}", &file_path);
     std::fs::remove_file(&file_path)
         .map_err(|e| NoteError::EditError(e.to_string()))
 }
 
-fn read_edited_text(local_note: &LocalNote, note: &Body, file_path: &str, _profile: &Profile) -> Result<LocalNote, NoteError> {
+fn read_edited_text(local_note: &LocalNote, note: &Body, file_path: &str, profile: &Profile) -> Result<LocalNote, NoteError> {
     //Read content and save to body.text
     let file_content = std::fs::read_to_string(&file_path)
         .map_err(|e| NoteError::EditError(e.to_string()))?;
 
-    if &file_content == note.text.as_ref().unwrap_or(&"".to_string())
+    edited_note(local_note, note, &file_content, profile)
+}
+
+/// Creates the edited version of local_note with the passed text as content
+///
+/// The edit date gets set to the current date and the body gets a new message-id,
+/// the previous message-id is kept, so that the sync process is able to
+/// detect if the note also changed remotely
+pub(crate) fn edited_note(local_note: &LocalNote, note: &Body, text: &str, _profile: &Profile) -> Result<LocalNote, NoteError> {
+    if text == note.text.as_ref().unwrap_or(&"".to_string())
         && local_note.metadata.new == false {
         return Err(ContentNotChanged);
     } else {
@@ -85,20 +122,21 @@ fn read_edited_text(local_note: &LocalNote, note: &Body, file_path: &str, _profi
             subfolder: local_note.metadata.subfolder.clone(),
             locally_deleted: local_note.metadata.locally_deleted,
             new: local_note.metadata.new,
-            date: Utc::now().to_rfc2822(),
+            date: Some(Utc::now().naive_utc()),
             uuid: local_note.metadata.uuid.clone(),
-            mime_version: local_note.metadata.mime_version.clone()
+            mime_version: local_note.metadata.mime_version.clone(),
+            created_date: local_note.metadata.created_date
         };
 
         #[cfg(not(test))]
         let mut body = BodyMetadataBuilder::new(_profile)
             .with_uid(note.uid.clone())
-            .with_text(&file_content);
+            .with_text(text);
 
         #[cfg(test)]
             let mut body = BodyMetadataBuilder::new()
             .with_uid(note.uid.clone())
-            .with_text(&file_content);
+            .with_text(text);
 
         if local_note.metadata.new == false {
             body = body.with_old_remote_message_id(&note.message_id);
@@ -126,9 +164,8 @@ fn replace_uuid(string: &str) -> String {
 #[cfg(test)]
 mod edit_tests {
     use error::NoteError;
-    use edit::{edit_note, replace_uuid};
+    use edit::{edit_note, edited_note, replace_uuid, append_text, replace_text};
     use builder::*;
-    use profile::Profile;
 
     #[test]
     fn should_generate_new_uuid() {
@@ -145,22 +182,60 @@ mod edit_tests {
         BodyMetadataBuilder::new().build()
     );
 
-        let profile = Profile {
-            username: "".to_string(),
-            password: Option::from("".to_string()),
-            imap_server: "".to_string(),
-            email: "".to_string(),
-            editor: "".to_string(),
-            editor_arguments: vec![],
-            secret_service_attribute: None,
-            secret_service_value: None,
-            domain: "".to_string(),
-            password_type: "".to_string()
-        };
+        let profile = ProfileBuilder::for_tests().build();
 
         match edit_note(&note, false, &profile) {
             Err(e) => { assert_eq!(e, NoteError::NeedsMerge) }
             Ok(_) => panic!("Should be error")
         }
     }
+
+    /// Editing an already edited note should refer to the message-id of the previous edit
+    #[test]
+    fn edit_twice() {
+        let profile = ProfileBuilder::for_tests().build();
+
+        let note = note!(
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_message_id("remote").with_text("text").build()
+        );
+
+        let first_edit = edited_note(&note, &note.body[0], "first edit", &profile).unwrap();
+        let second_edit = edited_note(&first_edit, &first_edit.body[0], "second edit", &profile).unwrap();
+
+        assert_eq!(first_edit.body[0].old_remote_message_id, Some("remote".to_string()));
+        assert_eq!(second_edit.body[0].old_remote_message_id, Some(first_edit.body[0].message_id.clone()));
+        assert_ne!(second_edit.body[0].message_id, first_edit.body[0].message_id);
+        assert_eq!(
+            edited_note(&second_edit, &second_edit.body[0], "second edit", &profile).err(),
+            Some(NoteError::ContentNotChanged)
+        );
+    }
+
+    /// Appending should behave like an editor edit of a note that is present on the server
+    #[test]
+    fn append_to_note() {
+        let profile = ProfileBuilder::for_tests().build();
+
+        let note = note!(
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_message_id("remote").with_text("Shopping\nMilk").build()
+        );
+
+        let appended = append_text(&note, "Eggs\n", &profile).unwrap();
+        assert_eq!(appended.body[0].text, Some("Shopping\nMilk\nEggs\n".to_string()));
+        assert_eq!(appended.body[0].old_remote_message_id, Some("remote".to_string()));
+        assert!(!appended.metadata.new);
+
+        let appended_twice = append_text(&appended, "Bread", &profile).unwrap();
+        assert_eq!(appended_twice.body[0].text, Some("Shopping\nMilk\nEggs\nBread".to_string()));
+        assert_eq!(appended_twice.body[0].old_remote_message_id, Some(appended.body[0].message_id.clone()));
+
+        let merge_note = note!(
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().build()
+        );
+        assert_eq!(replace_text(&merge_note, "text", &profile).err(), Some(NoteError::NeedsMerge));
+    }
 }
@@ -1,5 +1,7 @@
 #[cfg(target_family = "unix")]
 use xdg::BaseDirectoriesError;
+use config::ConfigIssue;
+use lookup::Candidate;
 
 pub type Result<T> = std::result::Result<T, Box<dyn ErrorCode>>;
 
@@ -14,12 +16,27 @@ pub enum ProfileError {
     NotFound(String),
     NoPasswordProvided(),
     AgentLocked(),
+    InvalidConfig(Vec<ConfigIssue>),
+    PasswordCommandFailed(String),
 }
 
 #[derive(Debug,PartialEq)]
 pub enum UpdateError {
     SyncError(String),
-    IoError(String)
+    IoError(String),
+    /// A pre hook exited with an error, the operation got aborted
+    HookFailed(String)
+}
+
+/// Errors of the requests apnotes serve answers
+#[derive(Debug,PartialEq)]
+pub enum ServerError {
+    Unauthorized,
+    BadRequest(String),
+    UnknownRoute(String),
+    SyncRunning,
+    /// The notes stayed locked too long, e.g. by a running sync
+    Busy,
 }
 
 #[derive(Debug,PartialEq)]
@@ -28,7 +45,10 @@ pub enum NoteError {
     EditError(String),
     NeedsMerge,
     ContentNotChanged,
-    NoteNotFound
+    NoteNotFound,
+    RevisionNotFound,
+    /// The reference matches all of the candidates
+    AmbiguousNote(String, Vec<Candidate>)
 }
 
 
@@ -48,6 +68,8 @@ impl ErrorCode for ProfileError {
             ProfileError::NotFound(_) => { 1 }
             ProfileError::NoPasswordProvided() => { 2 }
             ProfileError::AgentLocked() => { 3 }
+            ProfileError::InvalidConfig(_) => { 4 }
+            ProfileError::PasswordCommandFailed(_) => { 5 }
         }
     }
 
@@ -61,6 +83,7 @@ impl ErrorCode for UpdateError {
         match self {
             UpdateError::SyncError(_) => { 20 }
             UpdateError::IoError(_) => { 21 }
+            UpdateError::HookFailed(_) => { 22 }
         }
     }
 
@@ -69,6 +92,22 @@ impl ErrorCode for UpdateError {
     }
 }
 
+impl ErrorCode for ServerError {
+    fn error_code(&self) -> i32 {
+        match self {
+            ServerError::Unauthorized => { 40 }
+            ServerError::BadRequest(_) => { 41 }
+            ServerError::UnknownRoute(_) => { 42 }
+            ServerError::SyncRunning => { 43 }
+            ServerError::Busy => { 44 }
+        }
+    }
+
+    fn human_readable_error_message(&self) -> String {
+        "The request could not be handled:".to_string()
+    }
+}
+
 impl ErrorCode for NoteError {
     fn error_code(&self) -> i32 {
         match self {
@@ -77,6 +116,8 @@ impl ErrorCode for NoteError {
             NoteError::NeedsMerge => { 32 }
             NoteError::ContentNotChanged => { 33 }
             NoteError::NoteNotFound => { 34 }
+            NoteError::RevisionNotFound => { 35 }
+            NoteError::AmbiguousNote(_, _) => { 36 }
         }
     }
 
@@ -95,6 +136,26 @@ impl ErrorCode for diesel::result::Error {
     }
 }
 
+impl ErrorCode for diesel::ConnectionError {
+    fn error_code(&self) -> i32 {
+        return 255;
+    }
+
+    fn human_readable_error_message(&self) -> String {
+        "Could not open the database:".to_string()
+    }
+}
+
+impl ErrorCode for diesel_migrations::RunMigrationsError {
+    fn error_code(&self) -> i32 {
+        return 255;
+    }
+
+    fn human_readable_error_message(&self) -> String {
+        "Could not migrate the database:".to_string()
+    }
+}
+
 impl ErrorCode for std::str::Utf8Error {
     fn error_code(&self) -> i32 {
         return 255;
@@ -163,6 +224,12 @@ impl std::convert::From<NoteError> for std::boxed::Box<dyn ErrorCode> {
     }
 }
 
+impl std::convert::From<ServerError> for std::boxed::Box<dyn ErrorCode> {
+    fn from(e: ServerError) -> Self {
+        Box::new(e)
+    }
+}
+
 impl std::convert::From<UpdateError> for std::boxed::Box<dyn ErrorCode> {
     fn from(e: UpdateError) -> Self {
         Box::new(e)
@@ -182,6 +249,18 @@ impl std::convert::From<diesel::result::Error> for  std::boxed::Box<dyn ErrorCod
     }
 }
 
+impl std::convert::From<diesel::ConnectionError> for  std::boxed::Box<dyn ErrorCode> {
+    fn from(e: diesel::ConnectionError) -> Self {
+        Box::new(e)
+    }
+}
+
+impl std::convert::From<diesel_migrations::RunMigrationsError> for  std::boxed::Box<dyn ErrorCode> {
+    fn from(e: diesel_migrations::RunMigrationsError) -> Self {
+        Box::new(e)
+    }
+}
+
 impl std::convert::From<ProfileError> for  std::boxed::Box<dyn ErrorCode> {
     fn from(e: ProfileError) -> Self {
         Box::new(e)
@@ -222,6 +301,7 @@ impl std::convert::From<imap::Error> for  std::boxed::Box<dyn ErrorCode> {
 impl std::error::Error for ProfileError {}
 impl std::error::Error for NoteError {}
 impl std::error::Error for UpdateError {}
+impl std::error::Error for ServerError {}
 
 impl std::fmt::Display for UpdateError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -229,14 +309,40 @@ impl std::fmt::Display for UpdateError {
     }
 }
 
+impl std::fmt::Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::Unauthorized => write!(f, "Missing or wrong bearer token"),
+            ServerError::BadRequest(reason) => write!(f, "Bad request: {}", reason),
+            ServerError::UnknownRoute(route) => write!(f, "No such endpoint: {}", route),
+            ServerError::SyncRunning => write!(f, "A sync is already running"),
+            ServerError::Busy => write!(f, "The notes are busy, e.g. with a sync, try again later"),
+        }
+    }
+}
+
 impl std::fmt::Display for NoteError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            NoteError::AmbiguousNote(reference, candidates) => {
+                write!(f, "\"{}\" matches {} notes, use the uuid or folder/title:", reference, candidates.len())?;
+                for candidate in candidates {
+                    write!(f, "\n  {}  {}  [{}]", candidate.uuid, candidate.folder, candidate.subject)?;
+                }
+                Ok(())
+            }
+            _ => write!(f, "{:?}", self)
+        }
     }
 }
 
 impl std::fmt::Display for ProfileError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            ProfileError::InvalidConfig(issues) => {
+                write!(f, "Invalid config: {}", issues.iter().map(|issue| issue.to_string()).collect::<Vec<String>>().join(", "))
+            }
+            _ => write!(f, "{:?}", self)
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,335 @@
+extern crate walkdir;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use self::walkdir::WalkDir;
+use notes::localnote::LocalNote;
+use notes::traits::identifyable_note::IdentifiableNote;
+use model::Body;
+use error::Result;
+use util::format_date;
+
+/// File extension of exported markdown notes
+pub const MARKDOWN_EXTENSION: &str = "md";
+/// Root folder of all notes on the imap server
+const ROOT_FOLDER: &str = "Notes";
+
+/// Formats notes can be exported to
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportFormat {
+    Markdown,
+}
+
+/// Counts of the files that got touched by an export
+#[derive(Debug, PartialEq, Default)]
+pub struct ExportSummary {
+    pub written: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+/// Metadata that gets written as yaml front matter at the top of every
+/// exported note
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct FrontMatter {
+    pub uuid: Option<String>,
+    pub folder: Option<String>,
+    pub date: Option<String>,
+    pub message_id: Option<String>,
+}
+
+impl FrontMatter {
+
+    pub fn from_note(note: &LocalNote, body: &Body) -> FrontMatter {
+        FrontMatter {
+            uuid: Some(note.metadata.uuid.clone()),
+            folder: Some(note.folder()),
+            date: note.metadata.date.as_ref().map(format_date),
+            message_id: Some(body.message_id.clone()),
+        }
+    }
+
+    /// Renders the front matter block including the --- delimiters
+    pub fn render(&self) -> String {
+        let entries = vec![
+            ("uuid", &self.uuid),
+            ("folder", &self.folder),
+            ("date", &self.date),
+            ("message-id", &self.message_id),
+        ];
+
+        let lines: String = entries.into_iter()
+            .filter_map(|(key, value)| value.as_ref().map(|value| format!("{}: {}\n", key, quote(value))))
+            .collect();
+
+        format!("---\n{}---\n", lines)
+    }
+
+    /// Splits a file into its front matter and the remaining text. If the file
+    /// does not start with a front matter block the whole content is returned as text
+    pub fn parse(content: &str) -> (Option<FrontMatter>, String) {
+        let mut lines = content.split_inclusive('\n');
+
+        let mut consumed = match lines.next() {
+            Some(line) if line.trim_end() == "---" => line.len(),
+            _ => return (None, content.to_string())
+        };
+
+        let mut front_matter = FrontMatter::default();
+
+        for line in lines {
+            consumed += line.len();
+            let line = line.trim_end();
+            if line == "---" {
+                return (Some(front_matter), content[consumed..].to_string());
+            }
+
+            if let Some((key, value)) = line.split_once(':') {
+                let value = Some(unquote(value.trim()));
+                match key.trim() {
+                    "uuid" => front_matter.uuid = value,
+                    "folder" => front_matter.folder = value,
+                    "date" => front_matter.date = value,
+                    "message-id" => front_matter.message_id = value,
+                    _ => {}
+                }
+            }
+        }
+
+        // No closing delimiter, this is no front matter
+        (None, content.to_string())
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\")
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        value[1..value.len() - 1].replace("''", "'")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Maps an imap folder name to a directory relative to the export root
+///
+/// Notes -> ""
+/// Notes.Work.Projects -> Work/Projects
+pub fn folder_to_path(folder: &str) -> PathBuf {
+    folder.split('.')
+        .skip_while(|part| *part == ROOT_FOLDER)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Maps a directory relative to the export root back to an imap folder name
+pub fn path_to_folder(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .fold(ROOT_FOLDER.to_string(), |folder, part| format!("{}.{}", folder, part))
+}
+
+/// Generates the file name of a note body, notes that need to be merged
+/// get one file per body
+fn file_stem(note: &LocalNote, body: &Body, index: usize) -> String {
+    let subject = body.subject_escaped();
+    let subject = if subject.is_empty() { "untitled".to_string() } else { subject };
+
+    if note.needs_merge() {
+        format!("{}_{}", subject, index + 1)
+    } else {
+        subject
+    }
+}
+
+/// Returns the relative path and the content of every file that represents the passed note
+pub fn note_files(note: &LocalNote) -> Vec<(PathBuf, String)> {
+    let folder = folder_to_path(&note.folder());
+    note.body.iter().enumerate().map(|(index, body)| {
+        let path = folder.join(format!("{}.{}", file_stem(note, body, index), MARKDOWN_EXTENSION));
+        let content = format!("{}{}", FrontMatter::from_note(note, body).render(), body.text.as_deref().unwrap_or(""));
+        (path, content)
+    }).collect()
+}
+
+/// Collects all markdown files below dir that carry a note uuid inside their front matter
+pub fn exported_files(dir: &Path) -> Result<HashMap<PathBuf, FrontMatter>> {
+    let mut files = HashMap::new();
+
+    if !dir.exists() {
+        return Ok(files);
+    }
+
+    for entry in WalkDir::new(dir).into_iter().filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().map(|ext| ext != MARKDOWN_EXTENSION).unwrap_or(true) {
+            continue;
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        if let (Some(front_matter), _) = FrontMatter::parse(&content) {
+            if front_matter.uuid.is_some() {
+                files.insert(path.to_path_buf(), front_matter);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Writes every note as markdown file into dir. Running the export again updates
+/// the files in place. With prune, exported files of notes that do not exist
+/// anymore or got renamed are getting removed
+pub fn export_markdown(notes: &HashSet<LocalNote>, dir: &Path, prune: bool) -> Result<ExportSummary> {
+    let mut summary = ExportSummary::default();
+    let previous_files = if prune { exported_files(dir)? } else { HashMap::new() };
+    let mut written_files: HashSet<PathBuf> = HashSet::new();
+
+    let mut notes: Vec<&LocalNote> = notes.iter().collect();
+    notes.sort_by_key(|note| note.metadata.uuid.clone());
+
+    for note in notes {
+        for (relative_path, content) in note_files(note) {
+            let mut path = dir.join(&relative_path);
+
+            // Another note with the same subject got already written to this path
+            if written_files.contains(&path) {
+                let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+                let uuid_prefix: String = note.metadata.uuid.chars().take(8).collect();
+                path.set_file_name(format!("{}_{}.{}", stem, uuid_prefix, MARKDOWN_EXTENSION));
+            }
+
+            if std::fs::read_to_string(&path).ok().as_ref() == Some(&content) {
+                summary.unchanged += 1;
+            } else {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                debug!("Writing {}", path.display());
+                std::fs::write(&path, content)?;
+                summary.written += 1;
+            }
+
+            written_files.insert(path);
+        }
+    }
+
+    for path in previous_files.keys().filter(|path| !written_files.contains(*path)) {
+        debug!("Removing {}", path.display());
+        std::fs::remove_file(path)?;
+        summary.removed += 1;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+    use builder::{NotesMetadataBuilder, BodyMetadataBuilder};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("apnotes_export_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn front_matter_roundtrip() {
+        let front_matter = FrontMatter {
+            uuid: Some("1".to_string()),
+            folder: Some("Notes.Work".to_string()),
+            date: Some("Sat, 6 Feb 2021 12:00:00 +0000".to_string()),
+            message_id: Some("<\"quoted\"@test.de>".to_string()),
+        };
+
+        let content = format!("{}Title\nText", front_matter.render());
+        let (parsed, text) = FrontMatter::parse(&content);
+
+        assert_eq!(parsed, Some(front_matter));
+        assert_eq!(text, "Title\nText");
+    }
+
+    #[test]
+    fn no_front_matter() {
+        let (parsed, text) = FrontMatter::parse("---\nno front matter");
+        assert_eq!(parsed, None);
+        assert_eq!(text, "---\nno front matter");
+    }
+
+    #[test]
+    fn folder_mapping() {
+        assert_eq!(folder_to_path("Notes"), PathBuf::from(""));
+        assert_eq!(folder_to_path("Notes.Work.Projects"), PathBuf::from("Work/Projects"));
+        assert_eq!(path_to_folder(&PathBuf::from("Work/Projects")), "Notes.Work.Projects");
+        assert_eq!(path_to_folder(&PathBuf::from("")), "Notes");
+    }
+
+    /// Unmerged notes should be exported as one file per body
+    #[test]
+    fn export_unmerged_note() {
+        let dir = temp_dir("unmerged");
+        let note = note![
+            NotesMetadataBuilder::new().with_folder("Work".to_string()).build(),
+            BodyMetadataBuilder::new().with_text("Title\nfirst").build(),
+            BodyMetadataBuilder::new().with_text("Title\nsecond").build()
+        ];
+
+        let summary = export_markdown(&set![note], &dir, false).unwrap();
+
+        assert_eq!(summary.written, 2);
+        assert!(dir.join("Work/Title_1.md").is_file());
+        assert!(dir.join("Work/Title_2.md").is_file());
+    }
+
+    /// Second export should update the files in place and keep renamed ones
+    #[test]
+    fn export_twice() {
+        let dir = temp_dir("twice");
+        let note = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("Title\ntext").build()
+        ];
+
+        export_markdown(&set![note.clone()], &dir, false).unwrap();
+        let summary = export_markdown(&set![note.clone()], &dir, false).unwrap();
+        assert_eq!(summary, ExportSummary { written: 0, unchanged: 1, removed: 0 });
+
+        let mut renamed = note.clone();
+        renamed.body[0].text = Some("New Title\ntext".to_string());
+        let summary = export_markdown(&set![renamed], &dir, false).unwrap();
+
+        assert_eq!(summary, ExportSummary { written: 1, unchanged: 0, removed: 0 });
+        assert!(dir.join("Title.md").is_file());
+        assert!(dir.join("New_Title.md").is_file());
+    }
+
+    /// Pruning removes exported files of renamed notes, other markdown files stay
+    #[test]
+    fn export_prune() {
+        let dir = temp_dir("prune");
+        let note = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("Title\ntext").build()
+        ];
+
+        export_markdown(&set![note.clone()], &dir, true).unwrap();
+        std::fs::write(dir.join("README.md"), "Not a note").unwrap();
+
+        let mut renamed = note.clone();
+        renamed.body[0].text = Some("New Title\ntext".to_string());
+        let summary = export_markdown(&set![renamed], &dir, true).unwrap();
+
+        assert_eq!(summary, ExportSummary { written: 1, unchanged: 0, removed: 1 });
+        assert!(!dir.join("Title.md").exists());
+        assert!(dir.join("README.md").is_file());
+        let content = std::fs::read_to_string(dir.join("New_Title.md")).unwrap();
+        let (front_matter, text) = FrontMatter::parse(&content);
+        assert_eq!(front_matter.unwrap().uuid, Some(note.metadata.uuid));
+        assert_eq!(text, "New Title\ntext");
+    }
+}
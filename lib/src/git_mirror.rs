@@ -0,0 +1,361 @@
+extern crate walkdir;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use self::walkdir::WalkDir;
+use db::DatabaseService;
+use export::{FrontMatter, MARKDOWN_EXTENSION, folder_to_path};
+use error::{Result, UpdateError};
+use hooks::HookEvent;
+use notes::localnote::LocalNote;
+use notes::traits::identifyable_note::IdentifiableNote;
+use sync::SyncResult;
+
+/// Commit message of a sync, every successful action gets its own line like
+/// "UpdateLocally: Shopping list"
+pub(crate) fn sync_message(results: &[SyncResult]) -> String {
+    let lines: Vec<String> = results.iter()
+        .filter(|result| result.result.is_ok())
+        .map(|result| format!("{}: {}", result.action, result.subject))
+        .collect();
+
+    match lines.len() {
+        0 => "Sync".to_string(),
+        1 => lines[0].clone(),
+        count => format!("Sync {} notes\n\n{}", count, lines.join("\n"))
+    }
+}
+
+/// Commit message of a local change of the note
+pub(crate) fn edit_message(event: HookEvent, note: &dyn IdentifiableNote) -> String {
+    let action = match event {
+        HookEvent::NoteCreated => "Create",
+        HookEvent::NoteDeleted => "Delete",
+        _ => "Edit"
+    };
+    format!("{}: {}", action, note.first_subject())
+}
+
+/// Writes all notes into the repository and commits them. The repository gets
+/// initialized if it does not exist yet. Returns false if no note changed
+pub(crate) fn commit_notes(db_connection: &dyn DatabaseService, repository: &Path, message: &str) -> Result<bool> {
+    if !repository.join(".git").exists() {
+        std::fs::create_dir_all(repository)?;
+        git(repository, &["init", "--quiet"])?;
+    }
+
+    write_notes(&db_connection.fetch_all_notes()?, repository)?;
+    git(repository, &["add", "--all"])?;
+    commit(repository, message, &[])
+}
+
+/// Writes only the files of the passed note and commits them, used for local
+/// changes of a single note. Other files of the repository are neither staged nor
+/// committed. A repository that does not exist yet gets all notes
+pub(crate) fn commit_note(db_connection: &dyn DatabaseService, repository: &Path, note: &LocalNote, message: &str) -> Result<bool> {
+    if !repository.join(".git").exists() {
+        return commit_notes(db_connection, repository, message);
+    }
+
+    let (written, removed) = write_note(note, repository)?;
+    let written = relative_paths(repository, &written);
+    let removed = relative_paths(repository, &removed);
+
+    if !written.is_empty() {
+        git(repository, &with_paths(&["add", "--all", "--"], &written))?;
+    }
+    if !removed.is_empty() {
+        // Removed files that never got committed are unknown to git, add would fail for them
+        git(repository, &with_paths(&["rm", "--cached", "--ignore-unmatch", "--quiet", "--"], &removed))?;
+    }
+
+    commit(repository, message, &[written, removed].concat())
+}
+
+/// Commits the staged changes of the passed paths, or all staged changes if no
+/// paths are passed. Returns false if nothing got committed
+fn commit(repository: &Path, message: &str, paths: &[&str]) -> Result<bool> {
+    let staged = git(repository, &with_paths(&["diff", "--cached", "--name-only", "-z", "--"], paths))?;
+    let staged: Vec<&str> = staged.split('\0').filter(|path| !path.is_empty()).collect();
+    if staged.is_empty() {
+        return Ok(false);
+    }
+
+    let arguments = ["commit", "--quiet", "--message", message, "--"];
+    if paths.is_empty() {
+        git(repository, &arguments[..4])?;
+    } else {
+        git(repository, &with_paths(&arguments, &staged))?;
+    }
+    Ok(true)
+}
+
+fn with_paths<'a>(arguments: &[&'a str], paths: &[&'a str]) -> Vec<&'a str> {
+    arguments.iter().chain(paths).cloned().collect()
+}
+
+/// Paths relative to the repository, git runs inside of it
+fn relative_paths<'a>(repository: &Path, paths: &'a [PathBuf]) -> Vec<&'a str> {
+    paths.iter()
+        .filter_map(|path| path.strip_prefix(repository).ok())
+        .filter_map(|path| path.to_str())
+        .collect()
+}
+
+/// Returns the relative path and the content of every file of the note. The
+/// files are named by uuid, so that renaming a note keeps its history. Notes
+/// that need to be merged get one file per body
+fn note_files(note: &LocalNote) -> Vec<(PathBuf, String)> {
+    let folder = folder_to_path(&note.folder());
+    note.body.iter().enumerate().map(|(index, body)| {
+        let name = if note.needs_merge() {
+            format!("{}_{}.{}", note.metadata.uuid, index + 1, MARKDOWN_EXTENSION)
+        } else {
+            format!("{}.{}", note.metadata.uuid, MARKDOWN_EXTENSION)
+        };
+        // The message-id changes with every upload, it would only clutter the history
+        let front_matter = FrontMatter { message_id: None, ..FrontMatter::from_note(note, body) };
+        (folder.join(name), format!("{}{}", front_matter.render(), body.text.as_deref().unwrap_or("")))
+    }).collect()
+}
+
+/// Writes the notes that are not flagged for deletion, files of all other
+/// notes are getting removed
+fn write_notes(notes: &HashSet<LocalNote>, repository: &Path) -> Result<()> {
+    let mut written: HashSet<PathBuf> = HashSet::new();
+    for note in notes.iter().filter(|note| !note.metadata.locally_deleted) {
+        written.extend(write_files(note, repository)?);
+    }
+
+    let known_uuids: HashSet<String> = notes.iter().map(|note| note.metadata.uuid.clone()).collect();
+    let stale = files_of_notes(repository, &known_uuids)?.into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| !written.contains(path));
+    remove_files(stale)
+}
+
+/// Writes the files of a single note, or removes them if it is flagged for deletion.
+/// Files of the note that are not needed anymore, e.g. after a merge, are getting removed.
+/// Returns the paths of the files of the note and the paths of the removed files
+fn write_note(note: &LocalNote, repository: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let written = if note.metadata.locally_deleted {
+        HashSet::new()
+    } else {
+        write_files(note, repository)?
+    };
+
+    let known_uuids: HashSet<String> = vec![note.metadata.uuid.clone()].into_iter().collect();
+    let stale: Vec<PathBuf> = files_of_notes(repository, &known_uuids)?.into_iter()
+        .filter(|(path, uuid)| uuid == &note.metadata.uuid && !written.contains(path))
+        .map(|(path, _)| path)
+        .collect();
+    remove_files(stale.iter().cloned())?;
+    Ok((written.into_iter().collect(), stale))
+}
+
+/// Writes the files of the note that changed, returns the paths of all its files
+fn write_files(note: &LocalNote, repository: &Path) -> Result<HashSet<PathBuf>> {
+    let mut written = HashSet::new();
+    for (relative_path, content) in note_files(note) {
+        let path = repository.join(relative_path);
+        if std::fs::read_to_string(&path).ok().as_ref() != Some(&content) {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, content)?;
+        }
+        written.insert(path);
+    }
+    Ok(written)
+}
+
+fn remove_files<I: Iterator<Item=PathBuf>>(paths: I) -> Result<()> {
+    for path in paths {
+        debug!("Removing {}", path.display());
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Markdown files of the repository that belong to a note together with its uuid.
+/// A file belongs to a note if it is named after one of the known uuids, like
+/// <uuid>.md or <uuid>_2.md, or if it carries the front matter apnotes writes.
+/// Everything else, like a README, is left alone
+fn files_of_notes(repository: &Path, known_uuids: &HashSet<String>) -> Result<Vec<(PathBuf, String)>> {
+    let markdown_files = WalkDir::new(repository).into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().map(|ext| ext == MARKDOWN_EXTENSION).unwrap_or(false));
+
+    let mut files = vec![];
+    for path in markdown_files {
+        let uuid = match uuid_of_file_name(&path).filter(|uuid| known_uuids.contains(uuid)) {
+            Some(uuid) => Some(uuid),
+            None => std::fs::read_to_string(&path).ok()
+                .and_then(|content| FrontMatter::parse(&content).0)
+                .and_then(|front_matter| front_matter.uuid)
+        };
+        if let Some(uuid) = uuid {
+            files.push((path, uuid));
+        }
+    }
+    Ok(files)
+}
+
+/// <uuid>.md -> <uuid>, <uuid>_2.md -> <uuid>
+fn uuid_of_file_name(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    match stem.rsplit_once('_') {
+        Some((uuid, index)) if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()) => Some(uuid.to_string()),
+        _ => Some(stem.to_string())
+    }
+}
+
+fn git(repository: &Path, arguments: &[&str]) -> Result<String> {
+    let output = Command::new("git").arg("-C").arg(repository).args(arguments).output()?;
+    if !output.status.success() {
+        return Err(UpdateError::IoError(
+            format!("git {} failed: {}", arguments[0], String::from_utf8_lossy(&output.stderr).trim())
+        ).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod git_mirror_tests {
+    use super::*;
+    use builder::{NotesMetadataBuilder, BodyMetadataBuilder};
+    use db::SqliteDBConnection;
+
+    fn repository(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("apnotes_git_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        git(&std::env::temp_dir(), &["init", "--quiet", dir.to_str().unwrap()]).unwrap();
+        git(&dir, &["config", "user.name", "apnotes"]).unwrap();
+        git(&dir, &["config", "user.email", "apnotes@localhost"]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sync_messages() {
+        let result = |action: &str, subject: &str, result: Result<()>| SyncResult {
+            action: action.to_string(), subject: subject.to_string(), result
+        };
+
+        assert_eq!(sync_message(&[]), "Sync");
+        assert_eq!(sync_message(&[result("UpdateLocally", "Shopping list", Ok(()))]), "UpdateLocally: Shopping list");
+        assert_eq!(
+            sync_message(&[
+                result("UpdateLocally", "Shopping list", Ok(())),
+                result("AddRemotely", "Failed", Err(UpdateError::SyncError("offline".to_string()).into())),
+                result("DeleteLocally", "Todo", Ok(())),
+            ]),
+            "Sync 2 notes\n\nUpdateLocally: Shopping list\nDeleteLocally: Todo"
+        );
+    }
+
+    /// Files are named by uuid and removed again once the note got deleted
+    #[test]
+    fn commit_changes() {
+        let dir = repository("commit");
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().with_folder("Work".to_string()).build(),
+            BodyMetadataBuilder::new().with_text("Title\ntext").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+
+        assert!(commit_notes(&con, &dir, "Create: Title").unwrap());
+        let path = dir.join("Work").join(format!("{}.md", note.metadata.uuid));
+        assert!(std::fs::read_to_string(&path).unwrap().ends_with("Title\ntext"));
+        assert_eq!(git(&dir, &["log", "--format=%s"]).unwrap().trim(), "Create: Title");
+
+        // Nothing changed, so nothing gets committed
+        assert!(!commit_notes(&con, &dir, "Edit: Title").unwrap());
+
+        let mut deleted = note;
+        deleted.metadata.locally_deleted = true;
+        con.update(&deleted).unwrap();
+        assert!(commit_notes(&con, &dir, "Delete: Title").unwrap());
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Markdown files that do not belong to a note are never removed
+    #[test]
+    fn readme_survives() {
+        let dir = repository("readme");
+        let con = SqliteDBConnection::in_memory().unwrap();
+        std::fs::write(dir.join("README.md"), "# My notes\n").unwrap();
+        std::fs::create_dir_all(dir.join("Work")).unwrap();
+        std::fs::write(dir.join("Work").join("todo_list.md"), "---\ntitle: Todo\n---\ntext").unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().with_folder("Work".to_string()).build(),
+            BodyMetadataBuilder::new().with_text("Title\ntext").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+        assert!(commit_notes(&con, &dir, "Create: Title").unwrap());
+
+        let mut deleted = note;
+        deleted.metadata.locally_deleted = true;
+        con.update(&deleted).unwrap();
+        assert!(commit_note(&con, &dir, &deleted, "Delete: Title").unwrap());
+        assert!(commit_notes(&con, &dir, "Sync").is_ok());
+
+        assert!(!dir.join("Work").join(format!("{}.md", deleted.metadata.uuid)).exists());
+        assert_eq!(std::fs::read_to_string(dir.join("README.md")).unwrap(), "# My notes\n");
+        assert!(dir.join("Work").join("todo_list.md").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A local change only rewrites the files of the changed note
+    #[test]
+    fn commit_single_note() {
+        let dir = repository("single");
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let first = note![
+            NotesMetadataBuilder::new().with_folder("Work".to_string()).build(),
+            BodyMetadataBuilder::new().with_text("First\ntext").build()
+        ];
+        let second = note![
+            NotesMetadataBuilder::new().with_folder("Work".to_string()).build(),
+            BodyMetadataBuilder::new().with_text("Second\ntext").build()
+        ];
+        con.insert_into_db(&first).unwrap();
+        con.insert_into_db(&second).unwrap();
+        assert!(commit_notes(&con, &dir, "Sync").unwrap());
+
+        let first_path = dir.join("Work").join(format!("{}.md", first.metadata.uuid));
+        let second_path = dir.join("Work").join(format!("{}.md", second.metadata.uuid));
+        let second_content = std::fs::read_to_string(&second_path).unwrap();
+
+        let mut edited_first = first.clone();
+        edited_first.body[0].text = Some("First\nedited".to_string());
+        let mut edited_second = second.clone();
+        edited_second.body[0].text = Some("Second\nedited".to_string());
+        con.update(&edited_first).unwrap();
+        con.update(&edited_second).unwrap();
+
+        // Files of the user are neither staged nor committed with the note
+        std::fs::write(dir.join("scratch.txt"), "draft").unwrap();
+
+        assert!(commit_note(&con, &dir, &edited_first, "Edit: First").unwrap());
+        assert!(std::fs::read_to_string(&first_path).unwrap().ends_with("First\nedited"));
+        assert_eq!(std::fs::read_to_string(&second_path).unwrap(), second_content);
+        assert_eq!(git(&dir, &["show", "--name-only", "--format="]).unwrap().trim(), format!("Work/{}.md", first.metadata.uuid));
+        assert_eq!(git(&dir, &["status", "--porcelain"]).unwrap().trim(), "?? scratch.txt");
+
+        // Nothing changed, so nothing gets committed
+        assert!(!commit_note(&con, &dir, &edited_first, "Edit: First").unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
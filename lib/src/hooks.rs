@@ -0,0 +1,258 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use serde_derive::Serialize;
+use config::HookSettings;
+use error::{Result, UpdateError};
+use notes::traits::identifyable_note::IdentifiableNote;
+use sync::SyncResult;
+
+/// Events the [hooks] config section can run a command for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookEvent {
+    PreSync,
+    PostSync,
+    NoteCreated,
+    NoteUpdated,
+    NoteDeleted,
+    MergeNeeded,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::PreSync => "pre-sync",
+            HookEvent::PostSync => "post-sync",
+            HookEvent::NoteCreated => "note-created",
+            HookEvent::NoteUpdated => "note-updated",
+            HookEvent::NoteDeleted => "note-deleted",
+            HookEvent::MergeNeeded => "merge-needed",
+        }
+    }
+
+    /// Pre hooks abort the operation they run before if they fail
+    pub fn is_pre(&self) -> bool {
+        *self == HookEvent::PreSync
+    }
+
+    fn command<'a>(&self, settings: &'a HookSettings) -> Option<&'a String> {
+        match self {
+            HookEvent::PreSync => settings.pre_sync.as_ref(),
+            HookEvent::PostSync => settings.post_sync.as_ref(),
+            HookEvent::NoteCreated => settings.note_created.as_ref(),
+            HookEvent::NoteUpdated => settings.note_updated.as_ref(),
+            HookEvent::NoteDeleted => settings.note_deleted.as_ref(),
+            HookEvent::MergeNeeded => settings.merge_needed.as_ref(),
+        }
+    }
+}
+
+/// The note a hook runs for
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HookNote {
+    pub uuid: String,
+    pub folder: String,
+    pub subject: String,
+}
+
+impl HookNote {
+    pub fn of(note: &dyn IdentifiableNote) -> HookNote {
+        HookNote {
+            uuid: note.uuid(),
+            folder: note.folder(),
+            subject: note.first_subject(),
+        }
+    }
+}
+
+/// Outcome of a single sync action, passed to post-sync hooks
+#[derive(Serialize, Debug)]
+struct HookSyncResult {
+    action: String,
+    subject: String,
+    error: Option<String>,
+}
+
+/// JSON document hooks receive on stdin
+#[derive(Serialize, Debug)]
+struct HookInput {
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<HookNote>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    results: Option<Vec<HookSyncResult>>,
+}
+
+/// Runs the hook of a note event, failures are only logged
+pub(crate) fn note_hook(settings: &HookSettings, event: HookEvent, note: HookNote) {
+    let _ = run(settings, event, HookInput { event: event.as_str(), note: Some(note), results: None });
+}
+
+/// Runs the pre-sync hook, the sync has to be aborted if it fails
+pub(crate) fn pre_sync_hook(settings: &HookSettings) -> Result<()> {
+    run(settings, HookEvent::PreSync, HookInput { event: HookEvent::PreSync.as_str(), note: None, results: None })
+}
+
+/// Runs the post-sync hook with the outcome of every sync action, failures are only logged
+pub(crate) fn post_sync_hook(settings: &HookSettings, results: &[SyncResult]) {
+    let results = results.iter()
+        .map(|result| HookSyncResult {
+            action: result.action.clone(),
+            subject: result.subject.clone(),
+            error: result.result.as_ref().err().map(|e| e.to_string()),
+        })
+        .collect();
+    let event = HookEvent::PostSync;
+    let _ = run(settings, event, HookInput { event: event.as_str(), note: None, results: Some(results) });
+}
+
+/// Runs the hook of the event. Only a failing pre hook returns an error, failures
+/// of all other hooks are logged as warning
+fn run(settings: &HookSettings, event: HookEvent, input: HookInput) -> Result<()> {
+    match execute(settings, event, input) {
+        Err(e) if !event.is_pre() => {
+            warn!("{} hook failed: {}", event.as_str(), e);
+            Ok(())
+        }
+        result => result
+    }
+}
+
+/// Runs the configured command through the shell. The input is written to stdin
+/// as JSON, the event and the note are also passed as APNOTES_* env vars.
+/// The output of the hook is only logged, so that it does not end up in the
+/// output of apnotes
+fn execute(settings: &HookSettings, event: HookEvent, input: HookInput) -> Result<()> {
+    let command = match event.command(settings) {
+        Some(command) => command,
+        None => return Ok(())
+    };
+
+    debug!("Running {} hook: {}", event.as_str(), command);
+
+    #[cfg(target_family = "unix")]
+    let mut process = Command::new("sh");
+    #[cfg(target_family = "unix")]
+    process.arg("-c").arg(command);
+    #[cfg(target_family = "windows")]
+    let mut process = Command::new("cmd");
+    #[cfg(target_family = "windows")]
+    process.arg("/C").arg(command);
+
+    process.env("APNOTES_HOOK", event.as_str());
+    if let Some(note) = &input.note {
+        process.env("APNOTES_UUID", &note.uuid)
+            .env("APNOTES_FOLDER", &note.folder)
+            .env("APNOTES_SUBJECT", &note.subject);
+    }
+
+    let mut child = process
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Written by its own thread while the output is read, otherwise a hook that fills
+    // the stdout pipe before reading stdin would block forever. Hooks that do not
+    // read stdin close it early, that is no error
+    let writer = child.stdin.take().map(|mut stdin| {
+        let json = ::serde_json::to_string(&input).unwrap_or_default();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(json.as_bytes());
+        })
+    });
+
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !stdout.trim().is_empty() {
+        debug!("{} hook: {}", event.as_str(), stdout.trim());
+    }
+
+    if !output.status.success() {
+        return Err(UpdateError::HookFailed(
+            format!("\"{}\" exited with {}: {}", command, output.status, String::from_utf8_lossy(&output.stderr).trim())
+        ).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod hooks_tests {
+    use super::*;
+    use error::ErrorCode;
+
+    fn note() -> HookNote {
+        HookNote { uuid: "1234".to_string(), folder: "Notes".to_string(), subject: "Groceries".to_string() }
+    }
+
+    #[test]
+    fn unset_hook_is_skipped() {
+        assert!(pre_sync_hook(&HookSettings::default()).is_ok());
+    }
+
+    /// Failing post hooks are only logged
+    #[test]
+    fn failing_post_hook() {
+        let settings = HookSettings { post_sync: Some("exit 3".to_string()), ..HookSettings::default() };
+        let input = HookInput { event: HookEvent::PostSync.as_str(), note: None, results: Some(vec![]) };
+        assert!(execute(&settings, HookEvent::PostSync, input).is_err());
+        let input = HookInput { event: HookEvent::PostSync.as_str(), note: None, results: Some(vec![]) };
+        assert!(run(&settings, HookEvent::PostSync, input).is_ok());
+    }
+
+    #[test]
+    fn failing_pre_hook() {
+        let settings = HookSettings { pre_sync: Some("echo offline >&2; exit 3".to_string()), ..HookSettings::default() };
+        let error = pre_sync_hook(&settings).err().unwrap();
+        assert_eq!(error.error_code(), UpdateError::HookFailed(String::new()).error_code());
+        assert!(error.to_string().contains("offline"));
+    }
+
+    /// The note should be readable from stdin and from the env vars
+    #[test]
+    fn note_is_passed() {
+        let dir = std::env::temp_dir().join("apnotes_hooks");
+        std::fs::create_dir_all(&dir).unwrap();
+        let stdin = dir.join("stdin.json");
+        let env = dir.join("env");
+
+        let settings = HookSettings {
+            note_updated: Some(format!("cat > {}; echo \"$APNOTES_HOOK $APNOTES_UUID $APNOTES_SUBJECT\" > {}",
+                                       stdin.display(), env.display())),
+            ..HookSettings::default()
+        };
+        let input = HookInput { event: HookEvent::NoteUpdated.as_str(), note: Some(note()), results: None };
+        run(&settings, HookEvent::NoteUpdated, input).unwrap();
+
+        let json: ::serde_json::Value = ::serde_json::from_str(&std::fs::read_to_string(&stdin).unwrap()).unwrap();
+        assert_eq!(json["event"], "note-updated");
+        assert_eq!(json["note"]["folder"], "Notes");
+        assert_eq!(std::fs::read_to_string(&env).unwrap().trim(), "note-updated 1234 Groceries");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A hook that ignores a large input and prints more than the pipe buffer
+    /// must not block apnotes
+    #[test]
+    fn hook_ignores_stdin() {
+        let settings = HookSettings {
+            post_sync: Some("head -c 200000 /dev/zero; head -c 200000 /dev/zero >&2".to_string()),
+            ..HookSettings::default()
+        };
+        let results = (0..5000)
+            .map(|_| HookSyncResult { action: "Updated".to_string(), subject: "Groceries".repeat(10), error: None })
+            .collect();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let input = HookInput { event: HookEvent::PostSync.as_str(), note: None, results: Some(results) };
+            tx.send(execute(&settings, HookEvent::PostSync, input).is_ok()).unwrap();
+        });
+
+        assert_eq!(rx.recv_timeout(std::time::Duration::from_secs(10)), Ok(true));
+    }
+}
@@ -0,0 +1,275 @@
+extern crate walkdir;
+
+use std::path::{Path, PathBuf};
+use self::walkdir::WalkDir;
+use chrono::{DateTime, Utc, NaiveDateTime};
+use db::DatabaseService;
+use builder::{NotesMetadataBuilder, BodyMetadataBuilder};
+use export::{FrontMatter, path_to_folder, MARKDOWN_EXTENSION};
+use notes::localnote::LocalNote;
+use error::{Result, NoteError, UpdateError};
+use profile::Profile;
+use util;
+
+/// File extensions that are getting picked up by the import
+const IMPORTABLE_EXTENSIONS: [&str; 2] = [MARKDOWN_EXTENSION, "txt"];
+
+/// Settings that control how files are getting imported
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ImportOptions {
+    /// Skips files whose front matter uuid already exists inside the database
+    pub skip_imported: bool,
+    /// Writes the uuid of the created note into the front matter of the imported file
+    pub record_uuid: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ImportOutcome {
+    /// The file was imported into the note with the contained uuid
+    Imported(String),
+    /// The file was already imported into the note with the contained uuid
+    Skipped(String),
+}
+
+impl ImportOutcome {
+    pub fn uuid(&self) -> &str {
+        match self {
+            ImportOutcome::Imported(uuid) | ImportOutcome::Skipped(uuid) => uuid
+        }
+    }
+}
+
+pub struct ImportResult {
+    pub path: PathBuf,
+    pub result: Result<ImportOutcome>
+}
+
+/// Creates new notes from all markdown and text files below dir. The notes
+/// are getting flagged as new, so that they get uploaded with the next sync
+pub(crate) fn import_dir(db_connection: &dyn DatabaseService,
+                         dir: &Path,
+                         options: ImportOptions,
+                         profile: &Profile) -> Result<Vec<ImportResult>> {
+    if !dir.is_dir() {
+        return Err(UpdateError::IoError(format!("{} is not a directory", dir.display())).into());
+    }
+
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.path().to_path_buf())
+        .filter(|path| {
+            path.extension()
+                .map(|ext| IMPORTABLE_EXTENSIONS.iter().any(|importable| ext == *importable))
+                .unwrap_or(false)
+        })
+        .collect();
+
+    files.sort();
+
+    Ok(files.into_iter().map(|path| {
+        let result = import_file(db_connection, dir, &path, options, profile);
+        ImportResult { path, result }
+    }).collect())
+}
+
+pub(crate) fn import_file(db_connection: &dyn DatabaseService,
+                          root: &Path,
+                          path: &Path,
+                          options: ImportOptions,
+                          profile: &Profile) -> Result<ImportOutcome> {
+    let content = std::fs::read_to_string(path)?;
+    let (front_matter, text) = FrontMatter::parse(&content);
+    let front_matter = front_matter.unwrap_or_default();
+
+    let recorded_uuid = match &front_matter.uuid {
+        Some(uuid) if db_connection.fetch_single_note(uuid)?.is_some() => {
+            if options.skip_imported {
+                return Ok(ImportOutcome::Skipped(uuid.clone()));
+            }
+            // Present note must not be replaced, import as separate note
+            None
+        }
+        uuid => uuid.clone()
+    };
+
+    let note = note_from_file(root, path, &front_matter, &text, recorded_uuid, profile)?;
+    db_connection.insert_into_db(&note)?;
+
+    if options.record_uuid && front_matter.uuid.as_ref() != Some(&note.metadata.uuid) {
+        let front_matter = FrontMatter { uuid: Some(note.metadata.uuid.clone()), ..front_matter };
+        std::fs::write(path, format!("{}{}", front_matter.render(), text))?;
+    }
+
+    Ok(ImportOutcome::Imported(note.metadata.uuid))
+}
+
+fn note_from_file(root: &Path,
+                  path: &Path,
+                  front_matter: &FrontMatter,
+                  text: &str,
+                  uuid: Option<String>,
+                  _profile: &Profile) -> Result<LocalNote> {
+    let folder = match &front_matter.folder {
+        Some(folder) => folder.clone(),
+        None => {
+            let relative_dir = path.parent()
+                .and_then(|parent| parent.strip_prefix(root).ok())
+                .unwrap_or_else(|| Path::new(""));
+            folder_of_dir(relative_dir)
+        }
+    };
+
+    if !folder.split('.').all(|part| !part.is_empty() && part.chars().all(char::is_alphanumeric)) {
+        return Err(NoteError::InsertionError(format!("Folder name {} has to be alphanumeric", folder)).into());
+    }
+
+    let date = match &front_matter.date {
+        Some(date) => parse_date(date)?,
+        None => std::fs::metadata(path)?.modified()
+            .map(|modified| DateTime::<Utc>::from(modified).naive_utc())
+            .unwrap_or_else(|_| Utc::now().naive_utc())
+    };
+
+    // Apple Notes uses the first line as subject, fall back to the file name
+    let text = if text.trim().is_empty() {
+        path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+    } else {
+        text.to_string()
+    };
+
+    let mut metadata = NotesMetadataBuilder::new()
+        .is_new(true)
+        .with_date(date);
+
+    if let Some(uuid) = uuid {
+        metadata = metadata.with_uuid(&uuid);
+    }
+
+    let mut metadata = metadata.build();
+    metadata.subfolder = folder;
+
+    #[cfg(not(test))]
+    let body = BodyMetadataBuilder::new(_profile).with_text(&text).build();
+    #[cfg(test)]
+    let body = BodyMetadataBuilder::new().with_text(&text).build();
+
+    Ok(note!(metadata, body))
+}
+
+/// Maps a directory to a folder name, characters that folder names must not
+/// contain are dropped from the directory names. Names without any valid
+/// character are kept, so that the folder gets rejected
+///
+/// My-Notes/Work 2021 -> Notes.MyNotes.Work2021
+fn folder_of_dir(relative_dir: &Path) -> String {
+    let sanitized: PathBuf = relative_dir.components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .map(|name| match name.chars().filter(|c| c.is_alphanumeric()).collect::<String>() {
+            sanitized if sanitized.is_empty() => name,
+            sanitized => sanitized
+        })
+        .collect();
+
+    if sanitized != relative_dir {
+        warn!("Importing {} into folder {}", relative_dir.display(), path_to_folder(&sanitized));
+    }
+
+    path_to_folder(&sanitized)
+}
+
+/// Accepts RFC 2822 dates like the ones inside the mail headers and RFC 3339 dates
+fn parse_date(date: &str) -> Result<NaiveDateTime> {
+    util::parse_date(date)
+        .ok_or_else(|| NoteError::InsertionError(format!("Invalid date {}", date)).into())
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::*;
+    use builder::ProfileBuilder;
+    use db::SqliteDBConnection;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("apnotes_import_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("Work")).unwrap();
+        dir
+    }
+
+    /// Subdirectories should be mapped to folders, front matter should win
+    #[test]
+    fn import_folders() {
+        let dir = temp_dir("folders");
+        std::fs::write(dir.join("Work/plain.txt"), "Plain\ntext").unwrap();
+        std::fs::write(dir.join("Work/front.md"),
+                       "---\nfolder: \"Notes.Private\"\ndate: \"2021-02-06T12:00:00+00:00\"\n---\nFront\ntext").unwrap();
+        std::fs::write(dir.join("ignored.pdf"), "").unwrap();
+
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let results = import_dir(&con, &dir, ImportOptions::default(), &ProfileBuilder::for_tests().build()).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.result.is_ok()));
+
+        let notes = con.fetch_all_notes().unwrap();
+        let front = notes.iter().find(|note| note.body[0].subject() == "Front").unwrap();
+        assert_eq!(front.metadata.subfolder, "Notes.Private");
+        assert_eq!(front.metadata.date.as_ref().map(util::format_date), Some("Sat, 06 Feb 2021 12:00:00 +0000".to_string()));
+        assert_eq!(front.metadata.created_date, front.metadata.date);
+        assert!(front.metadata.new);
+
+        let plain = notes.iter().find(|note| note.body[0].subject() == "Plain").unwrap();
+        assert_eq!(plain.metadata.subfolder, "Notes.Work");
+    }
+
+    /// A second import should skip the files that got their uuid recorded
+    #[test]
+    fn import_twice() {
+        let dir = temp_dir("twice");
+        std::fs::write(dir.join("note.md"), "Note\ntext").unwrap();
+
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let options = ImportOptions { skip_imported: true, record_uuid: true };
+        let first = import_dir(&con, &dir, options, &ProfileBuilder::for_tests().build()).unwrap();
+        let second = import_dir(&con, &dir, options, &ProfileBuilder::for_tests().build()).unwrap();
+
+        let uuid = match first[0].result.as_ref() {
+            Ok(ImportOutcome::Imported(uuid)) => uuid.clone(),
+            _ => panic!("Should be imported")
+        };
+
+        assert_eq!(second[0].result.as_ref().ok(), Some(&ImportOutcome::Skipped(uuid)));
+        assert_eq!(con.fetch_all_notes().unwrap().len(), 1);
+
+        let (_, text) = FrontMatter::parse(&std::fs::read_to_string(dir.join("note.md")).unwrap());
+        assert_eq!(text, "Note\ntext");
+    }
+
+    /// Directory names that are no valid folder names are getting sanitized
+    #[test]
+    fn import_sanitized_folder() {
+        let dir = temp_dir("sanitized");
+        std::fs::create_dir_all(dir.join("My-Notes")).unwrap();
+        std::fs::write(dir.join("My-Notes/note.md"), "Note\ntext").unwrap();
+
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let results = import_dir(&con, &dir, ImportOptions::default(), &ProfileBuilder::for_tests().build()).unwrap();
+        assert!(results[0].result.is_ok());
+        assert_eq!(con.fetch_all_notes().unwrap().iter().next().unwrap().metadata.subfolder, "Notes.MyNotes");
+    }
+
+    #[test]
+    fn invalid_folder() {
+        let dir = temp_dir("invalid");
+        std::fs::write(dir.join("note.md"), "---\nfolder: \"Notes.Not Valid\"\n---\nNote").unwrap();
+
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let results = import_dir(&con, &dir, ImportOptions::default(), &ProfileBuilder::for_tests().build()).unwrap();
+        assert!(results[0].result.is_err());
+    }
+}
@@ -23,6 +23,7 @@ extern crate quoted_printable;
 #[macro_use]
 extern crate diesel_migrations;
 extern crate htmlescape;
+extern crate fs2;
 #[cfg(target_family = "unix")]
 extern crate secret_service;
 #[cfg(target_family = "unix")]
@@ -33,33 +34,127 @@ mod macros;
 mod apple_imap;
 mod converter;
 mod profile;
+pub mod config;
+pub mod setup;
 mod sync;
 #[macro_use]
 mod util;
 pub mod error;
 mod edit;
 pub mod db;
-mod model;
+pub mod model;
 mod schema;
-mod builder;
+pub mod builder;
 pub mod notes;
-mod merge;
+pub mod merge;
+pub mod restore;
+pub mod export;
+pub mod import;
+pub mod mirror;
+pub mod search;
+pub mod lookup;
+pub mod tags;
+pub mod revisions;
+pub mod trash;
+pub mod hooks;
+pub mod lock;
+mod git_mirror;
 
 use error::{Result, NoteError};
 
 use db::{DatabaseService};
 use error::NoteError::{NoteNotFound, InsertionError};
-use util::is_uuid;
 use notes::localnote::LocalNote;
 use error::{UpdateError};
 use std::collections::HashSet;
 use std::collections::hash_map::RandomState;
-use profile::Profile;
-use sync::SyncResult;
+pub use profile::Profile;
+pub use sync::SyncResult;
+pub use apple_imap::{MailService, MailServiceFactory, MailServiceImpl, connect_imap};
+pub use imap::types::Mailbox;
+use restore::{BackupSource, ClashStrategy, RestoreResult};
+use export::{ExportFormat, ExportSummary};
+use std::path::Path;
+use import::{ImportOptions, ImportResult};
+use std::time::Duration;
+use search::{SearchHit, SearchOptions};
+use revisions::{Revision, DiffLine};
+use trash::TrashedNote;
+use chrono::{NaiveDateTime, Utc};
+use hooks::{HookEvent, HookNote};
+use notes::traits::identifyable_note::IdentifiableNote;
+use lock::DatabaseLock;
+use std::path::PathBuf;
 
 pub struct AppleNotes {
     profile: Profile,
     db_connection: Box<dyn DatabaseService + Send>,
+    mail_service_factory: MailServiceFactory,
+    lock_file: Option<PathBuf>,
+}
+
+/// Builds AppleNotes with injected services, everything that is not set
+/// falls back to what the cli uses: the profile of the config file, the
+/// database the profile points to and an imap connection
+#[derive(Default)]
+pub struct AppleNotesBuilder {
+    profile: Option<Profile>,
+    db_connection: Option<Box<dyn DatabaseService + Send>>,
+    mail_service_factory: Option<MailServiceFactory>,
+    lock_file: Option<PathBuf>,
+}
+
+impl AppleNotesBuilder {
+    pub fn new() -> AppleNotesBuilder {
+        AppleNotesBuilder::default()
+    }
+
+    /// Profile that was built with builder::ProfileBuilder instead of the config file
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn with_database(mut self, db_connection: Box<dyn DatabaseService + Send>) -> Self {
+        self.db_connection = Some(db_connection);
+        self
+    }
+
+    /// Called whenever a connection to the mail server is needed, e.g. once per sync
+    pub fn with_mail_service<F>(mut self, factory: F) -> Self
+        where F: for<'a> Fn(&'a Profile) -> Result<Box<dyn MailService + 'a>> + Send + 'static {
+        self.mail_service_factory = Some(Box::new(factory));
+        self
+    }
+
+    /// File that gets locked while the database is written, every process that
+    /// passes the same file waits for the others, see lock::lock_file_of
+    pub fn with_lock_file(mut self, lock_file: PathBuf) -> Self {
+        self.lock_file = Some(lock_file);
+        self
+    }
+
+    pub fn build(self) -> Result<AppleNotes> {
+        let profile = match self.profile {
+            Some(profile) => profile,
+            None => get_user_profile()?
+        };
+        let mut lock_file = self.lock_file;
+        let db_connection = match self.db_connection {
+            Some(db_connection) => db_connection,
+            None => {
+                let db_path = profile.database_path()?;
+                lock_file = lock_file.or_else(|| Some(lock::lock_file_of(&db_path)));
+                Box::new(db::SqliteDBConnection::open(&db_path)?)
+            }
+        };
+        Ok(AppleNotes {
+            profile,
+            db_connection,
+            mail_service_factory: self.mail_service_factory.unwrap_or_else(|| Box::new(connect_imap)),
+            lock_file,
+        })
+    }
 }
 
 impl AppleNotes {
@@ -68,6 +163,8 @@ impl AppleNotes {
         AppleNotes {
             profile,
             db_connection,
+            mail_service_factory: Box::new(connect_imap),
+            lock_file: None,
         }
     }
 
@@ -82,8 +179,30 @@ impl AppleNotes {
     /// of every individual note that got processes
     ///
     /// Tuple content:  (UpdateAction,Subject,Result)
+    ///
+    /// The configured pre-sync hook runs first, the sync is aborted if it fails
     pub fn sync_notes(&self) -> Result<Vec<SyncResult>> {
-        sync::sync_notes(&self.db_connection, &self.profile)
+        hooks::pre_sync_hook(&self.profile.hooks)?;
+        let (results, note_events) = {
+            let _lock = self.lock_database()?;
+            let (results, note_events) = sync::sync_notes(&self.db_connection, &self.profile, &self.mail_service_factory)?;
+            self.commit_to_git(&git_mirror::sync_message(&results));
+
+            match trash::purge_expired(self.db_connection.as_ref(), &self.profile) {
+                Ok(purged) if purged > 0 => info!("Removed {} expired notes from the trash", purged),
+                Ok(_) => {}
+                Err(e) => warn!("Could not purge the trash: {}", e)
+            }
+            (results, note_events)
+        };
+
+        // Like the hooks of local changes they run without the lock, so that they can call the cli
+        for (event, note) in note_events {
+            hooks::note_hook(&self.profile.hooks, event, note);
+        }
+        hooks::post_sync_hook(&self.profile.hooks, &results);
+
+        Ok(results)
     }
 
     /// Opens a text editor with the content of the specified note
@@ -93,10 +212,28 @@ impl AppleNotes {
         edit::edit_note(local_note, new, &self.profile).map_err(|e| e.into())
     }
 
+    /// Replaces the text of the note like an editor edit would, the returned note
+    /// is not saved in the db
+    pub fn replace_text(&self, local_note: &LocalNote, text: &str) -> Result<LocalNote> {
+        edit::replace_text(local_note, text, &self.profile).map_err(|e| e.into())
+    }
+
+    /// Appends the text to the end of the note like an editor edit would, the
+    /// returned note is not saved in the db
+    pub fn append_text(&self, local_note: &LocalNote, text: &str) -> Result<LocalNote> {
+        edit::append_text(local_note, text, &self.profile).map_err(|e| e.into())
+    }
+
     /// Creates a new note, with the specified name inside the specified folder
     pub fn create_new_note(&self, with_subject: &String, folder: &String)
                               -> Result<LocalNote>
     {
+        self.create_note_with_text(with_subject, folder)
+    }
+
+    /// Creates a new note with the passed text inside the specified folder, the
+    /// first line of the text is the subject. The note gets inserted once, with its final text
+    pub fn create_note_with_text(&self, text: &str, folder: &str) -> Result<LocalNote> {
 
         if folder.chars().all(char::is_alphanumeric) == false {
             return Err(InsertionError("Folder name has to be alphanumeric".to_string()).into())
@@ -104,55 +241,36 @@ impl AppleNotes {
 
         #[cfg(test)]
             let note = note!(
-             builder::NotesMetadataBuilder::new().with_folder(folder.clone()).is_new(true).build(),
-             builder::BodyMetadataBuilder::new().with_text(&with_subject.clone()).build()
+             builder::NotesMetadataBuilder::new().with_folder(folder.to_string()).is_new(true).build(),
+             builder::BodyMetadataBuilder::new().with_text(text).build()
            );
         #[cfg(not(test))]
             let note = note!(
-             builder::NotesMetadataBuilder::new().with_folder(folder.clone()).is_new(true).build(),
-             builder::BodyMetadataBuilder::new(&self.profile).with_text(&with_subject.clone()).build()
+             builder::NotesMetadataBuilder::new().with_folder(folder.to_string()).is_new(true).build(),
+             builder::BodyMetadataBuilder::new(&self.profile).with_text(text).build()
            );
 
-        self.db_connection.insert_into_db(&note)
-            .and_then(|_| Ok(note))
-            .map_err(|e| e.into())
+        self.change_note(HookEvent::NoteCreated, || {
+            self.db_connection.insert_into_db(&note)?;
+            Ok(note)
+        })
     }
 
     /// Queries the database and tries to find a note with the provided search string
-    /// Auto-Detects if the user provides the title or a uuid.
+    /// Auto-Detects if the user provides a uuid, a uuid prefix, a folder/title path or a title.
     ///
-    /// If multiple notes with the same title exist it returns the first one
-    /// avaiable
+    /// If multiple notes match it fails with NoteError::AmbiguousNote, that lists
+    /// all matching notes
     pub fn find_note(&self, uuid_or_name: &String)
                         -> Result<LocalNote> {
-        match is_uuid(&uuid_or_name) {
-            true => {
-                match self.db_connection.fetch_single_note(&uuid_or_name) {
-                    Ok(Some(note)) => Ok(note),
-                    Ok(None) => {
-                        error!("Note does not exist");
-                        Err(NoteNotFound.into())
-                    }
-                    Err(e) => {
-                        error!("Error occurred: {}", e.to_string());
-                        Err(e.into())
-                    }
-                }
-            }
-            false => {
-                match self.db_connection.fetch_single_note_with_name(&uuid_or_name) {
-                    Ok(Some(note)) => Ok(note),
-                    Ok(None) => {
-                        error!("Note does not exist");
-                        Err(NoteNotFound.into())
-                    }
-                    Err(e) => {
-                        error!("Error occurred: {}", e.to_string());
-                        Err(e.into())
-                    }
-                }
-            }
-        }
+        lookup::find_note(self.db_connection.as_ref(), uuid_or_name)
+    }
+
+    /// Like find_note, but titles have to match exactly or as prefix. Commands that
+    /// change the note use it, so that a fuzzy match never edits or deletes the wrong note
+    pub fn find_note_strictly(&self, uuid_or_name: &String)
+                        -> Result<LocalNote> {
+        lookup::find_note_strictly(self.db_connection.as_ref(), uuid_or_name)
     }
 
     /// Merges notes that have > 1 bodies (right now only 2 bodies supported)
@@ -161,32 +279,64 @@ impl AppleNotes {
     pub fn merge(&self, uuid_or_name: &String)
                  -> Result<()> {
         self.find_note(&uuid_or_name)
-            .and_then(|note| {
+            .and_then(|note| self.merged_note(&note))
+            .and_then(|note| edit::edit_note(&note, false, &self.profile).map_err(|e| e.into()))
+            .and_then(|note| self.update_note(&note))
+    }
 
-                //TODO currently only supports merging for 2 notes
-                if note.needs_merge() == false || note.body.len() > 2 {
-                    return Err(UpdateError::SyncError("Note not mergeable, right now only notes with 2 bodies are mergeable".to_string()).into());
-                }
+    /// Saves the text as the merged body of the note, without opening the editor.
+    /// Used by interfaces that resolve the conflicts themselves
+    pub fn resolve_merge(&self, uuid_or_name: &String, text: &str) -> Result<()> {
+        self.find_note(uuid_or_name)
+            .and_then(|note| self.resolved_note(&note, text))
+            .and_then(|note| self.update_note(&note))
+    }
 
-                let diff = merge::merge_two(&note.body[0].text.as_ref().unwrap(), &note.body[1].text.as_ref().unwrap());
+    /// Replaces both bodies with one that contains the resolved text. The body refers
+    /// to the message-ids of both versions, so that the next sync replaces both remotely
+    fn resolved_note(&self, note: &LocalNote, text: &str) -> Result<LocalNote> {
+        if !note.mergeable() {
+            return Err(not_mergeable());
+        }
 
-                #[cfg(not(test))]
-                    let note = note![
-                note.metadata.clone(),
-                builder::BodyMetadataBuilder::new(&self.profile).with_text(&diff).with_message_id(&format!("{},{}",&note.body[0].message_id, &note.body[1].message_id)).build()
-                ];
+        let mut metadata = note.metadata.clone();
+        metadata.date = Some(Utc::now().naive_utc());
 
-                #[cfg(test)]
-                    let note = note![
-                note.metadata.clone(),
-                builder::BodyMetadataBuilder::new().with_text(&diff).with_message_id(&format!("{},{}",&note.body[0].message_id, &note.body[1].message_id)).build()
-                ];
+        #[cfg(not(test))]
+            let mut body = builder::BodyMetadataBuilder::new(&self.profile).with_text(text);
+        #[cfg(test)]
+            let mut body = builder::BodyMetadataBuilder::new().with_text(text);
 
+        if !note.metadata.new {
+            body = body.with_old_remote_message_id(&format!("{},{}", &note.body[0].message_id, &note.body[1].message_id));
+        }
 
-                Ok(note)
-            })
-            .and_then(|note| edit::edit_note(&note, false, &self.profile).map_err(|e| e.into()))
-            .and_then(|note| self.db_connection.update(&note).map_err(|e| e.into()))
+        Ok(note![metadata, body.build()])
+    }
+
+    /// Replaces both bodies with one that contains the merged text, the body
+    /// refers to the message-ids of both versions
+    fn merged_note(&self, note: &LocalNote) -> Result<LocalNote> {
+        let diff = merged_text(note)?;
+
+        #[cfg(not(test))]
+            let note = note![
+        note.metadata.clone(),
+        builder::BodyMetadataBuilder::new(&self.profile).with_text(&diff).with_message_id(&format!("{},{}",&note.body[0].message_id, &note.body[1].message_id)).build()
+        ];
+
+        #[cfg(test)]
+            let note = note![
+        note.metadata.clone(),
+        builder::BodyMetadataBuilder::new().with_text(&diff).with_message_id(&format!("{},{}",&note.body[0].message_id, &note.body[1].message_id)).build()
+        ];
+
+        Ok(note)
+    }
+
+    /// Returns the text merge would open the editor with, without changing the note
+    pub fn merge_preview(&self, uuid_or_name: &String) -> Result<String> {
+        self.find_note(uuid_or_name).and_then(|note| merged_text(&note))
     }
 
     pub fn print(&self, uuid_or_name: &String) -> Result<()> {
@@ -202,7 +352,8 @@ impl AppleNotes {
             })
     }
 
-    /// Unflags a note, so that in will not get deleted within the next sync
+    /// Unflags a note, so that in will not get deleted within the next sync.
+    /// Hooks see the undelete as update of the note
     pub fn undelete_note(&self, uuid_or_name: &String)
                             -> Result<()> {
         self.find_note(&uuid_or_name)
@@ -210,29 +361,483 @@ impl AppleNotes {
                 note.metadata.locally_deleted = false;
                 note
             })
-            .and_then(|note| self.db_connection.update(&note).map_err(|e| e.into()))
+            .and_then(|note| self.change_note_with_message(
+                HookEvent::NoteUpdated,
+                |note| format!("Undelete: {}", note.first_subject()),
+                || {
+                    self.db_connection.update(&note)?;
+                    Ok(note)
+                }))
+            .map(|_| ())
     }
 
     /// Flags a note for deletion, flagged notes are getting deleted remotely with the next synchronization
     pub fn delete_note(&self, uuid_or_name: &String) -> Result<()> {
-        self.find_note(&uuid_or_name)
+        self.find_note_strictly(&uuid_or_name)
             .map(|mut note| {
                 note.metadata.locally_deleted = true;
                 note
             })
-            .and_then(|note| self.db_connection.update(&note).map_err(|e| e.into()))
+            .and_then(|note| self.change_note(HookEvent::NoteDeleted, || {
+                self.db_connection.update(&note)?;
+                Ok(note)
+            }))
+            .map(|_| ())
     }
 
     pub fn get_notes(&self) -> Result<HashSet<LocalNote, RandomState>> {
         self.db_connection.fetch_all_notes().map_err(|e| e.into())
     }
 
+    /// Returns every hashtag that is used inside the notes together with the
+    /// number of notes that contain it
+    pub fn get_tags(&self) -> Result<Vec<(String, i64)>> {
+        self.db_connection.fetch_tags().map_err(|e| e.into())
+    }
+
+    /// Returns all notes that contain the passed hashtag, the leading # is optional
+    pub fn get_notes_with_tag(&self, tag: &str) -> Result<HashSet<LocalNote, RandomState>> {
+        let uuids = self.db_connection.fetch_uuids_with_tag(&tags::normalize_tag(tag))?;
+        self.get_notes()
+            .map(|notes| notes.into_iter().filter(|note| uuids.contains(&note.metadata.uuid)).collect())
+    }
+
+    /// Returns all notes that got modified at or after the passed UTC date
+    pub fn get_notes_modified_since(&self, since: &NaiveDateTime) -> Result<HashSet<LocalNote, RandomState>> {
+        let uuids = self.db_connection.fetch_uuids_modified_since(since)?;
+        self.get_notes()
+            .map(|notes| notes.into_iter().filter(|note| uuids.contains(&note.metadata.uuid)).collect())
+    }
+
+    /// Returns all revisions of the note, newest first. A revision is a snapshot of
+    /// the note content before it got replaced by an edit, a sync or a merge
+    pub fn history(&self, uuid_or_name: &String) -> Result<Vec<Revision>> {
+        self.find_note(uuid_or_name)
+            .and_then(|note| self.db_connection.fetch_revisions(&note.metadata.uuid).map_err(|e| e.into()))
+    }
+
+    /// Returns the changes between the passed revision and the current content of the note
+    pub fn diff(&self, uuid_or_name: &String, revision: i64) -> Result<Vec<DiffLine>> {
+        let note = self.find_note(uuid_or_name)?;
+        if note.needs_merge() {
+            return Err(NoteError::NeedsMerge.into());
+        }
+
+        let revision = revisions::revision_of_note(self.db_connection.as_ref(), &note, revision)?;
+        Ok(revisions::diff_lines(
+            revision.text.as_deref().unwrap_or(""),
+            note.body[0].text.as_deref().unwrap_or("")
+        ))
+    }
+
+    /// Restores the content of the passed revision, the note gets uploaded with the next sync
+    pub fn revert(&self, uuid_or_name: &String, revision: i64) -> Result<LocalNote> {
+        self.find_note_strictly(uuid_or_name)
+            .and_then(|note| self.change_note(HookEvent::NoteUpdated, || {
+                revisions::revert(self.db_connection.as_ref(), &note, revision, &self.profile)
+            }))
+    }
+
+    /// Returns all notes that got deleted locally or on another device and that
+    /// did not exceed the retention period yet
+    pub fn get_trash(&self) -> Result<Vec<TrashedNote>> {
+        self.db_connection.fetch_trash().map_err(|e| e.into())
+    }
+
+    /// Restores the passed notes from the trash, they get uploaded with the next sync.
+    /// Hooks see every restored note as a created note
+    pub fn restore_from_trash(&self, uuids: &[String]) -> Result<Vec<RestoreResult>> {
+        let mut trashed = {
+            let _lock = self.lock_database()?;
+            trash::trashed_notes(self.db_connection.as_ref(), uuids)?
+        };
+        trashed.sort_by_key(|note| note.metadata.subfolder.clone());
+
+        Ok(trashed.into_iter().map(|note| {
+            let uuid = note.metadata.uuid.clone();
+            let subject = note.first_subject();
+            let result = self.change_note_with_message(
+                HookEvent::NoteCreated,
+                |note| format!("Restore: {}", note.first_subject()),
+                || trash::restore_from_trash(self.db_connection.as_ref(), &uuid, &self.profile))
+                .map(|_| ());
+            RestoreResult { uuid, subject, result }
+        }).collect())
+    }
+
+    /// Removes notes from the trash for good. Only notes that exceeded the retention
+    /// period are removed, unless all is set
+    pub fn purge_trash(&self, all: bool) -> Result<usize> {
+        let _lock = self.lock_database()?;
+        if all {
+            self.db_connection.purge_trash(None).map_err(|e| e.into())
+        } else {
+            trash::purge_expired(self.db_connection.as_ref(), &self.profile)
+        }
+    }
+
+    /// Saves the note, it gets uploaded with the next sync
     pub fn update_note(&self, note: &LocalNote) -> Result<()> {
-        self.db_connection.update(note).map_err(|e| e.into())
+        self.change_note(HookEvent::NoteUpdated, || {
+            self.db_connection.update(note)?;
+            Ok(note.clone())
+        }).map(|_| ())
+    }
+
+    /// Runs the change while the database is locked and commits it to the git mirror.
+    /// The hook of the event runs after the lock got released, so that hooks can call the cli
+    fn change_note<F>(&self, event: HookEvent, change: F) -> Result<LocalNote>
+        where F: FnOnce() -> Result<LocalNote> {
+        self.change_note_with_message(event, |note| git_mirror::edit_message(event, note), change)
+    }
+
+    /// Like change_note, but with its own commit message
+    fn change_note_with_message<M, F>(&self, event: HookEvent, message: M, change: F) -> Result<LocalNote>
+        where M: FnOnce(&LocalNote) -> String, F: FnOnce() -> Result<LocalNote> {
+        let note = {
+            let _lock = self.lock_database()?;
+            let note = change()?;
+            self.commit_note_to_git(&note, &message(&note));
+            note
+        };
+        hooks::note_hook(&self.profile.hooks, event, HookNote::of(&note));
+        Ok(note)
+    }
+
+    /// Waits until no other process writes the database. Without a lock file,
+    /// e.g. when the database got injected, nothing is locked
+    fn lock_database(&self) -> Result<Option<DatabaseLock>> {
+        match &self.lock_file {
+            Some(lock_file) => DatabaseLock::acquire(lock_file).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Writes all notes to the configured git repository, failures are only logged
+    /// so that the mirror never blocks syncs or edits
+    fn commit_to_git(&self, message: &str) {
+        if let Some(repository) = &self.profile.git.repository {
+            if let Err(e) = git_mirror::commit_notes(self.db_connection.as_ref(), repository, message) {
+                warn!("Could not commit the notes to {}: {}", repository.display(), e);
+            }
+        }
+    }
+
+    /// Writes only the changed note to the configured git repository, failures are only logged
+    fn commit_note_to_git(&self, note: &LocalNote, message: &str) {
+        if let Some(repository) = &self.profile.git.repository {
+            if let Err(e) = git_mirror::commit_note(self.db_connection.as_ref(), repository, note, message) {
+                warn!("Could not commit {} to {}: {}", note.first_subject(), repository.display(), e);
+            }
+        }
+    }
+
+    /// Exports all notes into the passed directory, one file per note, grouped
+    /// by folder. Exporting into the same directory again updates the files in place,
+    /// with prune previously exported files that no note maps to anymore are removed
+    pub fn export(&self, format: ExportFormat, dir: &Path, prune: bool) -> Result<ExportSummary> {
+        let notes = self.get_notes()?;
+        match format {
+            ExportFormat::Markdown => export::export_markdown(&notes, dir, prune)
+        }
+    }
+
+    /// Creates new notes from all markdown and text files inside the passed directory,
+    /// subdirectories are getting mapped to folders. The notes get uploaded with the next sync
+    pub fn import(&self, dir: &Path, options: ImportOptions) -> Result<Vec<ImportResult>> {
+        let _lock = self.lock_database()?;
+        import::import_dir(self.db_connection.as_ref(), dir, options, &self.profile)
+    }
+
+    /// Returns all notes that are stored inside the provided backup
+    pub fn list_backup(&self, source: &BackupSource) -> Result<Vec<LocalNote>> {
+        match source {
+            BackupSource::Archive(path) => restore::fetch_archive(path),
+            BackupSource::Imap => {
+                (self.mail_service_factory)(&self.profile)
+                    .and_then(|mut imap_service| {
+                        restore::fetch_imap_backup(imap_service.as_mut())
+                            .and_then(|notes| imap_service.logout().map(|_| notes))
+                    })
+            }
+        }
+    }
+
+    /// Restores notes from the provided backup into the local database.
+    /// If no uuids are provided every note inside the backup gets restored.
+    ///
+    /// Restored notes are getting flagged as new or as locally edited, so that
+    /// they get uploaded to the notes folders with the next sync
+    pub fn restore(&self, source: &BackupSource, uuids: &Vec<String>, strategy: ClashStrategy)
+                   -> Result<Vec<RestoreResult>> {
+        let notes: Vec<LocalNote> = self.list_backup(source)?
+            .into_iter()
+            .filter(|note| uuids.is_empty() || uuids.contains(&note.metadata.uuid))
+            .collect();
+
+        if let Some(missing) = uuids.iter().find(|uuid| notes.iter().all(|note| &note.metadata.uuid != *uuid)) {
+            error!("Note {} is not part of the backup", missing);
+            return Err(NoteNotFound.into());
+        }
+
+        let _lock = self.lock_database()?;
+        Ok(restore::restore_notes(self.db_connection.as_ref(), notes, strategy, &self.profile))
+    }
+
+    /// Mirrors all notes as markdown files into the passed directory and blocks while
+    /// watching it. Edited files are getting saved as local edits, new files without
+    /// front matter are getting imported. Changes inside the database are written back
+    /// to the files every interval.
+    ///
+    /// The database is locked while a file change gets saved and while the files
+    /// get written back, so that syncs or edits of other processes run in between
+    pub fn mirror(&self, dir: &Path, interval: Duration) -> Result<()> {
+        mirror::mirror(self.db_connection.as_ref(), dir, interval, &self.profile, &|| self.lock_database())
+    }
+
+    /// Searches the text of all notes, the best matching notes come first.
+    ///
+    /// All terms of the query have to match, terms in double quotes are matched
+    /// as phrase and terms ending with an asterisk are matched as prefix
+    pub fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchHit>> {
+        search::search(self.db_connection.as_ref(), query, options)
+    }
+
+}
+
+/// Combines both bodies of an unmerged note, lines that only one body contains
+/// are prefixed with < or >
+fn merged_text(note: &LocalNote) -> Result<String> {
+    //TODO currently only supports merging for 2 notes
+    if !note.mergeable() {
+        return Err(not_mergeable());
     }
 
+    Ok(merge::merge_two(note.body[0].text.as_ref().unwrap(), note.body[1].text.as_ref().unwrap()))
+}
+
+fn not_mergeable() -> Box<dyn error::ErrorCode> {
+    UpdateError::SyncError("Note not mergeable, right now only notes with 2 bodies are mergeable".to_string()).into()
 }
 
 pub fn get_user_profile() -> Result<Profile> {
     profile::load_profile()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod apple_notes_tests {
+    use super::*;
+    use builder::{ProfileBuilder, NotesMetadataBuilder, BodyMetadataBuilder};
+    use model::NotesMetadata;
+    use notes::remote_note_header_collection::RemoteNoteHeaderCollection;
+    use notes::remote_note_metadata::RemoteNoteMetaData;
+    use error::ErrorCode;
+    use std::sync::{Arc, Mutex};
+
+    /// Mail server without notes that remembers the uuids of uploaded notes
+    struct UploadRecorder {
+        uploaded: Arc<Mutex<Vec<String>>>
+    }
+
+    impl MailService for UploadRecorder {
+        fn fetch_headers(&mut self) -> Result<RemoteNoteHeaderCollection> { Ok(vec![]) }
+        fn fetch_backup_headers(&mut self) -> Result<RemoteNoteHeaderCollection> { Ok(vec![]) }
+        fn create_mailbox(&mut self, _note: &NotesMetadata) -> Result<()> { Ok(()) }
+        fn fetch_header(&mut self, _subfolder: &str, _uid: i64) -> Result<RemoteNoteMetaData> { Err(NoteNotFound.into()) }
+        fn fetch_note_content(&mut self, _subfolder: &str, _uid: i64) -> Result<String> { Err(NoteNotFound.into()) }
+        fn update_message(&mut self, localnote: &LocalNote) -> Result<u32> {
+            self.uploaded.lock().unwrap().push(localnote.metadata.uuid.clone());
+            Ok(1)
+        }
+        fn delete_message(&mut self, _localnote: &LocalNote) -> Result<()> { Ok(()) }
+        fn select(&mut self, _folder: &str) -> Result<Mailbox> { Ok(Mailbox::default()) }
+        fn logout(&mut self) -> Result<()> { Ok(()) }
+    }
+
+    /// A profile built in code together with injected services should be
+    /// enough to sync, without config file or imap server
+    #[test]
+    fn sync_with_injected_services() {
+        let db_connection = db::SqliteDBConnection::in_memory().unwrap();
+        db_connection.insert_into_db(&note![
+            NotesMetadataBuilder::new().with_uuid("1").is_new(true).build(),
+            BodyMetadataBuilder::new().with_text("Injected").build()
+        ]).unwrap();
+
+        let uploaded = Arc::new(Mutex::new(vec![]));
+        let recorder = uploaded.clone();
+        let app = AppleNotesBuilder::new()
+            .with_profile(ProfileBuilder::new("test", "test@test.de", "imap.test.de").with_password("secret").build())
+            .with_database(Box::new(db_connection))
+            .with_mail_service(move |_| Ok(Box::new(UploadRecorder { uploaded: recorder.clone() })))
+            .build()
+            .unwrap();
+
+        let results = app.sync_notes().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].result.is_ok());
+        assert_eq!(*uploaded.lock().unwrap(), vec!["1".to_string()]);
+        assert!(!app.find_note(&"Injected".to_string()).unwrap().metadata.new);
+    }
+
+    /// Note hooks of a sync run after the database lock got released, a hook that
+    /// takes the lock itself, like a writing cli command, must not block
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn sync_hooks_run_unlocked() {
+        let dir = std::env::temp_dir().join("apnotes_sync_hook_lock");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let lock_file = dir.join("notes_db.lock");
+        let marker = dir.join("locked");
+
+        // Not new and missing remotely, so the sync deletes it locally
+        let db_connection = db::SqliteDBConnection::in_memory().unwrap();
+        db_connection.insert_into_db(&note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().with_text("Removed remotely").build()
+        ]).unwrap();
+
+        // flock(1) takes the same lock as DatabaseLock, it gives up after 5 seconds
+        let hooks = config::HookSettings {
+            note_deleted: Some(format!("flock -w 5 {} touch {}", lock_file.display(), marker.display())),
+            ..config::HookSettings::default()
+        };
+        let app = AppleNotesBuilder::new()
+            .with_profile(ProfileBuilder::for_tests().with_hook_settings(hooks).build())
+            .with_database(Box::new(db_connection))
+            .with_lock_file(lock_file)
+            .with_mail_service(|_| Ok(Box::new(UploadRecorder { uploaded: Arc::new(Mutex::new(vec![])) })))
+            .build()
+            .unwrap();
+
+        let results = app.sync_notes().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].result.is_ok());
+        assert!(marker.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A failing pre-sync hook must abort before the mail server gets contacted
+    #[test]
+    fn failing_pre_sync_hook() {
+        let hooks = config::HookSettings { pre_sync: Some("exit 1".to_string()), ..config::HookSettings::default() };
+        let app = AppleNotesBuilder::new()
+            .with_profile(ProfileBuilder::new("test", "test@test.de", "imap.test.de").with_hook_settings(hooks).build())
+            .with_database(Box::new(db::SqliteDBConnection::in_memory().unwrap()))
+            .with_mail_service(|_| panic!("connected despite the failing hook"))
+            .build()
+            .unwrap();
+
+        let error = app.sync_notes().err().unwrap();
+        assert_eq!(error.error_code(), UpdateError::HookFailed(String::new()).error_code());
+    }
+
+    /// Creating a note with text is a single insert that only runs the created hook
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn create_note_with_text() {
+        let log = std::env::temp_dir().join("apnotes_create_hooks.log");
+        let _ = std::fs::remove_file(&log);
+        let hooks = config::HookSettings {
+            note_created: Some(format!("echo created >> {}", log.display())),
+            note_updated: Some(format!("echo updated >> {}", log.display())),
+            ..config::HookSettings::default()
+        };
+        let app = AppleNotesBuilder::new()
+            .with_profile(ProfileBuilder::for_tests().with_hook_settings(hooks).build())
+            .with_database(Box::new(db::SqliteDBConnection::in_memory().unwrap()))
+            .with_mail_service(|_| panic!("creating must not connect"))
+            .build()
+            .unwrap();
+
+        assert!(app.create_note_with_text("Title\ntext", "Not Valid").is_err());
+        let note = app.create_note_with_text("Title\ntext", "Work").unwrap();
+
+        let stored = app.find_note(&note.metadata.uuid).unwrap();
+        assert_eq!(stored.body[0].text.as_deref(), Some("Title\ntext"));
+        assert!(stored.metadata.new);
+        assert_eq!(app.get_notes().unwrap().len(), 1);
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "created\n");
+    }
+
+    /// A note restored from the trash is committed to the git mirror and runs the created hook
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn restore_from_trash() {
+        let dir = std::env::temp_dir().join("apnotes_restore_trash");
+        let _ = std::fs::remove_dir_all(&dir);
+        let repository = dir.join("git");
+        let log = dir.join("hooks.log");
+        std::fs::create_dir_all(&repository).unwrap();
+        for arguments in &[vec!["init", "--quiet"], vec!["config", "user.name", "apnotes"], vec!["config", "user.email", "apnotes@localhost"]] {
+            assert!(std::process::Command::new("git").arg("-C").arg(&repository).args(arguments).status().unwrap().success());
+        }
+
+        let db_connection = db::SqliteDBConnection::in_memory().unwrap();
+        let note = note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().with_text("Trashed\ntext").build()
+        ];
+        db_connection.insert_into_db(&note).unwrap();
+        db_connection.move_to_trash(&note, trash::TrashOrigin::Local).unwrap();
+
+        let hooks = config::HookSettings {
+            note_created: Some(format!("echo created >> {}", log.display())),
+            ..config::HookSettings::default()
+        };
+        let app = AppleNotesBuilder::new()
+            .with_profile(ProfileBuilder::for_tests()
+                .with_hook_settings(hooks)
+                .with_git_settings(config::GitSettings { repository: Some(repository.clone()) })
+                .build())
+            .with_database(Box::new(db_connection))
+            .with_mail_service(|_| panic!("restoring must not connect"))
+            .build()
+            .unwrap();
+
+        assert!(app.restore_from_trash(&["2".to_string()]).is_err());
+        let results = app.restore_from_trash(&["1".to_string()]).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].result.is_ok());
+        assert!(app.get_trash().unwrap().is_empty());
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "created\n");
+
+        let output = std::process::Command::new("git").arg("-C").arg(&repository)
+            .args(&["log", "--format=%s"]).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "Restore: Trashed");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The resolved text replaces both versions and still refers to both message-ids
+    #[test]
+    fn resolve_merge() {
+        let db_connection = db::SqliteDBConnection::in_memory().unwrap();
+        let unmerged = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_message_id("<a@test.de>").with_text("Title\nfirst").build(),
+            BodyMetadataBuilder::new().with_message_id("<b@test.de>").with_text("Title\nsecond").build()
+        ];
+        db_connection.insert_into_db(&unmerged).unwrap();
+
+        let app = AppleNotesBuilder::new()
+            .with_profile(ProfileBuilder::new("test", "test@test.de", "imap.test.de").build())
+            .with_database(Box::new(db_connection))
+            .with_mail_service(|_| panic!("merging must not connect"))
+            .build()
+            .unwrap();
+
+        app.resolve_merge(&unmerged.metadata.uuid, "Title\nfirst\nsecond").unwrap();
+
+        let note = app.find_note(&unmerged.metadata.uuid).unwrap();
+        assert!(!note.needs_merge());
+        assert_eq!(note.body[0].text.as_deref(), Some("Title\nfirst\nsecond"));
+        assert_eq!(note.body[0].old_remote_message_id.as_deref(), Some("<a@test.de>,<b@test.de>"));
+        assert!(!note.body[0].message_id.contains("<a@test.de>"));
+
+        // Only a single version is left
+        assert!(!note.mergeable());
+        assert!(app.resolve_merge(&unmerged.metadata.uuid, "Title\nagain").is_err());
+    }
+}
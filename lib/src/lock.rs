@@ -0,0 +1,65 @@
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use fs2::FileExt;
+use error::Result;
+
+/// Exclusive lock on a notes database that is shared between processes, so
+/// that the cli, the tui and the server never write the database at the same time.
+/// The lock gets released once the value is dropped
+pub struct DatabaseLock {
+    file: File
+}
+
+impl DatabaseLock {
+    /// Blocks until no other process holds the lock of the database
+    pub fn acquire(lock_file: &Path) -> Result<DatabaseLock> {
+        let file = OpenOptions::new().write(true).create(true).open(lock_file)?;
+        file.lock_exclusive()?;
+        Ok(DatabaseLock { file })
+    }
+}
+
+impl Drop for DatabaseLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.unlock() {
+            warn!("Could not release the database lock: {}", e);
+        }
+    }
+}
+
+/// Location of the lock file of a database, next to the database itself
+///
+/// ~/.local/share/apnotes/notes_db -> ~/.local/share/apnotes/notes_db.lock
+pub fn lock_file_of(db_path: &Path) -> PathBuf {
+    let mut name = db_path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    name.push(".lock");
+    db_path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod lock_tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    /// A second lock has to wait until the first one got released
+    #[test]
+    fn exclusive() {
+        let lock_file = lock_file_of(&std::env::temp_dir().join("apnotes_lock_db"));
+        assert!(lock_file.to_string_lossy().ends_with("apnotes_lock_db.lock"));
+
+        let first = DatabaseLock::acquire(&lock_file).unwrap();
+
+        let (tx, rx) = channel();
+        let waiting_file = lock_file.clone();
+        let waiting = std::thread::spawn(move || {
+            let _second = DatabaseLock::acquire(&waiting_file).unwrap();
+            tx.send(()).unwrap();
+        });
+
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(first);
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+        waiting.join().unwrap();
+    }
+}
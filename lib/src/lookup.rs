@@ -0,0 +1,218 @@
+use db::DatabaseService;
+use error::{Result, NoteError};
+use notes::localnote::LocalNote;
+use notes::traits::identifyable_note::IdentifiableNote;
+use search::full_folder_name;
+use util::is_uuid;
+
+/// Uuid prefixes need at least this many characters, so that short
+/// subjects are not mistaken for a prefix
+const MIN_UUID_PREFIX_LEN: usize = 4;
+
+/// Index of the first title comparison of match_title that is neither exact nor a prefix
+const FIRST_LOOSE_STEP: usize = 3;
+
+/// A note that matched an ambiguous note reference
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub uuid: String,
+    pub folder: String,
+    pub subject: String,
+}
+
+impl Candidate {
+    fn from_note(note: &LocalNote) -> Candidate {
+        Candidate {
+            uuid: note.metadata.uuid.clone(),
+            folder: note.metadata.folder(),
+            subject: note.first_subject()
+        }
+    }
+}
+
+/// Finds the single note the reference points to. A reference is a uuid,
+/// a unique uuid prefix, a folder/title path or a title.
+///
+/// Titles are compared exactly first, then case insensitive, as prefix, as
+/// substring and at last fuzzy. The first of these steps that matches any note
+/// decides, if it matches more than one note the lookup fails with
+/// NoteError::AmbiguousNote instead of picking one
+pub(crate) fn find_note(db_connection: &dyn DatabaseService, reference: &str) -> Result<LocalNote> {
+    lookup(db_connection, reference, true)
+}
+
+/// Like find_note, but a title has to match exactly or as prefix. Used by commands
+/// that change the note, a substring or fuzzy match fails with NoteError::AmbiguousNote
+/// even if it matches only one note
+pub(crate) fn find_note_strictly(db_connection: &dyn DatabaseService, reference: &str) -> Result<LocalNote> {
+    lookup(db_connection, reference, false)
+}
+
+fn lookup(db_connection: &dyn DatabaseService, reference: &str, fuzzy: bool) -> Result<LocalNote> {
+    let reference = reference.trim();
+
+    if is_uuid(&reference.to_uppercase()) {
+        return db_connection.fetch_single_note(&reference.to_uppercase())?
+            .ok_or_else(|| NoteError::NoteNotFound.into());
+    }
+
+    let notes: Vec<LocalNote> = db_connection.fetch_all_notes()?.into_iter().collect();
+
+    let by_path = match reference.rsplit_once('/') {
+        Some((folder, title)) if !folder.is_empty() && !title.is_empty() => {
+            let folder = full_folder_name(&folder.replace('/', ".")).to_lowercase();
+            let in_folder: Vec<&LocalNote> = notes.iter()
+                .filter(|note| note.metadata.subfolder.to_lowercase() == folder)
+                .collect();
+            match_title(&in_folder, title, &[])
+        }
+        _ => (vec![], false)
+    };
+
+    let (matches, close) = if by_path.0.is_empty() {
+        let all: Vec<&LocalNote> = notes.iter().collect();
+        let by_uuid_prefix: Vec<&LocalNote> = if is_uuid_prefix(reference) {
+            let prefix = reference.to_uppercase();
+            all.iter().filter(|note| note.metadata.uuid.starts_with(&prefix)).cloned().collect()
+        } else {
+            vec![]
+        };
+        match_title(&all, reference, &by_uuid_prefix)
+    } else {
+        by_path
+    };
+
+    match matches.len() {
+        0 => Err(NoteError::NoteNotFound.into()),
+        1 if fuzzy || close => Ok(matches[0].clone()),
+        _ => {
+            let mut candidates: Vec<Candidate> = matches.iter().map(|note| Candidate::from_note(note)).collect();
+            candidates.sort_by(|a, b| (&a.folder, &a.subject, &a.uuid).cmp(&(&b.folder, &b.subject, &b.uuid)));
+            Err(NoteError::AmbiguousNote(reference.to_string(), candidates).into())
+        }
+    }
+}
+
+/// Returns the notes of the first matching step and whether that step compared the
+/// title exactly or as prefix, exact_matches are treated like notes with exactly the passed title
+fn match_title<'a>(notes: &[&'a LocalNote], title: &str, exact_matches: &[&'a LocalNote]) -> (Vec<&'a LocalNote>, bool) {
+    let title_lower = title.to_lowercase();
+    let steps: [&dyn Fn(&str) -> bool; 5] = [
+        &|subject| subject == title,
+        &|subject| subject.to_lowercase() == title_lower,
+        &|subject| subject.to_lowercase().starts_with(&title_lower),
+        &|subject| subject.to_lowercase().contains(&title_lower),
+        &|subject| is_subsequence(&title_lower, &subject.to_lowercase()),
+    ];
+
+    for (index, step) in steps.iter().enumerate() {
+        let mut matches: Vec<&LocalNote> = notes.iter()
+            // Unmerged notes have one subject per body
+            .filter(|note| note.body.iter().any(|body| step(&body.subject())))
+            .cloned()
+            .collect();
+
+        if index == 0 {
+            for note in exact_matches {
+                if !matches.iter().any(|present| present.metadata.uuid == note.metadata.uuid) {
+                    matches.push(note);
+                }
+            }
+        }
+
+        if !matches.is_empty() {
+            return (matches, index < FIRST_LOOSE_STEP);
+        }
+    }
+
+    (vec![], false)
+}
+
+fn is_uuid_prefix(reference: &str) -> bool {
+    reference.len() >= MIN_UUID_PREFIX_LEN
+        && reference.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Checks if all characters of needle appear in the same order inside haystack
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| haystack.any(|h| h == c))
+}
+
+#[cfg(test)]
+mod lookup_tests {
+    use super::*;
+    use builder::{NotesMetadataBuilder, BodyMetadataBuilder};
+    use db::SqliteDBConnection;
+    use error::ErrorCode;
+
+    fn insert(con: &SqliteDBConnection, uuid: &str, folder: &str, text: &str) {
+        let mut metadata = NotesMetadataBuilder::new().with_uuid(uuid).build();
+        metadata.subfolder = folder.to_string();
+        con.insert_into_db(&note![metadata, BodyMetadataBuilder::new().with_text(text).build()]).unwrap();
+    }
+
+    fn test_db() -> SqliteDBConnection {
+        let con = SqliteDBConnection::in_memory().unwrap();
+        insert(&con, "AAAA1111-0000-0000-0000-000000000000", "Notes", "Shopping list\nMilk");
+        insert(&con, "AAAA2222-0000-0000-0000-000000000000", "Notes.Work", "Meeting notes\nAgenda");
+        insert(&con, "BBBB3333-0000-0000-0000-000000000000", "Notes.Private", "Meeting notes\nDinner");
+        con
+    }
+
+    fn uuid_of(con: &SqliteDBConnection, reference: &str) -> String {
+        find_note(con, reference).unwrap().metadata.uuid
+    }
+
+    #[test]
+    fn find_by_uuid() {
+        let con = test_db();
+        assert_eq!(uuid_of(&con, "aaaa1111-0000-0000-0000-000000000000"), "AAAA1111-0000-0000-0000-000000000000");
+        assert_eq!(uuid_of(&con, "bbbb"), "BBBB3333-0000-0000-0000-000000000000");
+        assert_eq!(uuid_of(&con, "AAAA2"), "AAAA2222-0000-0000-0000-000000000000");
+        assert!(find_note(&con, "AAAA").is_err());
+    }
+
+    #[test]
+    fn find_by_title() {
+        let con = test_db();
+        assert_eq!(uuid_of(&con, "Shopping list"), "AAAA1111-0000-0000-0000-000000000000");
+        assert_eq!(uuid_of(&con, "shop"), "AAAA1111-0000-0000-0000-000000000000");
+        assert_eq!(uuid_of(&con, "list"), "AAAA1111-0000-0000-0000-000000000000");
+        assert_eq!(uuid_of(&con, "shplst"), "AAAA1111-0000-0000-0000-000000000000");
+        assert_eq!(uuid_of(&con, "Work/Meeting notes"), "AAAA2222-0000-0000-0000-000000000000");
+        assert_eq!(uuid_of(&con, "Notes.Private/meeting"), "BBBB3333-0000-0000-0000-000000000000");
+        assert_eq!(find_note(&con, "Groceries").err().unwrap().error_code(), NoteError::NoteNotFound.error_code());
+    }
+
+    /// A title that matches several notes must not pick one of them
+    #[test]
+    fn ambiguous_title() {
+        let con = test_db();
+        let error = find_note(&con, "Meeting").err().unwrap();
+        assert_eq!(error.error_code(), NoteError::AmbiguousNote(String::new(), vec![]).error_code());
+        assert!(error.to_string().contains("AAAA2222-0000-0000-0000-000000000000"));
+        assert!(error.to_string().contains("BBBB3333-0000-0000-0000-000000000000"));
+    }
+
+    /// Commands that change a note must not act on a substring or fuzzy match
+    #[test]
+    fn strict_title() {
+        let con = test_db();
+        let strict_uuid = |reference: &str| find_note_strictly(&con, reference).unwrap().metadata.uuid;
+        assert_eq!(strict_uuid("shopping list"), "AAAA1111-0000-0000-0000-000000000000");
+        assert_eq!(strict_uuid("Shop"), "AAAA1111-0000-0000-0000-000000000000");
+        assert_eq!(strict_uuid("aaaa1"), "AAAA1111-0000-0000-0000-000000000000");
+        assert_eq!(strict_uuid("Work/Meeting"), "AAAA2222-0000-0000-0000-000000000000");
+
+        let ambiguous = NoteError::AmbiguousNote(String::new(), vec![]).error_code();
+        for reference in &["list", "shplst", "Work/notes"] {
+            let error = find_note_strictly(&con, reference).err().unwrap();
+            assert_eq!(error.error_code(), ambiguous);
+            assert!(error.to_string().contains("[Shopping list]") || error.to_string().contains("[Meeting notes]"));
+        }
+        assert_eq!(find_note_strictly(&con, "Groceries").err().unwrap().error_code(), NoteError::NoteNotFound.error_code());
+    }
+}
@@ -10,4 +10,53 @@ pub fn merge_two(first: &str, second: &str) -> String {
     })
         .map(|string| format!("{}\n", string))
         .collect()
-}
\ No newline at end of file
+}
+
+/// Part of the line diff of two texts, either lines both texts share or a
+/// conflict with the lines that only one of them contains
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hunk {
+    Same(Vec<String>),
+    Conflict { left: Vec<String>, right: Vec<String> }
+}
+
+/// Groups the line diff of both texts into hunks, consecutive lines that
+/// differ form one conflict
+pub fn hunks(first: &str, second: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = vec![];
+
+    for line in diff::lines(first, second) {
+        match (line, hunks.last_mut()) {
+            (diff::Result::Both(l, _), Some(Hunk::Same(lines))) => lines.push(l.to_string()),
+            (diff::Result::Both(l, _), _) => hunks.push(Hunk::Same(vec![l.to_string()])),
+            (diff::Result::Left(l), Some(Hunk::Conflict { left, .. })) => left.push(l.to_string()),
+            (diff::Result::Right(r), Some(Hunk::Conflict { right, .. })) => right.push(r.to_string()),
+            (diff::Result::Left(l), _) => hunks.push(Hunk::Conflict { left: vec![l.to_string()], right: vec![] }),
+            (diff::Result::Right(r), _) => hunks.push(Hunk::Conflict { left: vec![], right: vec![r.to_string()] }),
+        }
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod merge_tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn groups_conflicting_lines() {
+        assert_eq!(
+            hunks("Title\nMilk\nEggs\nButter", "Title\nWater\nButter\nBread"),
+            vec![
+                Hunk::Same(lines(&["Title"])),
+                Hunk::Conflict { left: lines(&["Milk", "Eggs"]), right: lines(&["Water"]) },
+                Hunk::Same(lines(&["Butter"])),
+                Hunk::Conflict { left: vec![], right: lines(&["Bread"]) },
+            ]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+extern crate notify;
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+use self::notify::{Watcher, RecursiveMode, DebouncedEvent, watcher};
+use db::DatabaseService;
+use edit::edited_note;
+use export::{FrontMatter, MARKDOWN_EXTENSION, export_markdown};
+use import::{import_file, ImportOptions};
+use error::{Result, NoteError, UpdateError};
+use profile::Profile;
+use lock::DatabaseLock;
+
+/// Outcome of a single file change inside the mirror directory
+#[derive(Debug, PartialEq)]
+pub enum MirrorChange {
+    /// The file content got saved as local edit of the note with the contained uuid
+    Edited(String),
+    /// The file got imported as new note with the contained uuid
+    Created(String),
+    /// The file content equals the content inside the database
+    Unchanged,
+    /// The file was written for an outdated version of the note, the content
+    /// got saved next to the file
+    Conflict(PathBuf),
+}
+
+/// Applies the content of a changed file inside the mirror directory to the database
+///
+/// Files that carry a uuid are treated like an edit inside the text editor, files
+/// without front matter are getting imported as new notes
+pub(crate) fn apply_file_change(db_connection: &dyn DatabaseService,
+                                root: &Path,
+                                path: &Path,
+                                profile: &Profile) -> Result<MirrorChange> {
+    let content = std::fs::read_to_string(path)?;
+    let (front_matter, text) = FrontMatter::parse(&content);
+
+    let front_matter = match front_matter {
+        Some(FrontMatter { uuid: Some(_), .. }) => front_matter.unwrap(),
+        _ => {
+            let options = ImportOptions { skip_imported: true, record_uuid: true };
+            return import_file(db_connection, root, path, options, profile)
+                .map(|outcome| MirrorChange::Created(outcome.uuid().to_string()));
+        }
+    };
+
+    let uuid = front_matter.uuid.clone().unwrap();
+    let note = match db_connection.fetch_single_note(&uuid)? {
+        Some(note) => note,
+        None => return Err(NoteError::NoteNotFound.into())
+    };
+
+    if note.needs_merge() {
+        return Err(NoteError::NeedsMerge.into());
+    }
+
+    let body = &note.body[0];
+
+    if body.text.as_deref().unwrap_or("") == text {
+        return Ok(MirrorChange::Unchanged);
+    }
+
+    // The note changed inside the database since the file got written, keep the
+    // content of the file, so that the user is able to merge it by hand
+    if front_matter.message_id.as_ref() != Some(&body.message_id) {
+        let conflict_path = path.with_extension("conflict");
+        warn!("{} is outdated, saving its content to {}", path.display(), conflict_path.display());
+        std::fs::write(&conflict_path, text)?;
+        return Ok(MirrorChange::Conflict(conflict_path));
+    }
+
+    let mut edited = edited_note(&note, body, &text, profile)?;
+    // Files are saved many times between two syncs, the note has to keep referring
+    // to the message-id that is present on the server
+    if let Some(remote_message_id) = &body.old_remote_message_id {
+        edited.body[0].old_remote_message_id = Some(remote_message_id.clone());
+    }
+    db_connection.update(&edited)?;
+    Ok(MirrorChange::Edited(uuid))
+}
+
+/// Keeps dir and the database in sync until the watcher fails
+///
+/// Changed files are getting saved as local edits, changes inside the database
+/// (for example from a sync) are getting written back to the files every interval.
+/// The database gets locked through lock_database for every change and every write
+/// back, not while waiting for changes
+pub(crate) fn mirror(db_connection: &dyn DatabaseService,
+                     dir: &Path,
+                     interval: Duration,
+                     profile: &Profile,
+                     lock_database: &dyn Fn() -> Result<Option<DatabaseLock>>) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let dir = dir.canonicalize()?;
+    {
+        let _lock = lock_database()?;
+        export_markdown(&db_connection.fetch_all_notes()?, &dir, true)?;
+    }
+
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, Duration::from_millis(500))
+        .map_err(|e| UpdateError::IoError(e.to_string()))?;
+    watcher.watch(&dir, RecursiveMode::Recursive)
+        .map_err(|e| UpdateError::IoError(e.to_string()))?;
+
+    info!("Mirroring notes to {}", dir.display());
+
+    loop {
+        let changed_path = match rx.recv_timeout(interval) {
+            Ok(DebouncedEvent::Create(path)) | Ok(DebouncedEvent::Write(path)) | Ok(DebouncedEvent::Rename(_, path)) => Some(path),
+            Ok(DebouncedEvent::Error(e, _)) => return Err(UpdateError::IoError(e.to_string()).into()),
+            Ok(_) => None,
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return Err(UpdateError::IoError("File watcher stopped".to_string()).into())
+        };
+
+        let _lock = lock_database()?;
+
+        if let Some(path) = changed_path.filter(|path| is_note_file(path)) {
+            match apply_file_change(db_connection, &dir, &path, profile) {
+                Ok(MirrorChange::Edited(uuid)) => info!("Saved edit of {} [{}]", path.display(), uuid),
+                Ok(MirrorChange::Created(uuid)) => info!("Created note from {} [{}]", path.display(), uuid),
+                Ok(_) => {}
+                Err(e) => warn!("Could not apply changes of {}: {}", path.display(), e.to_string())
+            }
+        }
+
+        // Writes back the new message-ids of edited notes and everything that changed
+        // inside the database in the meantime
+        export_markdown(&db_connection.fetch_all_notes()?, &dir, true)?;
+    }
+}
+
+fn is_note_file(path: &Path) -> bool {
+    path.is_file() && path.extension().map(|ext| ext == MARKDOWN_EXTENSION).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod mirror_tests {
+    use super::*;
+    use builder::{NotesMetadataBuilder, BodyMetadataBuilder, ProfileBuilder};
+    use db::SqliteDBConnection;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("apnotes_mirror_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Edited files should be saved like an edit inside the text editor
+    #[test]
+    fn file_edit() {
+        let dir = temp_dir("edit");
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_message_id("remote").with_text("Title\ntext").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+        export_markdown(&con.fetch_all_notes().unwrap(), &dir, true).unwrap();
+
+        let path = dir.join("Title.md");
+        let content = std::fs::read_to_string(&path).unwrap().replace("text", "edited");
+        std::fs::write(&path, content).unwrap();
+
+        let change = apply_file_change(&con, &dir, &path, &ProfileBuilder::for_tests().build()).unwrap();
+        assert_eq!(change, MirrorChange::Edited(note.metadata.uuid.clone()));
+
+        let edited = con.fetch_single_note(&note.metadata.uuid).unwrap().unwrap();
+        assert_eq!(edited.body[0].text, Some("Title\nedited".to_string()));
+        assert_eq!(edited.body[0].old_remote_message_id, Some("remote".to_string()));
+
+        // Nothing changed after the edited note got written back
+        export_markdown(&con.fetch_all_notes().unwrap(), &dir, true).unwrap();
+        assert_eq!(apply_file_change(&con, &dir, &path, &ProfileBuilder::for_tests().build()).unwrap(), MirrorChange::Unchanged);
+
+        // Saving the file again still refers to the message-id of the server
+        let content = std::fs::read_to_string(&path).unwrap().replace("edited", "edited twice");
+        std::fs::write(&path, content).unwrap();
+        apply_file_change(&con, &dir, &path, &ProfileBuilder::for_tests().build()).unwrap();
+        let edited = con.fetch_single_note(&note.metadata.uuid).unwrap().unwrap();
+        assert_eq!(edited.body[0].old_remote_message_id, Some("remote".to_string()));
+    }
+
+    /// Files of outdated note versions should not override the database
+    #[test]
+    fn outdated_file() {
+        let dir = temp_dir("outdated");
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_message_id("old").with_text("Title\ntext").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+        export_markdown(&con.fetch_all_notes().unwrap(), &dir, true).unwrap();
+
+        let mut updated = note.clone();
+        updated.body[0].message_id = "new".to_string();
+        updated.body[0].text = Some("Title\nremote change".to_string());
+        con.update(&updated).unwrap();
+
+        let path = dir.join("Title.md");
+        let content = std::fs::read_to_string(&path).unwrap().replace("text", "local change");
+        std::fs::write(&path, content).unwrap();
+
+        let change = apply_file_change(&con, &dir, &path, &ProfileBuilder::for_tests().build()).unwrap();
+        assert_eq!(change, MirrorChange::Conflict(dir.join("Title.conflict")));
+        assert_eq!(
+            con.fetch_single_note(&note.metadata.uuid).unwrap().unwrap().body[0].text,
+            Some("Title\nremote change".to_string())
+        );
+    }
+}
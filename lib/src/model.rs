@@ -1,6 +1,7 @@
 use ::util;
 use schema::metadata;
 use schema::body;
+use schema::tags;
 #[cfg(test)]
 use notes::localnote::LocalNote;
 use std::hash::Hasher;
@@ -8,12 +9,14 @@ use notes::note_headers::NoteHeaders;
 use notes::remote_note_metadata::RemoteNoteMetaData;
 use notes::traits::identifyable_note::IdentifiableNote;
 use notes::traits::header_parser::HeaderParser;
-use chrono::{DateTime};
+use chrono::NaiveDateTime;
 #[cfg(not(test))]
 use profile::Profile;
 
 
-#[derive(Identifiable,Clone,Queryable,Insertable,Debug,Eq)]
+/// Loaded with the columns of METADATA_COLUMNS, the date column only
+/// exists for older versions
+#[derive(Identifiable,Clone,Queryable,Debug,Eq)]
 #[table_name="metadata"]
 #[primary_key(uuid)]
 pub struct NotesMetadata {
@@ -24,7 +27,9 @@ pub struct NotesMetadata {
     /// Indicator for newly created notes, so that they
     /// dont get deleted while syncing
     pub new: bool,
-    pub date: String, //TODO type
+    /// Date of the last modification in UTC, None if the date
+    /// header of the note was malformed
+    pub date: Option<NaiveDateTime>,
     /// UUID for the message. This uuid never changes after
     /// creating a note.
     ///
@@ -36,6 +41,57 @@ pub struct NotesMetadata {
     /// edited on device2.
     pub uuid: String,
     pub mime_version: String,
+    /// Creation date in UTC, taken from the X-Mail-Created-Date header.
+    /// None if the header was malformed or the note got stored before
+    /// the creation date was kept
+    pub created_date: Option<NaiveDateTime>,
+}
+
+/// Columns that are selected to load NotesMetadata objects
+pub(crate) const METADATA_COLUMNS: (
+    metadata::subfolder,
+    metadata::locally_deleted,
+    metadata::new,
+    metadata::modified_at,
+    metadata::uuid,
+    metadata::mime_version,
+    metadata::created_at,
+) = (
+    metadata::subfolder,
+    metadata::locally_deleted,
+    metadata::new,
+    metadata::modified_at,
+    metadata::uuid,
+    metadata::mime_version,
+    metadata::created_at,
+);
+
+#[derive(Insertable)]
+#[table_name="metadata"]
+pub(crate) struct NewMetadata<'a> {
+    subfolder: &'a str,
+    locally_deleted: bool,
+    new: bool,
+    date: String,
+    uuid: &'a str,
+    mime_version: &'a str,
+    modified_at: Option<NaiveDateTime>,
+    created_at: Option<NaiveDateTime>,
+}
+
+impl<'a> NewMetadata<'a> {
+    pub(crate) fn from_metadata(metadata: &'a NotesMetadata) -> NewMetadata<'a> {
+        NewMetadata {
+            subfolder: &metadata.subfolder,
+            locally_deleted: metadata.locally_deleted,
+            new: metadata.new,
+            date: metadata.date.as_ref().map(util::format_date).unwrap_or_default(),
+            uuid: &metadata.uuid,
+            mime_version: &metadata.mime_version,
+            modified_at: metadata.date,
+            created_at: metadata.created_date,
+        }
+    }
 }
 
 impl NotesMetadata {
@@ -44,9 +100,10 @@ impl NotesMetadata {
             subfolder,
             locally_deleted: false,
             new: false,
-            date: header.date(),
+            date: header_date(header, "Date"),
             uuid: header.uuid(),
             mime_version: header.mime_version(),
+            created_date: header_date(header, "X-Mail-Created-Date"),
         }
     }
 
@@ -55,17 +112,37 @@ impl NotesMetadata {
             subfolder: remote_metadata.folder.clone(),
             locally_deleted: false,
             new: false,
-            date: remote_metadata.headers.date(),
+            date: header_date(&remote_metadata.headers, "Date"),
             uuid: remote_metadata.headers.uuid(),
-            mime_version: remote_metadata.headers.mime_version()
+            mime_version: remote_metadata.headers.mime_version(),
+            created_date: header_date(&remote_metadata.headers, "X-Mail-Created-Date"),
         }
     }
 
+    /// Unix timestamp of the last modification, 0 if the date is unknown
     pub fn timestamp(&self) -> i64 {
-        DateTime::parse_from_rfc2822(self.date.as_ref()).unwrap().timestamp()
+        self.date.map(|date| date.timestamp()).unwrap_or(0)
+    }
+
+    /// Creation date of the note, falls back to the modification date
+    /// for notes without a known creation date
+    pub fn created_or_modified(&self) -> Option<NaiveDateTime> {
+        self.created_date.or(self.date)
     }
 }
 
+/// Parses the date inside the header with the passed name, malformed dates are logged
+/// and treated like missing ones
+pub(crate) fn header_date(headers: &NoteHeaders, name: &str) -> Option<NaiveDateTime> {
+    let value = headers.get_header_value(name)?;
+    let date = util::parse_date(&value);
+    if date.is_none() {
+        let uuid = headers.get_header_value("X-Universally-Unique-Identifier").unwrap_or_default();
+        warn!("Malformed {} header \"{}\" of note {}", name, value, uuid);
+    }
+    date
+}
+
 impl IdentifiableNote for NotesMetadata {
 
     fn folder(&self) -> String { self.subfolder.clone() }
@@ -198,3 +275,13 @@ impl std::hash::Hash for Body {
         self.message_id.hash(state);
     }
 }
+
+/// A hashtag that is contained inside the text of a note body
+#[derive(Clone,Queryable,Insertable,Debug,PartialEq,Eq)]
+#[table_name="tags"]
+pub struct Tag {
+    /// Lowercase tag without the leading #
+    pub tag: String,
+    /// Foreign key to the body that contains the tag
+    pub message_id: String
+}
@@ -10,6 +10,8 @@ use notes::traits::header_parser::HeaderParser;
 use std::collections::HashSet;
 use quoted_printable::ParseMode;
 use profile::Profile;
+use util::format_date;
+use chrono::Utc;
 
 #[derive(Eq,Clone,Debug)]
 pub struct LocalNote {
@@ -21,6 +23,11 @@ impl LocalNote {
     pub fn needs_merge(&self) -> bool {
         self.body.len() > 1
     }
+
+    /// Right now only notes with exactly 2 bodies can be merged
+    pub fn mergeable(&self) -> bool {
+        self.body.len() == 2
+    }
     //TODO right not it only works for merged notes
     pub fn to_header_vector(&self, profile: &Profile) -> NoteHeaders {
         let mut headers: Vec<(String,String)> = vec![];
@@ -28,8 +35,10 @@ impl LocalNote {
         headers.push(("Content-Type".to_string(), "text/html; charset=utf-8".to_string()));
         headers.push(("Content-Transfer-Encoding".to_string(), "quoted-printable".to_string()));
         headers.push(("Mime-Version".to_string(), "1.0 (Mac OS X Notes 4.6 \\(879.10\\))".to_string()));
-        headers.push(("Date".to_string(), self.metadata.date.clone()));
-        headers.push(("X-Mail-Created-Date".to_string(), self.metadata.date.clone()));
+        let now = Utc::now().naive_utc();
+        let date = self.metadata.date.unwrap_or(now);
+        headers.push(("Date".to_string(), format_date(&date)));
+        headers.push(("X-Mail-Created-Date".to_string(), format_date(&self.metadata.created_date.unwrap_or(date))));
         headers.push(("From".to_string(), (&profile.email).to_string())); //todo implement in noteheader
         headers.push(("Message-Id".to_string(), self.body.first().unwrap().message_id.clone()));
         headers.push(("X-Universally-Unique-Identifier".to_string(), self.metadata.uuid.clone()));
@@ -49,18 +58,7 @@ impl LocalNote {
     #[cfg(test)]
     pub fn to_remote_metadata(&self) -> RemoteNoteMetaData {
 
-        let profile = Profile {
-            username: "test".to_string(),
-            password: Option::from("test".to_string()),
-            imap_server: "test".to_string(),
-            email: "test@test.de".to_string(),
-            editor: "test".to_string(),
-            editor_arguments: vec![],
-            secret_service_attribute: None,
-            secret_service_value: None,
-            domain: "".to_string(),
-            password_type: "".to_string()
-        };
+        let profile = ::builder::ProfileBuilder::for_tests().build();
 
         RemoteNoteMetaData {
             headers: self.to_header_vector(&profile),
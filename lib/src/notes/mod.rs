@@ -1,6 +1,6 @@
 pub mod traits;
 pub(crate) mod grouped_remote_note_headers;
-pub(crate) mod remote_note_metadata;
-pub(crate) mod remote_note_header_collection;
-pub(crate) mod note_headers;
+pub mod remote_note_metadata;
+pub mod remote_note_header_collection;
+pub mod note_headers;
 pub mod localnote;
\ No newline at end of file
@@ -1,18 +1,14 @@
 #[cfg(target_family = "unix")]
 extern crate xdg;
 
-extern crate regex;
-extern crate log;
-
-use self::regex::Regex;
-use std::fs::File;
-use self::log::{warn};
 use std::path::PathBuf;
 use error::ProfileError::*;
 use std::str;
 use error::Result;
 
 use error::ProfileError;
+use config;
+use config::{TuiSettings, ServerSettings, HookSettings, GitSettings};
 
 #[cfg(target_family = "unix")]
 use self::xdg::BaseDirectories;
@@ -31,29 +27,89 @@ pub struct Profile {
     pub(crate) editor_arguments: Vec<String>,
     pub(crate) secret_service_attribute: Option<String>,
     pub(crate) secret_service_value: Option<String>,
+    /// Shell command that prints the password
+    pub(crate) password_command: Option<String>,
     #[allow(dead_code)]
     pub(crate) domain: String,
+    /// Days deleted notes are kept inside the trash
+    pub(crate) trash_retention_days: i64,
+    /// Location of the notes database, the default location
+    /// inside the user data directory is used if not set
+    pub(crate) db_path: Option<PathBuf>,
+    pub(crate) tui: TuiSettings,
+    pub(crate) server: ServerSettings,
+    pub(crate) hooks: HookSettings,
+    pub(crate) git: GitSettings,
 
     pub(crate) password: Option<String>,
 }
 
 impl Profile {
 
+    /// Settings of the [tui] config section
+    pub fn tui_settings(&self) -> &TuiSettings {
+        &self.tui
+    }
+
+    /// Settings of the [server] config section
+    pub fn server_settings(&self) -> &ServerSettings {
+        &self.server
+    }
+
+    /// Settings of the [hooks] config section
+    pub fn hook_settings(&self) -> &HookSettings {
+        &self.hooks
+    }
+
+    /// Settings of the [git] config section
+    pub fn git_settings(&self) -> &GitSettings {
+        &self.git
+    }
+
+    /// Returns the configured database location or the default one
+    pub fn database_path(&self) -> Result<PathBuf> {
+        match &self.db_path {
+            Some(path) => Ok(path.clone()),
+            None => default_db_path()
+        }
+    }
+
     #[cfg(target_family = "unix")]
     pub fn get_password(&self) -> Result<String> {
-        if self.password_type == "PLAIN" {
-            Ok(self.password.as_ref().unwrap().clone())
-        } else {
-            self.secret_service_get_pw()
+        match self.password_type.as_str() {
+            "PLAIN" => self.password.clone().ok_or_else(|| NoPasswordProvided().into()),
+            "COMMAND" => self.command_get_pw(),
+            _ => self.secret_service_get_pw()
         }
     }
 
     #[cfg(target_family = "windows")]
     pub fn get_password(&self) -> Result<String> {
-        if self.password_type == "PLAIN" {
-            Ok(self.password.as_ref().unwrap().clone())
-        } else {
-            panic!("Password type {} not supported", self.password_type)
+        match self.password_type.as_str() {
+            "PLAIN" => self.password.clone().ok_or_else(|| NoPasswordProvided().into()),
+            "COMMAND" => self.command_get_pw(),
+            _ => panic!("Password type {} not supported", self.password_type)
+        }
+    }
+
+    /// Runs the password command, the first line it prints is used as password
+    fn command_get_pw(&self) -> Result<String> {
+        let command = self.password_command.as_ref().unwrap();
+
+        #[cfg(target_family = "unix")]
+        let output = std::process::Command::new("sh").arg("-c").arg(command).output()?;
+        #[cfg(target_family = "windows")]
+        let output = std::process::Command::new("cmd").arg("/C").arg(command).output()?;
+
+        if !output.status.success() {
+            return Err(PasswordCommandFailed(
+                format!("\"{}\" exited with {}: {}", command, output.status, String::from_utf8_lossy(&output.stderr).trim())
+            ).into());
+        }
+
+        match str::from_utf8(&output.stdout)?.lines().next() {
+            Some(password) if !password.is_empty() => Ok(password.to_string()),
+            _ => Err(PasswordCommandFailed(format!("\"{}\" did not print a password", command)).into())
         }
     }
 
@@ -82,140 +138,37 @@ impl Profile {
     }
 }
 
+/// Location of the database inside the user data directory, the directory gets
+/// created if it does not exist yet
 #[cfg(target_family = "unix")]
-pub(crate)  fn get_config_path() -> Result<PathBuf> {
-        let xdg_dir = BaseDirectories::new()?;
-        match xdg_dir.find_config_file("apnotes/config") {
-            Some(path) => Ok(path),
-            None => {
-                warn!("Could not detect config file, gonna create empty one");
-                let mut path = xdg_dir.create_config_directory("apnotes")?;
-                path.push("config");
-                File::create(&path).expect("Unable to create file");
-                Ok(path.to_path_buf())
-            }
-        }
+pub(crate)  fn default_db_path() -> Result<PathBuf> {
+    let xdg_dir = BaseDirectories::new()?;
+    Ok(xdg_dir.place_data_file("apnotes/notes_db")?)
 }
 
 #[cfg(target_family = "windows")]
-pub(crate)  fn get_config_path() -> Result<PathBuf> {
-    let config_file_path = PathBuf::from(format!("{}\\{}",env!("APPDATA"),"apnotes\\config".to_string()));
-    if config_file_path.exists() {
-        Ok(config_file_path)
-    } else {
-        warn!("Could not detect config file, gonna create empty one");
-        std::fs::create_dir(&config_file_path.parent().unwrap())?;
-        File::create(&config_file_path)?;
-        Ok(config_file_path)
-    }
-}
-
-#[cfg(target_family = "unix")]
-pub(crate)  fn get_db_path() -> PathBuf {
-    let xdg_dir = BaseDirectories::new().expect("Could not find xdg dirs");
-    #[cfg(test)]
-        let db = "notes_db_test";
-    #[cfg(not(test))]
-        let db = "notes_db";
-    match xdg_dir.find_data_file(format!("apnotes/{}",db)) {
-        Some(path) => path,
-        None => {
-            warn!("Could not detect database, gonna create empty one");
-            let mut path = xdg_dir.create_data_directory("apnotes").expect("Could not create apple_notes config folder");
-            path.push(&db);
-            File::create(&path).expect("Unable to create file");
-            path.to_path_buf()
-        }
-    }
-}
-
-#[cfg(target_family = "windows")]
-pub(crate)  fn get_db_path() -> PathBuf {
+pub(crate)  fn default_db_path() -> Result<PathBuf> {
     let db_file_path = PathBuf::from(format!("{}\\{}", env!("APPDATA"), "apnotes\\db".to_string()));
-    if db_file_path.exists() {
-        db_file_path
-    } else {
-        warn!("Could not detect database, gonna create empty one");
-        if std::fs::create_dir(&db_file_path.parent().unwrap()).is_err() {
-            error!("Folder does already exist")
-        }
-        File::create(&db_file_path).expect("Unable to create config file");
-        db_file_path
-    }
+    std::fs::create_dir_all(&db_file_path.parent().unwrap())?;
+    Ok(db_file_path)
 }
 
+/// Reads the profile from the config file, config.toml is preferred over
+/// the legacy key=value config file
 pub(crate) fn load_profile() -> Result<Profile> {
-    let path = get_config_path()?;
-    let path = path.into_os_string().to_string_lossy().to_string();
-
-    trace!("Read config file from {}", &path);
     #[cfg(not(test))]
-    let creds = std::fs::read_to_string(&path)?;
-    #[cfg(test)]
-    let creds = unsafe { get_test_config() };
-
-    let username_regex = Regex::new(r"username=(.*)")?;
-    let password_regex = Regex::new(r"password=(.*)")?;
-    let imap_regex = Regex::new(r"imap_server=(.*)")?;
-    let email_regex = Regex::new(r"email=(.*)")?;
-    let editor_regex = Regex::new(r"editor=(.*)")?;
-    let args_regex = Regex::new(r"editor_arguments=(.*)")?;
-    let uuid_regex = Regex::new(r".*@(.*)")?;
-    let password_type_regex = Regex::new(r"password_type=(.*)")?;
-
-    let username = get_with_regex(username_regex, &creds)?;
-    let password = get_with_regex(password_regex, &creds).map(|e| Some(e)).or_else::<ProfileError,_>(|_| Ok(None))?;
-    let imap_server = get_with_regex(imap_regex, &creds)?;
-    let email = get_with_regex(email_regex, &creds)?;
-    let editor = get_with_regex(editor_regex, &creds)?;
-    let args = get_with_regex(args_regex, &creds)?.split(" ").map(|s| s.to_string()).filter(|s| s.len() > 0).collect();
-    let domain = get_with_regex(uuid_regex, &email)?;
-    let password_type = get_with_regex(password_type_regex, &creds).or_else::<ProfileError,_>(|_| Ok("PLAIN".to_string()))?;
-
-    let (secret_service_attribute, secret_service_value) = if password_type == "SECRET_SERVICE".to_string() {
-        let secret_service_attribute_regex = Regex::new(r"secret_service_attribute=(.*)")?;
-        let secret_service_value_regex = Regex::new(r"secret_service_value=(.*)")?;
-        (
-            Some(get_with_regex(secret_service_attribute_regex, &creds)?),
-            Some(get_with_regex(secret_service_value_regex, &creds)?),
-        )
-    } else {
-        if password.is_none() {
-            return Err(NoPasswordProvided().into())
+    let (creds, format) = {
+        let file = config::locate()?;
+        if !file.exists {
+            return Err(NotFound(format!("No config file at {}, run \"apnotes init\" to create one", file.path.display())).into());
         }
-        (None, None)
+        trace!("Read {} config file from {}", file.format.as_str(), file.path.display());
+        (std::fs::read_to_string(&file.path)?, file.format)
     };
+    #[cfg(test)]
+    let (creds, format) = (unsafe { get_test_config() }.to_string(), config::ConfigFormat::Legacy);
 
-    Ok(
-        Profile {
-            username,
-            password,
-            password_type,
-            imap_server,
-            email,
-            editor,
-            editor_arguments: args,
-            secret_service_attribute,
-            secret_service_value,
-            domain
-        }
-    )
-}
-
-fn get_with_regex(regex: Regex, creds: &str) -> Result<String> {
-    match regex.captures(&creds)
-        .and_then(|captured| captured.get(1))
-        .and_then(|result| Option::from(result.as_str().to_string())) {
-        Some(e) => Ok(e),
-        None => {
-            let config_entry_name = regex.to_string().replace("=(.*)","");
-            Err(
-                NotFound(
-                    format!("Could not find entry in config file for key: \"{}\"", config_entry_name)
-                ).into()
-            )
-        }
-    }
+    config::parse(&creds, format).map_err(|issues| InvalidConfig(issues).into())
 }
 
 #[cfg(test)]
@@ -230,6 +183,7 @@ unsafe fn get_test_config() -> &'static str {
 #[cfg(test)]
 mod tests {
     use profile::{load_profile, BASIC_SECRET_SERVICE_CONFIG};
+    use std::path::PathBuf;
     #[cfg(target_family = "unix")]
     use secret_service::{SecretService, EncryptionType};
 
@@ -273,6 +227,7 @@ mod tests {
 
             let profile = load_profile();
             assert_eq!(profile.as_ref().unwrap().password_type,"PLAIN");
+            assert_eq!(profile.as_ref().unwrap().db_path, None);
         }
     }
 
@@ -304,12 +259,14 @@ mod tests {
                 password_type=SECRET_SERVICE
                 secret_service_attribute=mail
                 secret_service_value=mailservice
+                db_path=/tmp/apnotes/notes_db
                 ";
 
             let profile = load_profile();
             assert_eq!(profile.as_ref().unwrap().secret_service_value.as_ref().unwrap(),"mailservice");
             assert_eq!(profile.as_ref().unwrap().secret_service_attribute.as_ref().unwrap(),"mail");
             assert_eq!(profile.as_ref().unwrap().password_type,"SECRET_SERVICE");
+            assert_eq!(profile.as_ref().unwrap().database_path().unwrap(), PathBuf::from("/tmp/apnotes/notes_db"));
         }
 
     }
@@ -0,0 +1,295 @@
+extern crate itertools;
+
+use self::itertools::Itertools;
+use std::path::{Path, PathBuf};
+use apple_imap::MailService;
+use db::{DatabaseService, SqliteDBConnection};
+use builder::BodyMetadataBuilder;
+use model::Body;
+use notes::localnote::LocalNote;
+use notes::traits::identifyable_note::IdentifiableNote;
+use error::{Result, NoteError, UpdateError};
+use sync::{collect_mergeable_notes, localnote_from_remote_header};
+use util::generate_uuid;
+use profile::Profile;
+use chrono::Utc;
+
+/// Prefix of the folders the backup tool creates on the imap server
+const BACKUP_FOLDER_PREFIX: &str = "Backup_Notes.";
+/// Suffix of the folders the backup tool creates on the imap server
+const BACKUP_FOLDER_SUFFIX: &str = "_backup";
+
+/// Location from where notes should be restored
+pub enum BackupSource {
+    /// The Backup_Notes.* folders on the imap server
+    Imap,
+    /// A copy of the notes database
+    Archive(PathBuf),
+}
+
+/// Defines what happens if a restored note has the same uuid
+/// as a note that is already present in the local database
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ClashStrategy {
+    /// The restored note gets a new uuid, both notes are kept
+    KeepBoth,
+    /// The content of the present note gets replaced with the restored one
+    Overwrite,
+}
+
+pub struct RestoreResult {
+    pub uuid: String,
+    pub subject: String,
+    pub result: Result<()>
+}
+
+/// Fetches all notes that are stored inside the Backup_Notes.* folders
+pub(crate) fn fetch_imap_backup(imap_connection: &mut dyn MailService) -> Result<Vec<LocalNote>> {
+    let headers = imap_connection.fetch_backup_headers()?;
+    collect_mergeable_notes(headers)
+        .iter()
+        .map(|noteheaders| localnote_from_remote_header(imap_connection, noteheaders))
+        .map(|note| note.map(|mut note| {
+            note.metadata.subfolder = live_folder_name(&note.metadata.subfolder);
+            note
+        }))
+        .collect()
+}
+
+/// Fetches all notes that are stored inside a copy of the notes database.
+///
+/// Opening a database migrates it, so the archive gets copied to a temporary
+/// file first, the backup itself is never modified
+pub(crate) fn fetch_archive(path: &Path) -> Result<Vec<LocalNote>> {
+    if !path.is_file() {
+        return Err(UpdateError::IoError(format!("Archive {} does not exist", path.display())).into());
+    }
+
+    let copy = std::env::temp_dir().join(format!("apnotes_archive_{}.db", generate_uuid()));
+    std::fs::copy(path, &copy)?;
+
+    let notes = SqliteDBConnection::open(&copy)
+        .and_then(|archive| archive.fetch_all_notes()
+            .map(|notes| notes.into_iter().collect())
+            .map_err(|e| e.into()));
+
+    if let Err(e) = std::fs::remove_file(&copy) {
+        warn!("Could not remove {}: {}", copy.display(), e);
+    }
+
+    notes
+}
+
+/// Maps the name of a backup folder to the folder the note originally lived in
+///
+/// Backup_Notes.Notes.Work_backup -> Notes.Work
+fn live_folder_name(backup_folder: &str) -> String {
+    let folder = backup_folder.trim_start_matches(BACKUP_FOLDER_PREFIX);
+    let folder = folder.strip_suffix(BACKUP_FOLDER_SUFFIX).unwrap_or(folder);
+    if folder.starts_with("Notes") {
+        folder.to_string()
+    } else {
+        "Notes".to_string()
+    }
+}
+
+/// Inserts the passed backup notes into the local database, all restored
+/// notes are getting uploaded with the next sync
+pub(crate) fn restore_notes(db_connection: &dyn DatabaseService,
+                            notes: Vec<LocalNote>,
+                            strategy: ClashStrategy,
+                            profile: &Profile) -> Vec<RestoreResult> {
+    notes.into_iter()
+        .sorted_by_key(|note| note.metadata.subfolder.clone())
+        .map(|note| {
+            let uuid = note.metadata.uuid.clone();
+            let subject = note.first_subject();
+            let result = restore_note(db_connection, note, strategy, profile).map(|_| ());
+            RestoreResult { uuid, subject, result }
+        })
+        .collect()
+}
+
+/// Inserts a single backup note into the local database, returns the note
+/// as it got stored
+pub(crate) fn restore_note(db_connection: &dyn DatabaseService,
+                           note: LocalNote,
+                           strategy: ClashStrategy,
+                           profile: &Profile) -> Result<LocalNote> {
+    let uuid = note.metadata.uuid.clone();
+    let restored = match db_connection.fetch_single_note(&uuid)? {
+        None => restored_note(note, uuid, profile),
+        Some(_) if strategy == ClashStrategy::KeepBoth => restored_note(note, generate_uuid(), profile),
+        Some(present_note) => {
+            let overwritten = overwritten_note(note, &present_note, profile)?;
+            db_connection.update(&overwritten)?;
+            return Ok(overwritten);
+        }
+    };
+    db_connection.insert_into_db(&restored)?;
+    Ok(restored)
+}
+
+/// Converts a backup note into a new local note, every body gets a fresh
+/// message-id so that it does not collide with the backup messages
+fn restored_note(note: LocalNote, uuid: String, profile: &Profile) -> LocalNote {
+    let mut metadata = note.metadata;
+    metadata.uuid = uuid;
+    metadata.new = true;
+    metadata.locally_deleted = false;
+
+    let bodies = note.body.iter()
+        .map(|body| fresh_body(body, profile).build())
+        .collect();
+
+    note_with_bodies(metadata, bodies)
+}
+
+/// Replaces the content of the present note with the content of the backup note,
+/// the change is treated like a local edit
+fn overwritten_note(note: LocalNote, present_note: &LocalNote, profile: &Profile) -> Result<LocalNote> {
+    if note.needs_merge() {
+        return Err(NoteError::NeedsMerge.into());
+    }
+
+    let mut metadata = present_note.metadata.clone();
+    metadata.subfolder = note.metadata.subfolder.clone();
+    metadata.locally_deleted = false;
+    metadata.date = Some(Utc::now().naive_utc());
+
+    let mut body = fresh_body(&note.body[0], profile)
+        .with_uid(present_note.body.first().and_then(|body| body.uid));
+
+    if !present_note.metadata.new {
+        let old_message_ids = present_note.body.iter()
+            .map(|body| body.old_remote_message_id.clone().unwrap_or(body.message_id.clone()))
+            .join(",");
+        body = body.with_old_remote_message_id(&old_message_ids);
+    }
+
+    Ok(note_with_bodies(metadata, vec![body.build()]))
+}
+
+fn fresh_body(body: &Body, _profile: &Profile) -> BodyMetadataBuilder {
+    #[cfg(not(test))]
+    let builder = BodyMetadataBuilder::new(_profile);
+    #[cfg(test)]
+    let builder = BodyMetadataBuilder::new();
+
+    builder.with_text(body.text.as_deref().unwrap_or(""))
+}
+
+fn note_with_bodies(metadata: ::model::NotesMetadata, bodies: Vec<Body>) -> LocalNote {
+    let uuid = metadata.uuid.clone();
+    LocalNote {
+        metadata,
+        body: bodies.into_iter().map(|mut body| {
+            body.metadata_uuid = uuid.clone();
+            body
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod restore_tests {
+    use super::*;
+    use builder::{NotesMetadataBuilder, ProfileBuilder};
+    use db::SqliteDBConnection;
+
+    /// Reading an archive must not migrate or otherwise touch the backup
+    #[test]
+    fn archive_stays_untouched() {
+        let path = std::env::temp_dir().join("apnotes_restore_archive.db");
+        let _ = std::fs::remove_file(&path);
+        {
+            let archive = SqliteDBConnection::open(&path).unwrap();
+            archive.insert_into_db(&note![
+                NotesMetadataBuilder::new().with_uuid("1").build(),
+                BodyMetadataBuilder::new().with_text("archived").build()
+            ]).unwrap();
+        }
+        let before = std::fs::read(&path).unwrap();
+
+        let notes = fetch_archive(&path).unwrap();
+
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].body[0].text, Some("archived".to_string()));
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn backup_folder_mapping() {
+        assert_eq!(live_folder_name("Backup_Notes.Notes_backup"), "Notes");
+        assert_eq!(live_folder_name("Backup_Notes.Notes.Work_backup"), "Notes.Work");
+        assert_eq!(live_folder_name("Backup_Notes"), "Notes");
+    }
+
+    /// A restored note that is not present locally should be flagged as new
+    #[test]
+    fn restore_missing_note() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let backup_note = note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().with_uid(Some(4)).with_text("restored").build()
+        ];
+
+        let results = restore_notes(&con, vec![backup_note.clone()], ClashStrategy::KeepBoth, &ProfileBuilder::for_tests().build());
+        assert!(results[0].result.is_ok());
+
+        let restored = con.fetch_single_note("1").unwrap().unwrap();
+        assert!(restored.metadata.new);
+        assert_eq!(restored.body[0].uid, None);
+        assert_ne!(restored.body[0].message_id, backup_note.body[0].message_id);
+        assert_eq!(restored.body[0].text, Some("restored".to_string()));
+    }
+
+    /// Both notes should be present after restoring with KeepBoth
+    #[test]
+    fn restore_keep_both() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let present_note = note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().with_text("present").build()
+        ];
+        con.insert_into_db(&present_note).unwrap();
+
+        let backup_note = note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().with_text("restored").build()
+        ];
+
+        restore_notes(&con, vec![backup_note], ClashStrategy::KeepBoth, &ProfileBuilder::for_tests().build());
+
+        assert_eq!(con.fetch_all_notes().unwrap().len(), 2);
+        assert_eq!(con.fetch_single_note("1").unwrap().unwrap().body[0].text, Some("present".to_string()));
+    }
+
+    /// The present note should be updated like a local edit
+    #[test]
+    fn restore_overwrite() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let present_note = note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().with_message_id("present").with_uid(Some(3)).with_text("present").build()
+        ];
+        con.insert_into_db(&present_note).unwrap();
+
+        let backup_note = note![
+            NotesMetadataBuilder::new().with_uuid("1").build(),
+            BodyMetadataBuilder::new().with_text("restored").build()
+        ];
+
+        restore_notes(&con, vec![backup_note], ClashStrategy::Overwrite, &ProfileBuilder::for_tests().build());
+
+        let notes = con.fetch_all_notes().unwrap();
+        assert_eq!(notes.len(), 1);
+        let note = notes.iter().next().unwrap();
+        assert_eq!(note.body[0].text, Some("restored".to_string()));
+        assert_eq!(note.body[0].uid, Some(3));
+        assert_eq!(note.body[0].old_remote_message_id, Some("present".to_string()));
+    }
+}
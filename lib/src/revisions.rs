@@ -0,0 +1,185 @@
+use schema::revisions;
+use model::Body;
+use notes::localnote::LocalNote;
+use db::DatabaseService;
+use edit::edited_note;
+use error::{Result, NoteError};
+use profile::Profile;
+use chrono::Utc;
+
+/// Describes why a note body got replaced
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RevisionKind {
+    /// The note got edited locally
+    Edit,
+    /// The note got replaced by a newer remote version during a sync
+    Sync,
+    /// Multiple unmerged bodies got replaced by a merged one
+    Merge,
+}
+
+impl RevisionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevisionKind::Edit => "edit",
+            RevisionKind::Sync => "sync",
+            RevisionKind::Merge => "merge",
+        }
+    }
+}
+
+/// Snapshot of a note body before it got replaced
+#[derive(Queryable, Debug, Clone, PartialEq)]
+pub struct Revision {
+    pub id: i64,
+    pub metadata_uuid: String,
+    pub message_id: String,
+    pub text: Option<String>,
+    /// One of the RevisionKind strings
+    pub kind: String,
+    /// RFC 3339 date of the moment the body got replaced
+    pub created_at: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "revisions"]
+pub(crate) struct NewRevision<'a> {
+    metadata_uuid: &'a str,
+    message_id: &'a str,
+    text: Option<&'a str>,
+    kind: &'a str,
+    created_at: String,
+}
+
+impl<'a> NewRevision<'a> {
+    pub(crate) fn from_body(body: &'a Body, kind: RevisionKind) -> NewRevision<'a> {
+        NewRevision {
+            metadata_uuid: &body.metadata_uuid,
+            message_id: &body.message_id,
+            text: body.text.as_deref(),
+            kind: kind.as_str(),
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// A single line of the difference between two note versions
+#[derive(Debug, PartialEq, Clone)]
+pub enum DiffLine {
+    Unchanged(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Compares two versions of a note text line by line
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    diff::lines(old, new).into_iter().map(|line| match line {
+        diff::Result::Left(l) => DiffLine::Removed(l.to_string()),
+        diff::Result::Both(l, _) => DiffLine::Unchanged(l.to_string()),
+        diff::Result::Right(r) => DiffLine::Added(r.to_string()),
+    }).collect()
+}
+
+/// Returns the revision with the passed id if it belongs to the passed note
+pub(crate) fn revision_of_note(db_connection: &dyn DatabaseService,
+                               note: &LocalNote,
+                               id: i64) -> Result<Revision> {
+    match db_connection.fetch_revision(id)? {
+        Some(revision) if revision.metadata_uuid == note.metadata.uuid => Ok(revision),
+        _ => Err(NoteError::RevisionNotFound.into())
+    }
+}
+
+/// Replaces the content of the note with the content of the revision, the
+/// change is treated like a local edit and gets uploaded with the next sync
+pub(crate) fn revert(db_connection: &dyn DatabaseService,
+                     note: &LocalNote,
+                     id: i64,
+                     profile: &Profile) -> Result<LocalNote> {
+    if note.needs_merge() {
+        return Err(NoteError::NeedsMerge.into());
+    }
+
+    let revision = revision_of_note(db_connection, note, id)?;
+    let reverted = edited_note(note, &note.body[0], revision.text.as_deref().unwrap_or(""), profile)?;
+    db_connection.update(&reverted)?;
+    Ok(reverted)
+}
+
+#[cfg(test)]
+mod revisions_tests {
+    use super::*;
+    use builder::{NotesMetadataBuilder, BodyMetadataBuilder, ProfileBuilder};
+    use db::SqliteDBConnection;
+
+    /// Edits, sync replacements and merges should snapshot the replaced bodies
+    #[test]
+    fn snapshots() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_message_id("first").with_text("Title\nfirst").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+
+        // Changing flags only must not create a revision
+        let mut flagged = note.clone();
+        flagged.metadata.locally_deleted = true;
+        con.update(&flagged).unwrap();
+        assert!(con.fetch_revisions(&note.metadata.uuid).unwrap().is_empty());
+
+        let edited = edited_note(&note, &note.body[0], "Title\nsecond", &ProfileBuilder::for_tests().build()).unwrap();
+        con.update(&edited).unwrap();
+
+        let mut remote_body = BodyMetadataBuilder::new().with_message_id("remote").with_text("Title\nremote").build();
+        remote_body.metadata_uuid = note.metadata.uuid.clone();
+        con.replace_notes(&vec![remote_body], note.metadata.uuid.clone()).unwrap();
+
+        let revisions = con.fetch_revisions(&note.metadata.uuid).unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].kind, "sync");
+        assert_eq!(revisions[0].text, Some("Title\nsecond".to_string()));
+        assert_eq!(revisions[1].kind, "edit");
+        assert_eq!(revisions[1].message_id, "first");
+    }
+
+    /// A revert should be a local edit with the old content
+    #[test]
+    fn revert_revision() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_message_id("remote").with_uid(Some(1)).with_text("Title\nfirst").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+
+        let edited = edited_note(&note, &note.body[0], "Title\nsecond", &ProfileBuilder::for_tests().build()).unwrap();
+        con.update(&edited).unwrap();
+
+        let revision = con.fetch_revisions(&note.metadata.uuid).unwrap().remove(0);
+        let reverted = revert(&con, &edited, revision.id, &ProfileBuilder::for_tests().build()).unwrap();
+
+        let stored = con.fetch_single_note(&note.metadata.uuid).unwrap().unwrap();
+        assert_eq!(stored.body[0].text, Some("Title\nfirst".to_string()));
+        assert_eq!(stored.body[0].old_remote_message_id, Some(edited.body[0].message_id.clone()));
+        assert_eq!(stored.body[0].message_id, reverted.body[0].message_id);
+        assert_eq!(con.fetch_revisions(&note.metadata.uuid).unwrap().len(), 2);
+
+        let other = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("Other").build()
+        ];
+        assert!(revert(&con, &other, revision.id, &ProfileBuilder::for_tests().build()).is_err());
+    }
+
+    #[test]
+    fn diff() {
+        assert_eq!(diff_lines("a\nb", "a\nc"), vec![
+            DiffLine::Unchanged("a".to_string()),
+            DiffLine::Removed("b".to_string()),
+            DiffLine::Added("c".to_string()),
+        ]);
+    }
+}
@@ -13,15 +13,62 @@ table! {
         subfolder -> Text,
         locally_deleted -> Bool,
         new -> Bool,
-        date -> Timestamp,
+        date -> Text,
         uuid -> Text,
         mime_version -> Text,
+        modified_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    tags (tag, message_id) {
+        tag -> Text,
+        message_id -> Text,
+    }
+}
+
+table! {
+    revisions (id) {
+        id -> BigInt,
+        metadata_uuid -> Text,
+        message_id -> Text,
+        text -> Nullable<Text>,
+        kind -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    trash (id) {
+        id -> BigInt,
+        uuid -> Text,
+        subfolder -> Text,
+        date -> Text,
+        mime_version -> Text,
+        message_id -> Text,
+        text -> Nullable<Text>,
+        origin -> Text,
+        deleted_at -> Text,
+        created_date -> Nullable<Text>,
+    }
+}
+
+table! {
+    upgrades (name) {
+        name -> Text,
+        applied_at -> Text,
     }
 }
 
 joinable!(body -> metadata (metadata_uuid));
+joinable!(tags -> body (message_id));
 
 allow_tables_to_appear_in_same_query!(
     body,
     metadata,
+    tags,
+    revisions,
+    trash,
+    upgrades,
 );
@@ -0,0 +1,195 @@
+use db::DatabaseService;
+use error::Result;
+
+/// Marks the start of a matched term inside a snippet
+pub const MATCH_START: &str = "\u{2}";
+/// Marks the end of a matched term inside a snippet
+pub const MATCH_END: &str = "\u{3}";
+
+/// A note that matched a search query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub uuid: String,
+    pub folder: String,
+    pub subject: String,
+    /// Excerpt of the note text around the matches, matched terms are
+    /// enclosed by MATCH_START and MATCH_END
+    pub snippet: String,
+    /// Relevance of the hit, higher is better
+    pub rank: f64,
+}
+
+/// Settings that narrow down the results of a search
+#[derive(Debug, Default, Clone)]
+pub struct SearchOptions {
+    /// Only return notes inside these folders or their subfolders
+    pub folders: Vec<String>,
+    /// Maximum number of returned notes
+    pub limit: Option<usize>,
+}
+
+/// Searches the text of all notes that are not flagged for deletion, the best
+/// matching notes come first. Every note is returned at most once
+pub(crate) fn search(db_connection: &dyn DatabaseService,
+                     query: &str,
+                     options: &SearchOptions) -> Result<Vec<SearchHit>> {
+    let fts_query = match fts_query(query) {
+        Some(fts_query) => fts_query,
+        None => return Ok(vec![])
+    };
+
+    let folders: Vec<String> = options.folders.iter().map(|folder| full_folder_name(folder)).collect();
+    let mut seen_notes = std::collections::HashSet::new();
+
+    let hits = db_connection.search(&fts_query)?
+        .into_iter()
+        .filter(|hit| folders.is_empty() || folders.iter().any(|folder| is_inside_folder(&hit.folder, folder)))
+        // Unmerged notes can match with multiple bodies
+        .filter(|hit| seen_notes.insert(hit.uuid.clone()))
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(hits)
+}
+
+/// Converts the user input into an FTS5 query, all terms have to match
+///
+/// Terms inside double quotes are matched as phrase, terms that end with
+/// an asterisk are matched as prefix. Everything else is quoted, so that the
+/// input never contains FTS5 syntax errors
+pub(crate) fn fts_query(input: &str) -> Option<String> {
+    let mut terms: Vec<String> = vec![];
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let term: String = if c == '"' {
+            let phrase: String = chars.by_ref().take_while(|c| *c != '"').collect();
+            phrase
+        } else {
+            let mut word = c.to_string();
+            while let Some(next) = chars.peek() {
+                if next.is_whitespace() || *next == '"' {
+                    break;
+                }
+                word.push(chars.next().unwrap());
+            }
+            word
+        };
+
+        let is_prefix = term.ends_with('*') || chars.peek() == Some(&'*');
+        if chars.peek() == Some(&'*') {
+            chars.next();
+        }
+
+        let term = term.trim_end_matches('*').replace('"', "");
+        if term.trim().is_empty() {
+            continue;
+        }
+
+        terms.push(format!("\"{}\"{}", term, if is_prefix { "*" } else { "" }));
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Folders can be passed with or without the Notes root folder
+///
+/// Work -> Notes.Work
+pub(crate) fn full_folder_name(folder: &str) -> String {
+    if folder == "Notes" || folder.starts_with("Notes.") {
+        folder.to_string()
+    } else {
+        format!("Notes.{}", folder)
+    }
+}
+
+fn is_inside_folder(note_folder: &str, folder: &str) -> bool {
+    note_folder == folder || note_folder.starts_with(&format!("{}.", folder))
+}
+
+#[cfg(test)]
+mod search_tests {
+    use super::*;
+    use builder::{NotesMetadataBuilder, BodyMetadataBuilder};
+    use db::SqliteDBConnection;
+
+    #[test]
+    fn query_conversion() {
+        assert_eq!(fts_query("rust notes"), Some("\"rust\" \"notes\"".to_string()));
+        assert_eq!(fts_query("\"apple notes\" imap*"), Some("\"apple notes\" \"imap\"*".to_string()));
+        assert_eq!(fts_query("\"apple no\"*"), Some("\"apple no\"*".to_string()));
+        assert_eq!(fts_query("a-b NOT"), Some("\"a-b\" \"NOT\"".to_string()));
+        assert_eq!(fts_query(" * \"\" "), None);
+    }
+
+    #[test]
+    fn search_notes() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let work = note![
+            NotesMetadataBuilder::new().with_folder("Work".to_string()).build(),
+            BodyMetadataBuilder::new().with_text("Meeting\nDiscuss the quarterly report").build()
+        ];
+        let private = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("Shopping\nBuy paper for the report printer").build()
+        ];
+        let mut deleted = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("Old\nreport").build()
+        ];
+        deleted.metadata.locally_deleted = true;
+
+        con.insert_into_db(&work).unwrap();
+        con.insert_into_db(&private).unwrap();
+        con.insert_into_db(&deleted).unwrap();
+
+        let hits = search(&con, "report", &SearchOptions::default()).unwrap();
+        assert_eq!(hits.len(), 2);
+
+        let hits = search(&con, "quarter*", &SearchOptions::default()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].uuid, work.metadata.uuid);
+        assert_eq!(hits[0].subject, "Meeting");
+        assert!(hits[0].snippet.contains(&format!("{}quarterly{}", MATCH_START, MATCH_END)));
+
+        let hits = search(&con, "\"report printer\"", &SearchOptions::default()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].uuid, private.metadata.uuid);
+
+        let options = SearchOptions { folders: vec!["Work".to_string()], limit: None };
+        let hits = search(&con, "report", &options).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].folder, "Notes.Work");
+    }
+
+    /// The index should follow edits and deletions
+    #[test]
+    fn index_updates() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("Title\nbefore").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+
+        let mut edited = note;
+        edited.body[0].text = Some("Title\nafter".to_string());
+        con.update(&edited).unwrap();
+
+        assert!(search(&con, "before", &SearchOptions::default()).unwrap().is_empty());
+        assert_eq!(search(&con, "after", &SearchOptions::default()).unwrap().len(), 1);
+
+        con.delete(&edited).unwrap();
+        assert!(search(&con, "after", &SearchOptions::default()).unwrap().is_empty());
+    }
+}
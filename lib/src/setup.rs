@@ -0,0 +1,193 @@
+extern crate toml;
+
+use self::toml::Value;
+use self::toml::value::Table;
+use apple_imap::{MailService, MailServiceImpl};
+use config::{self, ConfigFile, ConfigFormat};
+use error::{Result, ProfileError, UpdateError};
+use profile::Profile;
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(target_family = "unix")]
+use std::fs::Permissions;
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+/// Pattern of the imap folders apple devices store notes in
+const NOTES_FOLDER_PATTERN: &str = "Notes*";
+
+/// Where the imap password is taken from
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordStorage {
+    /// Stored as plain text inside the config file
+    Plain(String),
+    /// Queried from the secret service with the attribute and value
+    SecretService { attribute: String, value: String },
+    /// Printed by a shell command, like a password manager
+    Command(String),
+}
+
+/// Answers of the init wizard
+#[derive(Debug, Clone)]
+pub struct Setup {
+    pub imap_server: String,
+    pub username: String,
+    pub email: String,
+    pub editor: String,
+    pub editor_arguments: Vec<String>,
+    pub password: PasswordStorage,
+}
+
+impl Setup {
+
+    /// Renders the answers as TOML config file
+    pub fn render(&self) -> String {
+        let mut account = Table::new();
+        account.insert("username".to_string(), Value::String(self.username.clone()));
+        account.insert("email".to_string(), Value::String(self.email.clone()));
+        account.insert("imap_server".to_string(), Value::String(self.imap_server.clone()));
+
+        match &self.password {
+            PasswordStorage::Plain(password) => {
+                account.insert("password_type".to_string(), Value::String("plain".to_string()));
+                account.insert("password".to_string(), Value::String(password.clone()));
+            }
+            PasswordStorage::SecretService { attribute, value } => {
+                account.insert("password_type".to_string(), Value::String("secret_service".to_string()));
+                account.insert("secret_service_attribute".to_string(), Value::String(attribute.clone()));
+                account.insert("secret_service_value".to_string(), Value::String(value.clone()));
+            }
+            PasswordStorage::Command(command) => {
+                account.insert("password_type".to_string(), Value::String("command".to_string()));
+                account.insert("password_command".to_string(), Value::String(command.clone()));
+            }
+        }
+
+        let mut editor = Table::new();
+        editor.insert("command".to_string(), Value::String(self.editor.clone()));
+        editor.insert("arguments".to_string(), Value::Array(
+            self.editor_arguments.iter().map(|argument| Value::String(argument.clone())).collect()
+        ));
+
+        let mut root = Table::new();
+        root.insert("account".to_string(), Value::Table(account));
+        root.insert("editor".to_string(), Value::Table(editor));
+
+        toml::to_string(&Value::Table(root)).unwrap_or_default()
+    }
+
+    /// Reads the rendered config back, so that the wizard never writes a config
+    /// that would not load
+    fn to_profile(&self) -> Result<Profile> {
+        config::parse(&self.render(), ConfigFormat::Toml)
+            .map_err(|issues| ProfileError::InvalidConfig(issues).into())
+    }
+
+    /// Logs into the imap server and returns the folders that contain notes
+    pub fn verify_login(&self) -> Result<Vec<String>> {
+        let profile = self.to_profile()?;
+        let mut mail_service = MailServiceImpl::new_with_login(&profile)?;
+        let folders = mail_service.list_folders(NOTES_FOLDER_PATTERN)?;
+        mail_service.logout()?;
+        Ok(folders)
+    }
+
+    /// Writes the config file, an existing file only gets replaced with overwrite.
+    /// On unix only the owner is able to read the file, it may contain the password
+    pub fn write(&self, file: &ConfigFile, overwrite: bool) -> Result<()> {
+        if file.path.exists() && !overwrite {
+            return Err(UpdateError::IoError(format!("{} already exists", file.path.display())).into());
+        }
+
+        self.to_profile()?;
+
+        if let Some(dir) = file.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(target_family = "unix")]
+        options.mode(0o600);
+
+        let mut config_file = options.open(&file.path)?;
+        // The mode only applies to new files, a replaced config keeps its permissions
+        #[cfg(target_family = "unix")]
+        config_file.set_permissions(Permissions::from_mode(0o600))?;
+        config_file.write_all(self.render().as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod setup_tests {
+    use super::*;
+
+    fn setup(password: PasswordStorage) -> Setup {
+        Setup {
+            imap_server: "imap.test.de".to_string(),
+            username: "test".to_string(),
+            email: "test@test.de".to_string(),
+            editor: "vi".to_string(),
+            editor_arguments: vec!["-n".to_string()],
+            password
+        }
+    }
+
+    /// Every password storage should result in a config that loads
+    #[test]
+    fn rendered_config_loads() {
+        let profile = setup(PasswordStorage::Plain("secret".to_string())).to_profile().unwrap();
+        assert_eq!(profile.password, Some("secret".to_string()));
+        assert_eq!(profile.domain, "test.de");
+
+        let profile = setup(PasswordStorage::SecretService { attribute: "mail".to_string(), value: "imap".to_string() })
+            .to_profile().unwrap();
+        assert_eq!(profile.password_type, "SECRET_SERVICE");
+        assert_eq!(profile.secret_service_value, Some("imap".to_string()));
+
+        let profile = setup(PasswordStorage::Command("echo secret".to_string())).to_profile().unwrap();
+        assert_eq!(profile.password_command, Some("echo secret".to_string()));
+        #[cfg(target_family = "unix")]
+        assert_eq!(profile.get_password().unwrap(), "secret");
+    }
+
+    #[test]
+    fn write_config() {
+        let dir = std::env::temp_dir().join("apnotes_setup");
+        let _ = std::fs::remove_dir_all(&dir);
+        let file = ConfigFile { path: dir.join("apnotes").join(config::CONFIG_FILE), format: ConfigFormat::Toml, exists: false };
+
+        let setup = setup(PasswordStorage::Command("pass show mail".to_string()));
+        setup.write(&file, false).unwrap();
+        assert!(setup.write(&file, false).is_err());
+        setup.write(&file, true).unwrap();
+
+        assert!(config::check(&file).unwrap().is_empty());
+    }
+
+    /// The config may contain the password, other users must not be able to read it
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn config_permissions() {
+        let dir = std::env::temp_dir().join("apnotes_setup_permissions");
+        let _ = std::fs::remove_dir_all(&dir);
+        let file = ConfigFile { path: dir.join(config::CONFIG_FILE), format: ConfigFormat::Toml, exists: false };
+        let mode = |file: &ConfigFile| std::fs::metadata(&file.path).unwrap().permissions().mode() & 0o777;
+
+        let setup = setup(PasswordStorage::Plain("secret".to_string()));
+        setup.write(&file, false).unwrap();
+        assert_eq!(mode(&file), 0o600);
+
+        std::fs::set_permissions(&file.path, Permissions::from_mode(0o644)).unwrap();
+        setup.write(&file, true).unwrap();
+        assert_eq!(mode(&file), 0o600);
+    }
+
+    #[test]
+    fn invalid_email() {
+        let mut setup = setup(PasswordStorage::Plain("secret".to_string()));
+        setup.email = "test".to_string();
+        assert!(setup.to_profile().is_err());
+    }
+}
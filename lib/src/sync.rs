@@ -9,10 +9,10 @@ use self::itertools::Itertools;
 use self::log::*;
 use std::collections::HashSet;
 use sync::UpdateAction::{AddLocally, UpdateRemotely, UpdateLocally, AddRemotely, DeleteLocally, DeleteRemote, Merge};
-use model::{NotesMetadata, Body};
+use model::{NotesMetadata, Body, header_date};
 use error::UpdateError::SyncError;
 use error::UpdateError;
-use apple_imap::{MailService};
+use apple_imap::{MailService, MailServiceFactory};
 use db::{DatabaseService};
 use converter::convert2md;
 use notes::localnote::{LocalNote};
@@ -25,9 +25,10 @@ use notes::traits::mergeable_note_body::MergeableNoteBody;
 use util::filter_none;
 use std::fmt::{Display, Formatter};
 use colored::Colorize;
-use chrono::DateTime;
 use profile::Profile;
 use ::error::Result;
+use trash::TrashOrigin;
+use hooks::{HookEvent, HookNote};
 
 pub struct SyncResult {
     pub action: String,
@@ -88,11 +89,15 @@ pub enum MergeMethod {
     AppendLocally,
 }
 
-pub fn sync_notes(db_connection: &Box<dyn DatabaseService + Send>, profile: &Profile)
-    -> Result<Vec<SyncResult>> {
-    ::apple_imap::MailServiceImpl::new_with_login(profile)
+/// Hook event of a note that a sync changed locally, the caller runs the hook
+/// once the database is not locked anymore
+pub type NoteEvent = (HookEvent, HookNote);
+
+pub fn sync_notes(db_connection: &Box<dyn DatabaseService + Send>, profile: &Profile, connect: &MailServiceFactory)
+    -> Result<(Vec<SyncResult>, Vec<NoteEvent>)> {
+    connect(profile)
         .and_then(|mut imap_service| {
-            sync(&mut imap_service, db_connection).map(|result| (result,imap_service))
+            sync(imap_service.as_mut(), db_connection).map(|result| (result,imap_service))
         })
         .and_then(|(result, mut imap_service)| {
             imap_service.logout().map(|_| result).map_err(|e| e.into())
@@ -274,8 +279,8 @@ fn get_needs_merge_basic<'a>(remote_note_header: Option<&'a RemoteNoteHeaderColl
     }
 }
 
-pub fn sync<T>(imap_session: &mut dyn MailService<T>, db_connection: &Box<dyn DatabaseService + Send>)
-    -> Result<Vec<SyncResult>>
+pub fn sync(imap_session: &mut dyn MailService, db_connection: &Box<dyn DatabaseService + Send>)
+    -> Result<(Vec<SyncResult>, Vec<NoteEvent>)>
 
 {
     let headers = imap_session.fetch_headers()?;
@@ -284,7 +289,7 @@ pub fn sync<T>(imap_session: &mut dyn MailService<T>, db_connection: &Box<dyn Da
 
     let actions =
         get_sync_actions(&grouped_not_headers, &fetches);
-    let results = process_actions(imap_session, db_connection, &actions);
+    let (results, note_events) = process_actions(imap_session, db_connection, &actions);
 
     for (action, subject, result) in &results {
 
@@ -301,16 +306,18 @@ pub fn sync<T>(imap_session: &mut dyn MailService<T>, db_connection: &Box<dyn Da
         .map(|(action,subject,result)| SyncResult { action: action.to_string(),subject, result } )
         .collect();
 
-    Ok(results)
+    Ok((results, note_events))
 }
 
-pub fn process_actions<'a, T>(
-    imap_connection: &mut dyn MailService<T>,
+/// Runs every action. Returns the results together with the hook events of every
+/// action that changed a local note successfully
+pub fn process_actions<'a>(
+    imap_connection: &mut dyn MailService,
     db_connection: &Box<dyn DatabaseService + Send>,
-    actions: &'a Vec<UpdateAction<'a>>) -> Vec<(&'a UpdateAction<'a>,String, Result<()>)>
+    actions: &'a Vec<UpdateAction<'a>>) -> (Vec<(&'a UpdateAction<'a>,String, Result<()>)>, Vec<NoteEvent>)
 
 {
-    let result = actions
+    let result: Vec<(&'a UpdateAction<'a>,String, Result<()>)> = actions
         .iter()
         .map(|action| {
             let result = match action {
@@ -325,10 +332,21 @@ pub fn process_actions<'a, T>(
         }
         ).collect();
 
-    return result;
+    let note_events = result.iter()
+        .filter(|(_, _, result)| result.is_ok())
+        .filter_map(|(action, _, _)| match action {
+            UpdateAction::AddLocally(note_headers) => Some((HookEvent::NoteCreated, HookNote::of(*note_headers))),
+            UpdateAction::UpdateLocally(note_headers) => Some((HookEvent::NoteUpdated, HookNote::of(*note_headers))),
+            UpdateAction::DeleteLocally(local_note) => Some((HookEvent::NoteDeleted, HookNote::of(*local_note))),
+            UpdateAction::Merge(_, note_headers) => Some((HookEvent::MergeNeeded, HookNote::of(*note_headers))),
+            _ => None
+        })
+        .collect();
+
+    return (result, note_events);
 }
 
-fn process_add_locally<'a,T>(imap_connection: &mut dyn MailService<T>,
+fn process_add_locally<'a>(imap_connection: &mut dyn MailService,
                                db_connection: &Box<dyn DatabaseService + Send>,
                                action: &'a UpdateAction,
                                noteheaders: &RemoteNoteHeaderCollection)
@@ -342,7 +360,7 @@ fn process_add_locally<'a,T>(imap_connection: &mut dyn MailService<T>,
     (action, noteheaders.first_subject(), result)
 }
 
-fn process_update_locally<'a,T>(imap_connection: &mut dyn MailService<T>,
+fn process_update_locally<'a>(imap_connection: &mut dyn MailService,
                                    db_connection: &Box<dyn DatabaseService + Send>,
                                    action: &'a UpdateAction,
                                    new_note_bodies: &RemoteNoteHeaderCollection)
@@ -385,10 +403,18 @@ fn process_update_locally<'a,T>(imap_connection: &mut dyn MailService<T>,
     // Update the modification date in metadata object
     let date = new_note_bodies
         .iter()
-        .max_by_key(|k| DateTime::parse_from_rfc2822(&k.headers.date()).unwrap().timestamp());
+        .filter_map(|k| header_date(&k.headers, "Date"))
+        .max();
+
+    // Notes that got stored before the creation date was kept get it from the server
+    let created_date = new_note_bodies
+        .iter()
+        .filter_map(|k| header_date(&k.headers, "X-Mail-Created-Date"))
+        .min();
 
     let mut note = db_connection.fetch_single_note(uuid).unwrap().unwrap();
-    note.metadata.date = date.unwrap().headers.date();
+    note.metadata.date = date.or(note.metadata.date);
+    note.metadata.created_date = note.metadata.created_date.or(created_date);
     let new_folder = new_note_bodies.iter().last().unwrap().folder.clone();
     note.metadata.subfolder = new_folder;
     db_connection.update(&note).unwrap();
@@ -396,7 +422,7 @@ fn process_update_locally<'a,T>(imap_connection: &mut dyn MailService<T>,
     (action, new_note_bodies.first_subject(), result)
 }
 
-fn process_delete_remotely<'a, T>(imap_connection: &mut dyn MailService<T>,
+fn process_delete_remotely<'a>(imap_connection: &mut dyn MailService,
                                      db_connection: &Box<dyn DatabaseService + Send>,
                                      action: &'a UpdateAction,
                                      localnote: &LocalNote)
@@ -405,7 +431,7 @@ fn process_delete_remotely<'a, T>(imap_connection: &mut dyn MailService<T>,
 {
     let result = imap_connection
         .delete_message(localnote)
-        .and_then(|_| db_connection.delete(&localnote)
+        .and_then(|_| db_connection.move_to_trash(&localnote, TrashOrigin::Local)
             .map_err(|e| e.into())
         );
     (action,localnote.first_subject(), result)
@@ -419,12 +445,12 @@ fn process_delete_locally<'a>(db_connection: &Box<dyn DatabaseService + Send>,
     //TODO what happens if remote umerged note gets deleted only delete this body
     // what happens if to be deleted note with message-id:x has merged un-updated
     //content on local side
-    let result = db_connection.delete(b)
+    let result = db_connection.move_to_trash(b, TrashOrigin::Remote)
         .map_err(|e| e.into());
     (action,b.metadata.first_subject(), result)
 }
 
-fn update_message_remotely<'a, T>(imap_connection: &mut dyn MailService<T>,
+fn update_message_remotely(imap_connection: &mut dyn MailService,
                                      db_connection: &Box<dyn DatabaseService + Send>,
                                      localnote: &LocalNote)
     -> Result<()>
@@ -446,9 +472,10 @@ fn update_message_remotely<'a, T>(imap_connection: &mut dyn MailService<T>,
                                 subfolder: localnote.metadata.subfolder.clone(),
                                 locally_deleted: localnote.metadata.locally_deleted,
                                 new: false,
-                                date: localnote.metadata.date.clone(),
+                                date: localnote.metadata.date,
                                 uuid:localnote.metadata.uuid.clone(),
-                                mime_version: localnote.metadata.mime_version.clone()
+                                mime_version: localnote.metadata.mime_version.clone(),
+                                created_date: localnote.metadata.created_date
                             },
                             Body {
                                 old_remote_message_id: None,
@@ -463,7 +490,7 @@ fn update_message_remotely<'a, T>(imap_connection: &mut dyn MailService<T>,
         })
 }
 
-fn process_merge<'a,T>(imap_connection: &mut dyn MailService<T>,
+fn process_merge<'a>(imap_connection: &mut dyn MailService,
                                   db_connection: &Box<dyn DatabaseService + Send>,
                                   action: &'a UpdateAction,
                                   new_notes: &Vec<RemoteNoteMetaData>)
@@ -517,7 +544,7 @@ fn process_merge<'a,T>(imap_connection: &mut dyn MailService<T>,
     }
 }
 
-fn localnote_from_remote_header<T>(imap_connection: &mut dyn MailService<T>, noteheaders: &Vec<RemoteNoteMetaData>)
+pub(crate) fn localnote_from_remote_header(imap_connection: &mut dyn MailService, noteheaders: &Vec<RemoteNoteMetaData>)
     -> Result<LocalNote>
 
 {
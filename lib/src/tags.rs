@@ -0,0 +1,72 @@
+extern crate regex;
+
+use self::regex::Regex;
+use std::collections::BTreeSet;
+
+/// Extracts all hashtags like #work or #project-x out of the passed text
+///
+/// Tags are case insensitive and returned in lowercase without the leading #,
+/// every tag is returned once. Markdown headings, numbers like #1 and url
+/// fragments are not treated as tags
+pub fn extract_tags(text: &str) -> Vec<String> {
+    let tag_regex = Regex::new(r"(?:^|[^\w#&/])#([\w-]+)").unwrap();
+
+    let tags: BTreeSet<String> = tag_regex.captures_iter(text)
+        .map(|captures| captures[1].trim_end_matches('-').to_lowercase())
+        .filter(|tag| tag.chars().any(|c| !c.is_numeric() && c != '-' && c != '_'))
+        .collect();
+
+    tags.into_iter().collect()
+}
+
+/// Normalizes user input like #Work to the stored representation
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+#[cfg(test)]
+mod tags_tests {
+    use super::*;
+    use std::collections::HashSet;
+    use builder::{NotesMetadataBuilder, BodyMetadataBuilder};
+    use db::{DatabaseService, SqliteDBConnection};
+
+    #[test]
+    fn extract() {
+        let text = "# Heading\nShopping list #groceries #Home\nSee #1 and https://test.de/#anchor &#39;\n#home again, #project-x.";
+        assert_eq!(extract_tags(text), vec!["groceries", "home", "project-x"]);
+    }
+
+    /// The tags table should follow inserts, edits and deletions
+    #[test]
+    fn tags_table() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let first = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("First\n#work #urgent").build()
+        ];
+        let second = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("Second\n#work").build()
+        ];
+        con.insert_into_db(&first).unwrap();
+        con.insert_into_db(&second).unwrap();
+
+        assert_eq!(con.fetch_tags().unwrap(), vec![("work".to_string(), 2), ("urgent".to_string(), 1)]);
+        assert_eq!(con.fetch_uuids_with_tag("urgent").unwrap(), set![first.metadata.uuid.clone()]);
+
+        let mut edited = first;
+        edited.body[0].text = Some("First\n#private".to_string());
+        con.update(&edited).unwrap();
+        con.delete(&second).unwrap();
+
+        assert_eq!(con.fetch_tags().unwrap(), vec![("private".to_string(), 1)]);
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(normalize_tag(" #Work"), "work");
+        assert_eq!(normalize_tag("work"), "work");
+    }
+}
@@ -0,0 +1,214 @@
+use schema::trash;
+use model::{NotesMetadata, Body};
+use notes::localnote::LocalNote;
+use db::DatabaseService;
+use restore::{restore_note, ClashStrategy};
+use error::{Result, NoteError};
+use profile::Profile;
+use chrono::{Utc, Duration};
+use util::{format_date, parse_date};
+
+/// Retention period that is used if the config file does not contain one
+pub const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+/// Describes where a trashed note got deleted
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TrashOrigin {
+    /// The note got flagged for deletion locally and was deleted with a sync
+    Local,
+    /// The note got deleted on another device
+    Remote,
+}
+
+impl TrashOrigin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrashOrigin::Local => "local",
+            TrashOrigin::Remote => "remote",
+        }
+    }
+}
+
+/// A single trashed note body, notes that needed a merge occupy multiple rows
+#[derive(Queryable, Debug, Clone)]
+pub(crate) struct TrashEntry {
+    pub uuid: String,
+    pub subfolder: String,
+    pub date: String,
+    pub mime_version: String,
+    pub message_id: String,
+    pub text: Option<String>,
+    pub origin: String,
+    pub deleted_at: String,
+    pub created_date: Option<String>,
+}
+
+#[derive(Insertable)]
+#[table_name = "trash"]
+pub(crate) struct NewTrashEntry<'a> {
+    uuid: &'a str,
+    subfolder: &'a str,
+    date: String,
+    mime_version: &'a str,
+    message_id: &'a str,
+    text: Option<&'a str>,
+    origin: &'a str,
+    deleted_at: String,
+    created_date: Option<String>,
+}
+
+impl<'a> NewTrashEntry<'a> {
+    pub(crate) fn from_note(note: &'a LocalNote, origin: TrashOrigin) -> Vec<NewTrashEntry<'a>> {
+        let deleted_at = Utc::now().to_rfc3339();
+        note.body.iter().map(|body| NewTrashEntry {
+            uuid: &note.metadata.uuid,
+            subfolder: &note.metadata.subfolder,
+            date: note.metadata.date.as_ref().map(format_date).unwrap_or_default(),
+            mime_version: &note.metadata.mime_version,
+            message_id: &body.message_id,
+            text: body.text.as_deref(),
+            origin: origin.as_str(),
+            deleted_at: deleted_at.clone(),
+            created_date: note.metadata.created_date.as_ref().map(format_date),
+        }).collect()
+    }
+}
+
+/// A deleted note inside the trash
+#[derive(Debug, Clone)]
+pub struct TrashedNote {
+    pub note: LocalNote,
+    /// One of the TrashOrigin strings
+    pub origin: String,
+    /// RFC 3339 date of the deletion
+    pub deleted_at: String,
+}
+
+/// Groups the trash rows by note, the order of the rows is kept
+pub(crate) fn group_entries(entries: Vec<TrashEntry>) -> Vec<TrashedNote> {
+    let mut notes: Vec<TrashedNote> = vec![];
+
+    for entry in entries {
+        let body = Body {
+            old_remote_message_id: None,
+            message_id: entry.message_id,
+            text: entry.text,
+            uid: None,
+            metadata_uuid: entry.uuid.clone(),
+        };
+
+        let uuid = &body.metadata_uuid;
+        match notes.iter_mut().find(|trashed| &trashed.note.metadata.uuid == uuid) {
+            Some(trashed) => trashed.note.body.push(body),
+            None => notes.push(TrashedNote {
+                note: LocalNote {
+                    metadata: NotesMetadata {
+                        subfolder: entry.subfolder,
+                        locally_deleted: false,
+                        new: false,
+                        date: parse_date(&entry.date),
+                        uuid: entry.uuid,
+                        mime_version: entry.mime_version,
+                        created_date: entry.created_date.as_deref().and_then(parse_date),
+                    },
+                    body: vec![body],
+                },
+                origin: entry.origin,
+                deleted_at: entry.deleted_at,
+            })
+        }
+    }
+
+    notes
+}
+
+/// Returns the trashed notes with the passed uuids, fails if one of them is not inside the trash
+pub(crate) fn trashed_notes(db_connection: &dyn DatabaseService, uuids: &[String]) -> Result<Vec<LocalNote>> {
+    let trashed: Vec<LocalNote> = db_connection.fetch_trash()?
+        .into_iter()
+        .map(|trashed| trashed.note)
+        .filter(|note| uuids.contains(&note.metadata.uuid))
+        .collect();
+
+    if let Some(missing) = uuids.iter().find(|uuid| trashed.iter().all(|note| &note.metadata.uuid != *uuid)) {
+        error!("Note {} is not inside the trash", missing);
+        return Err(NoteError::NoteNotFound.into());
+    }
+
+    Ok(trashed)
+}
+
+/// Moves the trashed note back into the notes, it gets uploaded with the next sync.
+/// If a note with the same uuid exists both are kept. Returns the restored note
+pub(crate) fn restore_from_trash(db_connection: &dyn DatabaseService,
+                                 uuid: &str,
+                                 profile: &Profile) -> Result<LocalNote> {
+    let note = trashed_notes(db_connection, &[uuid.to_string()])?.remove(0);
+    let restored = restore_note(db_connection, note, ClashStrategy::KeepBoth, profile)?;
+    db_connection.delete_from_trash(uuid)?;
+    Ok(restored)
+}
+
+/// Removes all notes from the trash that got deleted before the retention period,
+/// returns the number of removed notes
+pub(crate) fn purge_expired(db_connection: &dyn DatabaseService, profile: &Profile) -> Result<usize> {
+    let deleted_before = (Utc::now() - Duration::days(profile.trash_retention_days)).to_rfc3339();
+    db_connection.purge_trash(Some(&deleted_before)).map_err(|e| e.into())
+}
+
+#[cfg(test)]
+mod trash_tests {
+    use super::*;
+    use builder::{NotesMetadataBuilder, BodyMetadataBuilder, ProfileBuilder};
+    use db::SqliteDBConnection;
+
+    /// Trashed notes should be restorable as new notes
+    #[test]
+    fn trash_and_restore() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().with_uuid("1").with_folder("Work".to_string()).build(),
+            BodyMetadataBuilder::new().with_uid(Some(3)).with_text("first").build(),
+            BodyMetadataBuilder::new().with_uid(Some(4)).with_text("second").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+        con.move_to_trash(&note, TrashOrigin::Remote).unwrap();
+
+        assert!(con.fetch_single_note("1").unwrap().is_none());
+
+        let trashed = con.fetch_trash().unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].origin, "remote");
+        assert_eq!(trashed[0].note.body.len(), 2);
+        assert_eq!(trashed[0].note.metadata.subfolder, "Notes.Work");
+
+        assert!(trashed_notes(&con, &["2".to_string()]).is_err());
+        assert_eq!(trashed_notes(&con, &["1".to_string()]).unwrap().len(), 1);
+        let restored = restore_from_trash(&con, "1", &ProfileBuilder::for_tests().build()).unwrap();
+        assert!(con.fetch_trash().unwrap().is_empty());
+        assert_eq!(con.fetch_single_note("1").unwrap().unwrap(), restored);
+
+        assert!(restored.metadata.new);
+        assert_eq!(restored.body.len(), 2);
+        assert!(restored.body.iter().all(|body| body.uid.is_none()));
+    }
+
+    #[test]
+    fn purge() {
+        let con = SqliteDBConnection::in_memory().unwrap();
+
+        let note = note![
+            NotesMetadataBuilder::new().build(),
+            BodyMetadataBuilder::new().with_text("text").build()
+        ];
+        con.insert_into_db(&note).unwrap();
+        con.move_to_trash(&note, TrashOrigin::Local).unwrap();
+
+        assert_eq!(purge_expired(&con, &ProfileBuilder::for_tests().build()).unwrap(), 0);
+
+        let profile = ProfileBuilder::for_tests().with_trash_retention_days(-1).build();
+        assert_eq!(purge_expired(&con, &profile).unwrap(), 1);
+        assert!(con.fetch_trash().unwrap().is_empty());
+    }
+}
@@ -2,6 +2,7 @@ extern crate regex;
 
 use uuid::Uuid;
 use regex::Regex;
+use chrono::{DateTime, NaiveDateTime, Utc};
 
 pub fn generate_uuid() -> String {
     Uuid::new_v4().to_string().to_uppercase()
@@ -21,3 +22,17 @@ pub fn filter_none<S>(e: Option<S>) -> Option<S> {
         None
     }
 }
+
+/// Parses RFC 2822 dates like the ones inside the mail headers and RFC 3339 dates,
+/// returns the date in UTC or None if the date is malformed
+pub fn parse_date(date: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc2822(date.trim())
+        .or_else(|_| DateTime::parse_from_rfc3339(date.trim()))
+        .map(|date| date.naive_utc())
+        .ok()
+}
+
+/// Formats a UTC date the way dates are written inside the mail headers
+pub fn format_date(date: &NaiveDateTime) -> String {
+    DateTime::<Utc>::from_utc(*date, Utc).to_rfc2822()
+}
@@ -0,0 +1,4 @@
+DROP TRIGGER body_fts_update;
+DROP TRIGGER body_fts_delete;
+DROP TRIGGER body_fts_insert;
+DROP TABLE body_fts;
@@ -0,0 +1,22 @@
+-- Full-text index over the note bodies, kept up to date by triggers
+
+CREATE VIRTUAL TABLE body_fts USING fts5(
+    message_id UNINDEXED,
+    text
+);
+
+INSERT INTO body_fts (message_id, text)
+    SELECT message_id, COALESCE(text, '') FROM body;
+
+CREATE TRIGGER body_fts_insert AFTER INSERT ON body BEGIN
+    INSERT INTO body_fts (message_id, text) VALUES (new.message_id, COALESCE(new.text, ''));
+END;
+
+CREATE TRIGGER body_fts_delete AFTER DELETE ON body BEGIN
+    DELETE FROM body_fts WHERE message_id = old.message_id;
+END;
+
+CREATE TRIGGER body_fts_update AFTER UPDATE ON body BEGIN
+    DELETE FROM body_fts WHERE message_id = old.message_id;
+    INSERT INTO body_fts (message_id, text) VALUES (new.message_id, COALESCE(new.text, ''));
+END;
@@ -0,0 +1,2 @@
+DROP INDEX tags_message_id;
+DROP TABLE tags;
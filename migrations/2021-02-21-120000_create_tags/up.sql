@@ -0,0 +1,11 @@
+-- Hashtags that are contained inside the note bodies, existing bodies
+-- are getting indexed after the migration ran
+
+CREATE TABLE tags (
+    tag VARCHAR NOT NULL,
+    message_id VARCHAR NOT NULL,
+    PRIMARY KEY(tag, message_id),
+    FOREIGN KEY(message_id) REFERENCES body(message_id) ON DELETE CASCADE
+);
+
+CREATE INDEX tags_message_id ON tags(message_id);
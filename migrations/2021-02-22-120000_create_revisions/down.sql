@@ -0,0 +1,2 @@
+DROP INDEX revisions_metadata_uuid;
+DROP TABLE revisions;
@@ -0,0 +1,12 @@
+-- Snapshots of note bodies that got replaced by a local edit, a sync or a merge
+
+CREATE TABLE revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    metadata_uuid VARCHAR NOT NULL,
+    message_id VARCHAR NOT NULL,
+    text VARCHAR,
+    kind VARCHAR NOT NULL,
+    created_at VARCHAR NOT NULL
+);
+
+CREATE INDEX revisions_metadata_uuid ON revisions(metadata_uuid);
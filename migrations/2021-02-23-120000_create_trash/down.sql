@@ -0,0 +1,2 @@
+DROP INDEX trash_uuid;
+DROP TABLE trash;
@@ -0,0 +1,15 @@
+-- Notes that got deleted locally or on another device, one row per note body
+
+CREATE TABLE trash (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    uuid VARCHAR NOT NULL,
+    subfolder VARCHAR NOT NULL,
+    date VARCHAR NOT NULL,
+    mime_version VARCHAR NOT NULL,
+    message_id VARCHAR NOT NULL,
+    text VARCHAR,
+    origin VARCHAR NOT NULL,
+    deleted_at VARCHAR NOT NULL
+);
+
+CREATE INDEX trash_uuid ON trash(uuid);
@@ -0,0 +1,45 @@
+DROP TABLE upgrades;
+
+-- DROP COLUMN needs SQLite 3.35, the tables are rebuilt without the new columns instead
+
+CREATE TABLE trash_backup (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    uuid VARCHAR NOT NULL,
+    subfolder VARCHAR NOT NULL,
+    date VARCHAR NOT NULL,
+    mime_version VARCHAR NOT NULL,
+    message_id VARCHAR NOT NULL,
+    text VARCHAR,
+    origin VARCHAR NOT NULL,
+    deleted_at VARCHAR NOT NULL
+);
+INSERT INTO trash_backup SELECT id, uuid, subfolder, date, mime_version, message_id, text, origin, deleted_at FROM trash;
+DROP INDEX trash_uuid;
+DROP TABLE trash;
+ALTER TABLE trash_backup RENAME TO trash;
+CREATE INDEX trash_uuid ON trash(uuid);
+
+-- Foreign keys can not be disabled inside the migration transaction, the bodies
+-- and their tags are put aside while the metadata table gets rebuilt
+CREATE TEMPORARY TABLE body_backup AS SELECT * FROM body;
+CREATE TEMPORARY TABLE tags_backup AS SELECT * FROM tags;
+DELETE FROM body;
+
+DROP INDEX metadata_modified_at;
+
+CREATE TABLE metadata_backup (
+    subfolder VARCHAR NOT NULL,
+    locally_deleted BOOLEAN NOT NULL,
+    new BOOLEAN NOT NULL,
+    date TIMESTAMP NOT NULL,
+    uuid VARCHAR PRIMARY KEY NOT NULL,
+    mime_version VARCHAR NOT NULL
+);
+INSERT INTO metadata_backup SELECT subfolder, locally_deleted, new, date, uuid, mime_version FROM metadata;
+DROP TABLE metadata;
+ALTER TABLE metadata_backup RENAME TO metadata;
+
+INSERT INTO body SELECT * FROM body_backup;
+INSERT INTO tags SELECT * FROM tags_backup;
+DROP TABLE body_backup;
+DROP TABLE tags_backup;
@@ -0,0 +1,16 @@
+-- Modification and creation date of the notes in UTC. The date column keeps the
+-- RFC 2822 modification date for older versions
+ALTER TABLE metadata ADD COLUMN modified_at TIMESTAMP;
+ALTER TABLE metadata ADD COLUMN created_at TIMESTAMP;
+
+CREATE INDEX metadata_modified_at ON metadata (modified_at);
+
+-- Trashed notes keep their creation date as RFC 2822 string like the date column
+ALTER TABLE trash ADD COLUMN created_date VARCHAR;
+
+-- Conversions of existing rows that SQL can not express, like parsing the RFC 2822
+-- dates into the timestamp columns, run once on connect and are recorded here
+CREATE TABLE upgrades (
+    name VARCHAR PRIMARY KEY NOT NULL,
+    applied_at VARCHAR NOT NULL
+);